@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS job_visitors;
+DROP TABLE IF EXISTS job_response_api_keys;
+DROP TABLE IF EXISTS job_response_channels;
+DROP TABLE IF EXISTS job_response_stats;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS jobs;
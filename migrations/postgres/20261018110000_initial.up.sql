@@ -0,0 +1,55 @@
+-- The schema of the SQLite migrations up to 20261018110000, in one step, since
+-- PostgreSQL databases never held the data that the SQLite history reconciles.
+--
+-- Later migrations have the version of the SQLite migration they mirror.
+CREATE TABLE jobs
+(
+    job_id       TEXT PRIMARY KEY NOT NULL,
+    shortcode    TEXT UNIQUE NOT NULL,
+    escrow_id    TEXT NOT NULL,
+    manifest_url TEXT,
+    posted       BIGINT NOT NULL,
+    expires      TIMESTAMP,
+    password     TEXT,
+    responses    BIGINT NOT NULL
+);
+
+CREATE TABLE api_keys
+(
+    api_key BYTEA PRIMARY KEY
+);
+
+-- Hourly response buckets for each job.
+CREATE TABLE job_response_stats
+(
+    shortcode TEXT NOT NULL,
+    bucket    BIGINT NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, bucket)
+);
+
+-- Responses split by access channel (html, raw, api).
+CREATE TABLE job_response_channels
+(
+    shortcode TEXT NOT NULL,
+    channel   TEXT NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, channel)
+);
+
+-- Responses made through the API, per API key.
+CREATE TABLE job_response_api_keys
+(
+    shortcode TEXT NOT NULL,
+    api_key   BYTEA NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, api_key)
+);
+
+-- Visitor fingerprints used to approximate unique visitors.
+CREATE TABLE job_visitors
+(
+    shortcode TEXT NOT NULL,
+    visitor   TEXT NOT NULL,
+    PRIMARY KEY (shortcode, visitor)
+);
@@ -0,0 +1,5 @@
+-- Indexes for the queries which run on every sync, purge and rollup.
+CREATE INDEX IF NOT EXISTS jobs_posted ON jobs (posted);
+CREATE INDEX IF NOT EXISTS jobs_expires ON jobs (expires);
+CREATE INDEX IF NOT EXISTS jobs_escrow_id ON jobs (escrow_id);
+CREATE INDEX IF NOT EXISTS job_response_stats_bucket ON job_response_stats (bucket);
@@ -0,0 +1,3 @@
+-- The original case of the addresses is not kept. Lower case addresses are
+-- still valid escrow ids, so there is nothing to undo.
+SELECT 1;
@@ -0,0 +1,5 @@
+-- Store escrow addresses in lower case, so that they can be looked up
+-- whether they were posted checksummed or not.
+UPDATE jobs
+SET escrow_id = lower(escrow_id)
+WHERE escrow_id ~ '^0[xX][0-9a-fA-F]{40}$';
@@ -0,0 +1,5 @@
+-- The manifest hash recorded by the escrow, and whether the manifest matches it.
+ALTER TABLE jobs ADD COLUMN manifest_hash TEXT;
+ALTER TABLE jobs ADD COLUMN manifest_status TEXT NOT NULL DEFAULT 'unverifiable';
+ALTER TABLE jobs ADD COLUMN manifest_checked BIGINT;
+CREATE INDEX jobs_manifest_status ON jobs (manifest_status, manifest_checked);
@@ -0,0 +1,3 @@
+-- The HUMAN task type of each job, from its manifest's request_type.
+ALTER TABLE jobs ADD COLUMN task_type TEXT;
+CREATE INDEX jobs_task_type ON jobs (task_type, posted);
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS jobs_reward_balance;
+DROP INDEX IF EXISTS jobs_task_bid;
+ALTER TABLE jobs DROP COLUMN total_tasks;
+ALTER TABLE jobs DROP COLUMN task_bid;
+ALTER TABLE jobs DROP COLUMN reward_balance;
+ALTER TABLE jobs DROP COLUMN reward_decimals;
+ALTER TABLE jobs DROP COLUMN reward_symbol;
+ALTER TABLE jobs DROP COLUMN reward_token;
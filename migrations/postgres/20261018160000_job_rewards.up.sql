@@ -0,0 +1,10 @@
+-- The token an escrow is funded with and what its job pays. Amounts are exact
+-- decimal strings in the token's smallest unit, and are sorted by length first.
+ALTER TABLE jobs ADD COLUMN reward_token TEXT;
+ALTER TABLE jobs ADD COLUMN reward_symbol TEXT;
+ALTER TABLE jobs ADD COLUMN reward_decimals BIGINT NOT NULL DEFAULT 18;
+ALTER TABLE jobs ADD COLUMN reward_balance TEXT;
+ALTER TABLE jobs ADD COLUMN task_bid TEXT;
+ALTER TABLE jobs ADD COLUMN total_tasks BIGINT;
+CREATE INDEX jobs_task_bid ON jobs (length(task_bid), task_bid);
+CREATE INDEX jobs_reward_balance ON jobs (length(reward_balance), reward_balance);
@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS jobs_delete ON jobs;
+DROP TRIGGER IF EXISTS jobs_update ON jobs;
+DROP TRIGGER IF EXISTS jobs_insert ON jobs;
+DROP FUNCTION IF EXISTS jobs_delete();
+DROP FUNCTION IF EXISTS jobs_update();
+DROP FUNCTION IF EXISTS jobs_insert();
+DROP TABLE IF EXISTS job_documents;
@@ -0,0 +1,61 @@
+-- The searchable text of each job, with the search index kept in a generated
+-- column. Rows are added and kept up to date by the triggers on jobs, and the
+-- manifest text is filled in when it is fetched. A `chr(1)` is put after each
+-- `<`, so the parser indexes words such as `<cat>` instead of skipping them as
+-- HTML tags.
+CREATE TABLE job_documents
+(
+    doc_id      BIGSERIAL PRIMARY KEY,
+    job_id      TEXT NOT NULL UNIQUE,
+    escrow_id   TEXT NOT NULL,
+    task_type   TEXT,
+    description TEXT,
+    manifest    TEXT,
+    search      TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', replace(
+        escrow_id || ' ' || coalesce(task_type, '') || ' ' ||
+        coalesce(description, '') || ' ' || coalesce(manifest, ''),
+        '<', '<' || chr(1)))) STORED
+);
+CREATE INDEX job_documents_search ON job_documents USING GIN (search);
+
+CREATE FUNCTION jobs_insert() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO job_documents (job_id, escrow_id, task_type)
+    VALUES (new.job_id, new.escrow_id, new.task_type);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER jobs_insert AFTER INSERT ON jobs
+FOR EACH ROW EXECUTE FUNCTION jobs_insert();
+
+-- The manifest text is cleared when the manifest_url changes, until the new
+-- manifest is fetched.
+CREATE FUNCTION jobs_update() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE job_documents SET
+        escrow_id = new.escrow_id,
+        task_type = new.task_type,
+        description = CASE WHEN old.manifest_url IS NOT DISTINCT FROM new.manifest_url THEN description END,
+        manifest = CASE WHEN old.manifest_url IS NOT DISTINCT FROM new.manifest_url THEN manifest END
+    WHERE job_id = new.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER jobs_update AFTER UPDATE OF escrow_id, task_type, manifest_url ON jobs
+FOR EACH ROW
+WHEN (old.escrow_id IS DISTINCT FROM new.escrow_id
+   OR old.task_type IS DISTINCT FROM new.task_type
+   OR old.manifest_url IS DISTINCT FROM new.manifest_url)
+EXECUTE FUNCTION jobs_update();
+
+CREATE FUNCTION jobs_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER jobs_delete AFTER DELETE ON jobs
+FOR EACH ROW EXECUTE FUNCTION jobs_delete();
+
+INSERT INTO job_documents (job_id, escrow_id, task_type)
+SELECT job_id, escrow_id, task_type FROM jobs;
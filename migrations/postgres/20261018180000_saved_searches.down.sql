@@ -0,0 +1,15 @@
+DROP TRIGGER IF EXISTS saved_searches_delete ON saved_searches;
+DROP FUNCTION IF EXISTS saved_searches_delete();
+DROP TRIGGER IF EXISTS api_keys_delete ON api_keys;
+DROP FUNCTION IF EXISTS api_keys_delete();
+
+CREATE OR REPLACE FUNCTION jobs_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TABLE IF EXISTS job_alerts;
+DROP TABLE IF EXISTS saved_searches;
+ALTER TABLE jobs DROP COLUMN network;
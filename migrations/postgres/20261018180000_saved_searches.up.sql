@@ -0,0 +1,60 @@
+-- The network of the subgraph which a job was read from.
+ALTER TABLE jobs ADD COLUMN network TEXT;
+
+-- Searches which API key holders are alerted about when a matching job is ingested.
+CREATE TABLE saved_searches
+(
+    search_id    TEXT PRIMARY KEY,
+    api_key      BYTEA NOT NULL,
+    network      TEXT,
+    task_type    TEXT,
+    -- In whole tokens, as it was saved, since jobs are paid in tokens with different decimals.
+    min_task_bid TEXT,
+    webhook_url  TEXT,
+    created      BIGINT NOT NULL
+);
+CREATE INDEX saved_searches_api_key ON saved_searches (api_key);
+CREATE INDEX saved_searches_task_type ON saved_searches (task_type);
+
+-- A job which matched a saved search. Each job alerts a search at most once.
+-- next_attempt is when the webhook is next sent, and is NULL once it has been
+-- delivered, given up on, or when the search has no webhook.
+CREATE TABLE job_alerts
+(
+    alert_id     BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    search_id    TEXT NOT NULL,
+    job_id       TEXT NOT NULL,
+    created      BIGINT NOT NULL,
+    next_attempt BIGINT,
+    attempts     BIGINT NOT NULL DEFAULT 0,
+    delivered    BIGINT,
+    UNIQUE (search_id, job_id)
+);
+CREATE INDEX job_alerts_job_id ON job_alerts (job_id);
+CREATE INDEX job_alerts_next_attempt ON job_alerts (next_attempt);
+
+CREATE OR REPLACE FUNCTION jobs_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+    DELETE FROM job_alerts WHERE job_id = old.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION api_keys_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM saved_searches WHERE api_key = old.api_key;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER api_keys_delete AFTER DELETE ON api_keys
+FOR EACH ROW EXECUTE FUNCTION api_keys_delete();
+
+CREATE FUNCTION saved_searches_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_alerts WHERE search_id = old.search_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER saved_searches_delete AFTER DELETE ON saved_searches
+FOR EACH ROW EXECUTE FUNCTION saved_searches_delete();
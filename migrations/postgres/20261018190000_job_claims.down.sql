@@ -0,0 +1,16 @@
+CREATE OR REPLACE FUNCTION api_keys_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM saved_searches WHERE api_key = old.api_key;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION jobs_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+    DELETE FROM job_alerts WHERE job_id = old.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TABLE IF EXISTS job_claims;
@@ -0,0 +1,31 @@
+-- Task slots of a job which are reserved by an API key until the lease expires.
+-- Expired claims no longer count against the job's total_tasks, and are
+-- deleted by the claim_expiry task.
+CREATE TABLE job_claims
+(
+    claim_id TEXT PRIMARY KEY,
+    job_id   TEXT NOT NULL,
+    api_key  BYTEA NOT NULL,
+    created  BIGINT NOT NULL,
+    expires  BIGINT NOT NULL
+);
+CREATE INDEX job_claims_job_id_expires ON job_claims (job_id, expires);
+CREATE INDEX job_claims_api_key ON job_claims (api_key);
+CREATE INDEX job_claims_expires ON job_claims (expires);
+
+CREATE OR REPLACE FUNCTION jobs_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+    DELETE FROM job_alerts WHERE job_id = old.job_id;
+    DELETE FROM job_claims WHERE job_id = old.job_id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION api_keys_delete() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM saved_searches WHERE api_key = old.api_key;
+    DELETE FROM job_claims WHERE api_key = old.api_key;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
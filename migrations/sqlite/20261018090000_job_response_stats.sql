@@ -0,0 +1,8 @@
+-- Hourly response buckets for each job.
+CREATE TABLE IF NOT EXISTS job_response_stats
+(
+    shortcode TEXT NOT NULL,
+    bucket    BIGINT NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, bucket)
+);
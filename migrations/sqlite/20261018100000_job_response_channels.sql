@@ -0,0 +1,25 @@
+-- Responses split by access channel (html, raw, api).
+CREATE TABLE IF NOT EXISTS job_response_channels
+(
+    shortcode TEXT NOT NULL,
+    channel   TEXT NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, channel)
+);
+
+-- Responses made through the API, per API key.
+CREATE TABLE IF NOT EXISTS job_response_api_keys
+(
+    shortcode TEXT NOT NULL,
+    api_key   BLOB NOT NULL,
+    responses BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, api_key)
+);
+
+-- Visitor fingerprints used to approximate unique visitors.
+CREATE TABLE IF NOT EXISTS job_visitors
+(
+    shortcode TEXT NOT NULL,
+    visitor   TEXT NOT NULL,
+    PRIMARY KEY (shortcode, visitor)
+);
@@ -0,0 +1,26 @@
+-- Rebuild `jobs` with a single schema.
+--
+-- Depending on which migration created it, `jobs` either has `manifest_url`
+-- and a BIGINT `posted`, or `manifest_id` and a DATETIME `posted`. Columns
+-- are copied by position, and text timestamps are converted to seconds.
+CREATE TABLE jobs_reconciled
+(
+    job_id       TEXT PRIMARY KEY NOT NULL,
+    shortcode    TEXT UNIQUE NOT NULL,
+    escrow_id    TEXT NOT NULL,
+    manifest_url TEXT,
+    posted       BIGINT NOT NULL,
+    expires      DATETIME,
+    password     TEXT,
+    responses    BIGINT NOT NULL
+);
+
+INSERT INTO jobs_reconciled SELECT * FROM jobs;
+
+UPDATE jobs_reconciled
+SET posted = COALESCE(CAST(strftime('%s', posted) AS INTEGER), 0)
+WHERE typeof(posted) = 'text';
+
+DROP TABLE jobs;
+
+ALTER TABLE jobs_reconciled RENAME TO jobs;
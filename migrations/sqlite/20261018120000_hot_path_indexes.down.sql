@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS job_response_stats_bucket;
+DROP INDEX IF EXISTS jobs_escrow_id;
+DROP INDEX IF EXISTS jobs_expires;
+DROP INDEX IF EXISTS jobs_posted;
@@ -0,0 +1,7 @@
+-- Store escrow addresses in lower case, so that they can be looked up
+-- whether they were posted checksummed or not.
+UPDATE jobs
+SET escrow_id = lower(escrow_id)
+WHERE length(escrow_id) = 42
+  AND escrow_id GLOB '0[xX]*'
+  AND substr(escrow_id, 3) NOT GLOB '*[^0-9a-fA-F]*';
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS jobs_manifest_status;
+ALTER TABLE jobs DROP COLUMN manifest_checked;
+ALTER TABLE jobs DROP COLUMN manifest_status;
+ALTER TABLE jobs DROP COLUMN manifest_hash;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS jobs_task_type;
+ALTER TABLE jobs DROP COLUMN task_type;
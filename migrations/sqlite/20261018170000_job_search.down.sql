@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS jobs_search_update;
+DROP TRIGGER IF EXISTS jobs_search_delete;
+DROP TRIGGER IF EXISTS jobs_search_insert;
+DROP TRIGGER IF EXISTS job_documents_update;
+DROP TRIGGER IF EXISTS job_documents_delete;
+DROP TRIGGER IF EXISTS job_documents_insert;
+DROP TABLE IF EXISTS job_search;
+DROP TABLE IF EXISTS job_documents;
@@ -0,0 +1,64 @@
+-- The searchable text of each job. Rows are added and kept up to date by the
+-- triggers on jobs, and the manifest text is filled in when it is fetched.
+CREATE TABLE IF NOT EXISTS job_documents (
+    doc_id INTEGER PRIMARY KEY,
+    job_id TEXT NOT NULL UNIQUE,
+    escrow_id TEXT NOT NULL,
+    task_type TEXT,
+    description TEXT,
+    manifest TEXT
+);
+
+-- A full-text index over job_documents, which stores no copy of the text.
+CREATE VIRTUAL TABLE IF NOT EXISTS job_search USING fts5(
+    escrow_id,
+    task_type,
+    description,
+    manifest,
+    content = 'job_documents',
+    content_rowid = 'doc_id'
+);
+
+CREATE TRIGGER IF NOT EXISTS job_documents_insert AFTER INSERT ON job_documents BEGIN
+    INSERT INTO job_search (rowid, escrow_id, task_type, description, manifest)
+    VALUES (new.doc_id, new.escrow_id, new.task_type, new.description, new.manifest);
+END;
+
+CREATE TRIGGER IF NOT EXISTS job_documents_delete AFTER DELETE ON job_documents BEGIN
+    INSERT INTO job_search (job_search, rowid, escrow_id, task_type, description, manifest)
+    VALUES ('delete', old.doc_id, old.escrow_id, old.task_type, old.description, old.manifest);
+END;
+
+CREATE TRIGGER IF NOT EXISTS job_documents_update AFTER UPDATE ON job_documents BEGIN
+    INSERT INTO job_search (job_search, rowid, escrow_id, task_type, description, manifest)
+    VALUES ('delete', old.doc_id, old.escrow_id, old.task_type, old.description, old.manifest);
+    INSERT INTO job_search (rowid, escrow_id, task_type, description, manifest)
+    VALUES (new.doc_id, new.escrow_id, new.task_type, new.description, new.manifest);
+END;
+
+CREATE TRIGGER IF NOT EXISTS jobs_search_insert AFTER INSERT ON jobs BEGIN
+    INSERT INTO job_documents (job_id, escrow_id, task_type)
+    VALUES (new.job_id, new.escrow_id, new.task_type);
+END;
+
+CREATE TRIGGER IF NOT EXISTS jobs_search_delete AFTER DELETE ON jobs BEGIN
+    DELETE FROM job_documents WHERE job_id = old.job_id;
+END;
+
+-- The manifest text is cleared when the manifest_url changes, until the new
+-- manifest is fetched.
+CREATE TRIGGER IF NOT EXISTS jobs_search_update AFTER UPDATE OF escrow_id, task_type, manifest_url ON jobs
+WHEN old.escrow_id IS NOT new.escrow_id
+  OR old.task_type IS NOT new.task_type
+  OR old.manifest_url IS NOT new.manifest_url
+BEGIN
+    UPDATE job_documents SET
+        escrow_id = new.escrow_id,
+        task_type = new.task_type,
+        description = CASE WHEN old.manifest_url IS new.manifest_url THEN description END,
+        manifest = CASE WHEN old.manifest_url IS new.manifest_url THEN manifest END
+    WHERE job_id = new.job_id;
+END;
+
+INSERT INTO job_documents (job_id, escrow_id, task_type)
+SELECT job_id, escrow_id, task_type FROM jobs;
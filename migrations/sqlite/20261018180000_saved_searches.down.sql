@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS jobs_alerts_delete;
+DROP TRIGGER IF EXISTS saved_searches_alerts_delete;
+DROP TRIGGER IF EXISTS api_keys_saved_searches_delete;
+DROP TABLE IF EXISTS job_alerts;
+DROP TABLE IF EXISTS saved_searches;
+ALTER TABLE jobs DROP COLUMN network;
@@ -0,0 +1,44 @@
+-- The network of the subgraph which a job was read from.
+ALTER TABLE jobs ADD COLUMN network TEXT;
+
+-- Searches which API key holders are alerted about when a matching job is ingested.
+CREATE TABLE IF NOT EXISTS saved_searches (
+    search_id TEXT PRIMARY KEY,
+    api_key BLOB NOT NULL,
+    network TEXT,
+    task_type TEXT,
+    -- In whole tokens, as it was saved, since jobs are paid in tokens with different decimals.
+    min_task_bid TEXT,
+    webhook_url TEXT,
+    created BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS saved_searches_api_key ON saved_searches (api_key);
+CREATE INDEX IF NOT EXISTS saved_searches_task_type ON saved_searches (task_type);
+
+-- A job which matched a saved search. Each job alerts a search at most once.
+-- next_attempt is when the webhook is next sent, and is NULL once it has been
+-- delivered, given up on, or when the search has no webhook.
+CREATE TABLE IF NOT EXISTS job_alerts (
+    alert_id INTEGER PRIMARY KEY,
+    search_id TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    created BIGINT NOT NULL,
+    next_attempt BIGINT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    delivered BIGINT,
+    UNIQUE (search_id, job_id)
+);
+CREATE INDEX IF NOT EXISTS job_alerts_job_id ON job_alerts (job_id);
+CREATE INDEX IF NOT EXISTS job_alerts_next_attempt ON job_alerts (next_attempt);
+
+CREATE TRIGGER IF NOT EXISTS api_keys_saved_searches_delete AFTER DELETE ON api_keys BEGIN
+    DELETE FROM saved_searches WHERE api_key = old.api_key;
+END;
+
+CREATE TRIGGER IF NOT EXISTS saved_searches_alerts_delete AFTER DELETE ON saved_searches BEGIN
+    DELETE FROM job_alerts WHERE search_id = old.search_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS jobs_alerts_delete AFTER DELETE ON jobs BEGIN
+    DELETE FROM job_alerts WHERE job_id = old.job_id;
+END;
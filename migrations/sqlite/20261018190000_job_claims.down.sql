@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS api_keys_claims_delete;
+DROP TRIGGER IF EXISTS jobs_claims_delete;
+DROP TABLE IF EXISTS job_claims;
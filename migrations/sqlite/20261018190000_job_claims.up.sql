@@ -0,0 +1,21 @@
+-- Task slots of a job which are reserved by an API key until the lease expires.
+-- Expired claims no longer count against the job's total_tasks, and are
+-- deleted by the claim_expiry task.
+CREATE TABLE IF NOT EXISTS job_claims (
+    claim_id TEXT PRIMARY KEY,
+    job_id TEXT NOT NULL,
+    api_key BLOB NOT NULL,
+    created BIGINT NOT NULL,
+    expires BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS job_claims_job_id_expires ON job_claims (job_id, expires);
+CREATE INDEX IF NOT EXISTS job_claims_api_key ON job_claims (api_key);
+CREATE INDEX IF NOT EXISTS job_claims_expires ON job_claims (expires);
+
+CREATE TRIGGER IF NOT EXISTS jobs_claims_delete AFTER DELETE ON jobs BEGIN
+    DELETE FROM job_claims WHERE job_id = old.job_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS api_keys_claims_delete AFTER DELETE ON api_keys BEGIN
+    DELETE FROM job_claims WHERE api_key = old.api_key;
+END;
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS job_visitors;
+CREATE TABLE job_visitors
+(
+    shortcode TEXT NOT NULL,
+    visitor   TEXT NOT NULL,
+    PRIMARY KEY (shortcode, visitor)
+);
@@ -0,0 +1,11 @@
+-- Visitor fingerprints are recorded per day, so that old ones can be purged.
+-- The fingerprints recorded so far were not salted, and are discarded.
+DROP TABLE IF EXISTS job_visitors;
+CREATE TABLE job_visitors
+(
+    shortcode TEXT   NOT NULL,
+    day       BIGINT NOT NULL,
+    visitor   TEXT   NOT NULL,
+    PRIMARY KEY (shortcode, day, visitor)
+);
+CREATE INDEX IF NOT EXISTS job_visitors_day ON job_visitors (day);
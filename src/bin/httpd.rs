@@ -1,42 +1,259 @@
 use dotenv::dotenv;
-use gpt_exchange::data::AppDatabase;
+use gpt_exchange::config::AppConfig;
+use gpt_exchange::data::{migration, snapshot, AppDatabase, DataError};
 use gpt_exchange::domain::maintenance::Maintenance;
+use gpt_exchange::logging::{self, LogFormat};
+use gpt_exchange::web::admin::{AdminToken, BackupDirectory};
 use gpt_exchange::web::{renderer::Renderer, responsecounter::ResponseCounter};
+use rocket::figment::providers::Serialized;
+use rocket::figment::Figment;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+/// Command line arguments. These override the configuration file and environment variables.
 #[derive(StructOpt, Debug)]
 #[structopt(name = "httpd")]
 struct Opt {
-    #[structopt(default_value = "sqlite:data.db")]
-    connection_string: String,
-    #[structopt(short, long, parse(from_os_str), default_value = "templates/")]
-    template_directory: PathBuf,
+    /// SQLite or PostgreSQL connection string, such as `sqlite:data.db` or
+    /// `postgres://localhost/gptx`.
+    connection_string: Option<String>,
+    /// Configuration file. Defaults to `gptx.toml`, if it exists.
+    #[structopt(short, long, parse(from_os_str), env = "GPTX_CONFIG")]
+    config: Option<PathBuf>,
+    /// Directory containing the page templates.
+    #[structopt(short, long, parse(from_os_str))]
+    template_directory: Option<PathBuf>,
+    /// Token required in the `x-admin-token` header to use the admin API.
+    ///
+    /// The admin API is disabled when no token is provided.
+    #[structopt(long)]
+    admin_token: Option<String>,
+    /// Log output format: `text` or `json`.
+    #[structopt(long)]
+    log_format: Option<LogFormat>,
+    /// Address to listen on.
+    #[structopt(long)]
+    address: Option<IpAddr>,
+    /// Port to listen on.
+    #[structopt(long)]
+    port: Option<u16>,
+    /// Don't apply pending migrations on startup.
+    #[structopt(long)]
+    no_migrate: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands which run instead of the server.
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Manage the database schema.
+    Migrate(MigrateCommand),
+    /// Write a snapshot of the database to a new file.
+    ///
+    /// Only SQLite databases can be snapshotted; use `pg_dump` for PostgreSQL.
+    Backup {
+        /// Where to write the snapshot.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Replace the contents of the database with a snapshot.
+    ///
+    /// The snapshot must have been taken at the same schema version as the database.
+    Restore {
+        /// The snapshot to restore.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum MigrateCommand {
+    /// List the migrations, and whether they have been applied.
+    Status,
+    /// Apply every pending migration.
+    Up,
+    /// Revert the most recent migration.
+    Down {
+        /// Revert every migration newer than this version instead.
+        #[structopt(long)]
+        target: Option<i64>,
+    },
+}
+
+impl Opt {
+    /// Layer the provided arguments over the `figment`.
+    fn merge_into(self, figment: Figment) -> Figment {
+        fn set<T: serde::Serialize>(figment: Figment, key: &str, value: Option<T>) -> Figment {
+            match value {
+                Some(value) => figment.merge(Serialized::default(key, value)),
+                None => figment,
+            }
+        }
+        let figment = set(figment, "database.url", self.connection_string);
+        let figment = set(
+            figment,
+            "server.template_directory",
+            self.template_directory,
+        );
+        let figment = set(figment, "server.admin_token", self.admin_token);
+        let figment = set(figment, "server.log_format", self.log_format);
+        let figment = set(figment, "server.address", self.address);
+        let figment = set(figment, "server.port", self.port);
+        set(
+            figment,
+            "database.migrate_on_startup",
+            self.no_migrate.then_some(false),
+        )
+    }
+}
+
+/// Print the error and exit.
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
+
+/// Load the configuration, exiting with an explanation if it is invalid.
+fn load_config(opt: Opt) -> AppConfig {
+    let config = AppConfig::figment(opt.config.as_deref())
+        .and_then(|figment| AppConfig::from_figment(&opt.merge_into(figment)));
+    config.unwrap_or_else(|e| exit_with(e))
+}
+
+/// Run a `migrate` subcommand.
+async fn migrate(database: &AppDatabase, command: MigrateCommand) -> Result<(), DataError> {
+    let pool = database.get_pool();
+    match command {
+        MigrateCommand::Status => {
+            for migration in migration::status(pool).await? {
+                println!(
+                    "{} {:<9} {}{}",
+                    migration.version,
+                    if migration.applied {
+                        "applied"
+                    } else {
+                        "pending"
+                    },
+                    migration.description,
+                    if migration.reversible {
+                        ""
+                    } else {
+                        " (irreversible)"
+                    }
+                );
+            }
+        }
+        MigrateCommand::Up => {
+            for version in migration::run(pool).await? {
+                println!("applied {}", version);
+            }
+        }
+        MigrateCommand::Down { target } => {
+            for version in migration::revert(pool, target).await? {
+                println!("reverted {}", version);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run a command instead of the server.
+async fn run_command(database: &AppDatabase, command: Command) -> Result<(), DataError> {
+    match command {
+        Command::Migrate(command) => migrate(database, command).await,
+        Command::Backup { path } => {
+            let header = snapshot::create(database.get_pool(), &path).await?;
+            println!(
+                "wrote snapshot of schema version {} to {}",
+                header.schema_version,
+                path.display()
+            );
+            Ok(())
+        }
+        Command::Restore { path } => {
+            let header = snapshot::restore(database.get_pool(), &path).await?;
+            println!(
+                "restored snapshot of schema version {} from {}",
+                header.schema_version,
+                path.display()
+            );
+            Ok(())
+        }
+    }
 }
 
 fn main() {
     dotenv().ok();
-    let opt = Opt::from_args();
+    let mut opt = Opt::from_args();
+    let command = opt.command.take();
+    let config = load_config(opt);
+    logging::init(config.server.log_format);
 
     let rt = tokio::runtime::Runtime::new().expect("failed to spawn tokio runtime");
 
-    let handle = rt.handle().clone();
-    let renderer = Renderer::new(opt.template_directory.clone());
+    let database = rt
+        .block_on(AppDatabase::with_config(
+            &config.database.url,
+            &config.pool(),
+        ))
+        .unwrap_or_else(|e| exit_with(e));
+
+    if let Some(command) = command {
+        rt.block_on(run_command(&database, command))
+            .unwrap_or_else(|e| exit_with(e));
+        return;
+    }
 
-    let database = rt.block_on(async move { AppDatabase::new(&opt.connection_string).await });
+    if config.database.migrate_on_startup {
+        let applied = rt
+            .block_on(migration::run(database.get_pool()))
+            .unwrap_or_else(|e| exit_with(e));
+        if !applied.is_empty() {
+            tracing::info!(?applied, "applied database migrations");
+        }
+    } else {
+        match rt.block_on(migration::pending(database.get_pool())) {
+            Ok(pending) if !pending.is_empty() => {
+                tracing::warn!(
+                    ?pending,
+                    "database migrations are pending; run: httpd migrate up"
+                )
+            }
+            Ok(_) => (),
+            Err(e) => tracing::warn!(error = %e, "failed to check for pending migrations"),
+        }
+    }
+
+    let handle = rt.handle().clone();
+    let renderer = Renderer::new(config.server.template_directory.clone());
+    let admin_token = AdminToken::new(config.server.admin_token.clone());
 
-    let hit_counter = ResponseCounter::new(database.get_pool().clone(), handle.clone());
-    let maintenance = Maintenance::spawn(database.get_pool().clone(), handle);
+    let hit_counter = ResponseCounter::with_config(
+        database.get_pool().clone(),
+        handle.clone(),
+        config.response_counter(),
+    );
+    let maintenance =
+        Maintenance::with_config(database.get_pool().clone(), handle, config.maintenance());
 
-    let config = gpt_exchange::RocketConfig {
+    let rocket_config = gpt_exchange::RocketConfig {
         renderer,
         database,
         response_counter: hit_counter,
         maintenance,
+        admin_token,
+        cookies: config.cookies.clone(),
+        backup_directory: BackupDirectory(config.database.backup_directory.clone()),
+        shortcodes: config.shortcodes.clone(),
+        manifests: config.manifests.clone(),
+        webhooks: config.webhooks.clone(),
+        address: config.server.bind(),
     };
 
     rt.block_on(async move {
-        gpt_exchange::rocket(config)
+        gpt_exchange::rocket(rocket_config)
             .launch()
             .await
             .expect("failed to launch rocket server")
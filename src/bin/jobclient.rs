@@ -1,7 +1,7 @@
 use gpt_exchange::domain::job::field::{
-    EscrowId, Expires, ManifestUrl, Password, Posted, ShortCode,
+    EscrowId, Expires, ManifestUrl, Password, Posted, ShortCode, VanityCode,
 };
-use gpt_exchange::service::ask::{GetJob, NewJob, UpdateJob};
+use gpt_exchange::service::ask::{GetJob, JobRef, NewJob, UpdateJob};
 use gpt_exchange::web::api::{ApiKey, API_KEY_HEADER};
 use gpt_exchange::Job;
 use std::error::Error;
@@ -10,12 +10,13 @@ use structopt::StructOpt;
 #[derive(StructOpt, Debug)]
 enum Command {
     Get {
-        shortcode: ShortCode,
+        #[structopt(help = "shortcode or escrow address")]
+        job: JobRef,
         #[structopt(short, long, help = "password")]
         password: Option<String>,
     },
     New {
-        #[structopt(help = "escrow_id")]
+        #[structopt(help = "escrow address")]
         job: String,
         #[structopt(help = "posted")]
         posted: u64,
@@ -25,6 +26,8 @@ enum Command {
         expires: Option<Expires>,
         #[structopt(short, long, help = "manifest_url")]
         manifest_url: Option<ManifestUrl>,
+        #[structopt(short, long, help = "custom shortcode")]
+        shortcode: Option<VanityCode>,
     },
     Update {
         shortcode: ShortCode,
@@ -53,7 +56,12 @@ struct Opt {
 
 fn get_job(addr: &str, ask_svc: GetJob, api_key: ApiKey) -> Result<Job, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?;
-    let addr = format!("{}/api/job/{}", addr, ask_svc.shortcode.into_inner());
+    let addr = match ask_svc.job {
+        JobRef::ShortCode(shortcode) => format!("{}/api/job/{}", addr, shortcode.into_inner()),
+        JobRef::EscrowId(escrow_id) => {
+            format!("{}/api/v1/escrow/{}", addr, escrow_id.into_inner())
+        }
+    };
     let mut request = client.get(addr);
     request = match ask_svc.password.into_inner() {
         Some(password) => request.header(reqwest::header::COOKIE, format!("password={}", password)),
@@ -81,13 +89,10 @@ fn update_job(addr: &str, ask_svc: UpdateJob, api_key: ApiKey) -> Result<Job, Bo
 
 fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
     match opt.command {
-        Command::Get {
-            shortcode,
-            password,
-        } => {
+        Command::Get { job, password } => {
             let req = GetJob {
                 password: Password::new(password.unwrap_or_default())?,
-                shortcode,
+                job,
             };
             let job = get_job(opt.addr.as_str(), req, opt.api_key)?;
             println!("{:#?}", job);
@@ -99,6 +104,7 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             password,
             expires,
             manifest_url,
+            shortcode,
         } => {
             let req = NewJob {
                 escrow_id: EscrowId::new(job.as_str())?,
@@ -106,6 +112,7 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
                 manifest_url: manifest_url.unwrap_or_default(),
                 expires: expires.unwrap_or_default(),
                 password: password.unwrap_or_default(),
+                shortcode: shortcode.unwrap_or_default(),
             };
             let job = new_job(opt.addr.as_str(), req, opt.api_key)?;
             println!("{:#?}", job);
@@ -121,7 +128,7 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             let password = password.unwrap_or_default();
             let svc_req = GetJob {
                 password: password.clone(),
-                shortcode: shortcode.clone(),
+                job: shortcode.clone().into(),
             };
             let original_job = get_job(opt.addr.as_str(), svc_req, opt.api_key.clone())?;
             let svc_req = UpdateJob {
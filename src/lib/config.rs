@@ -0,0 +1,617 @@
+//! Application configuration.
+//!
+//! Settings are layered, with later layers taking priority:
+//!
+//! 1. The defaults.
+//! 2. A TOML file, `gptx.toml` unless another path is provided.
+//! 3. Environment variables prefixed with `GPTX_`, using `__` to separate
+//!    sections, such as `GPTX_SERVER__PORT=8080`. `DATABASE_URL`, `ADMIN_TOKEN`
+//!    and `LOG_FORMAT` are also read, including from a `.env` file.
+//! 4. Command line arguments.
+
+use crate::data::graph::GraphConfig;
+use crate::data::{Backend, JournalMode, PoolConfig, Synchronous};
+use crate::domain::alert::WebhookPolicy;
+use crate::domain::job::field::{EscrowId, ManifestPolicy, ShortCode, ShortCodeConfig, TokenList};
+use crate::domain::maintenance::MaintenanceConfig;
+use crate::logging::LogFormat;
+use crate::web::responsecounter::{ResponseCounterConfig, VisitorSalt};
+use crate::web::{CookieConfig, SameSitePolicy};
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::Figment;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The configuration file which is read when no other path is provided.
+pub const DEFAULT_CONFIG_FILE: &str = "gptx.toml";
+
+/// Prefix of the environment variables which are read into the configuration.
+const ENV_PREFIX: &str = "GPTX_";
+
+/// The most decimal places a token can have, since a `uint256` has 78 digits.
+const MAX_TOKEN_DECIMALS: u8 = 77;
+
+/// The possible errors that can occur when loading the configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The configuration file does not exist.
+    #[error("configuration file '{0}' does not exist")]
+    MissingFile(PathBuf),
+
+    /// A setting could not be read, or has the wrong type.
+    #[error("{0}")]
+    Load(Box<rocket::figment::Error>),
+
+    /// Some settings have invalid values.
+    #[error("{}", InvalidSettings(.0))]
+    Invalid(Vec<String>),
+}
+
+impl From<rocket::figment::Error> for ConfigError {
+    fn from(err: rocket::figment::Error) -> Self {
+        Self::Load(Box::new(err))
+    }
+}
+
+/// Formats each invalid setting on its own line.
+struct InvalidSettings<'a>(&'a [String]);
+
+impl fmt::Display for InvalidSettings<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for the HTTP server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// The address to listen on.
+    pub address: IpAddr,
+    /// The port to listen on.
+    pub port: u16,
+    /// Where the page templates are read from.
+    pub template_directory: PathBuf,
+    /// Token required in the `x-admin-token` header to use the admin API.
+    ///
+    /// The admin API is disabled when no token is set.
+    pub admin_token: Option<String>,
+    /// How log entries are written.
+    pub log_format: LogFormat,
+}
+
+/// The Default implementation listens on `127.0.0.1:8000`.
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8000,
+            template_directory: PathBuf::from("templates/"),
+            admin_token: None,
+            log_format: LogFormat::Text,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// The socket address to listen on.
+    pub fn bind(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+/// Settings for the database.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// The SQLite connection string.
+    pub url: String,
+    /// Whether pending migrations are applied when the server starts.
+    pub migrate_on_startup: bool,
+    /// Where snapshots requested through the admin API are written.
+    pub backup_directory: PathBuf,
+    /// The most connections that are kept open at once.
+    pub max_connections: u32,
+    /// The fewest connections that are kept open at once.
+    pub min_connections: u32,
+    /// How the SQLite rollback journal is kept.
+    pub journal_mode: JournalMode,
+    /// How often SQLite waits for writes to reach the disk.
+    pub synchronous: Synchronous,
+    /// Milliseconds to wait for a lock held by another connection.
+    pub busy_timeout_ms: u64,
+}
+
+/// The Default implementation uses `data.db` in the working directory, and the
+/// defaults of [`PoolConfig`].
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let defaults = PoolConfig::default();
+        Self {
+            url: "sqlite:data.db".to_owned(),
+            migrate_on_startup: true,
+            backup_directory: "backups/".into(),
+            max_connections: defaults.max_connections,
+            min_connections: defaults.min_connections,
+            journal_mode: defaults.journal_mode,
+            synchronous: defaults.synchronous,
+            busy_timeout_ms: defaults.busy_timeout.as_millis() as u64,
+        }
+    }
+}
+
+/// How often the background maintenance tasks run.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TasksConfig {
+    /// Seconds between fetching new jobs from the Graph.
+    pub sync_interval_secs: u64,
+    /// Seconds between deleting expired jobs.
+    pub expiry_purge_interval_secs: u64,
+    /// Seconds between merging old hourly response buckets into daily buckets.
+    pub stats_rollup_interval_secs: u64,
+    /// Days that hourly response buckets are kept before being merged.
+    pub hourly_stats_retention_days: u64,
+    /// Days that visitors are remembered for, to count unique visitors.
+    pub visitor_retention_days: u64,
+    /// Seconds between updating query planner statistics and releasing unused space.
+    pub optimize_interval_secs: u64,
+    /// Seconds between checking pending manifests against their escrow's hash.
+    pub manifest_verify_interval_secs: u64,
+    /// Seconds between posting alerts to the webhooks of saved searches.
+    pub alert_delivery_interval_secs: u64,
+    /// Seconds between deleting claims whose lease has run out.
+    pub claim_expiry_interval_secs: u64,
+}
+
+/// Uses the defaults of [`MaintenanceConfig`].
+impl Default for TasksConfig {
+    fn default() -> Self {
+        let defaults = MaintenanceConfig::default();
+        Self {
+            sync_interval_secs: defaults.sync_interval.as_secs(),
+            expiry_purge_interval_secs: defaults.expiry_purge_interval.as_secs(),
+            stats_rollup_interval_secs: defaults.stats_rollup_interval.as_secs(),
+            hourly_stats_retention_days: defaults.hourly_stats_retention.as_secs() / (24 * 60 * 60),
+            visitor_retention_days: defaults.visitor_retention.as_secs() / (24 * 60 * 60),
+            optimize_interval_secs: defaults.optimize_interval.as_secs(),
+            manifest_verify_interval_secs: defaults.manifest_verify_interval.as_secs(),
+            alert_delivery_interval_secs: defaults.alert_delivery_interval.as_secs(),
+            claim_expiry_interval_secs: defaults.claim_expiry_interval.as_secs(),
+        }
+    }
+}
+
+/// How often buffered responses are committed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ResponseCounterSettings {
+    /// Seconds between commits.
+    pub commit_interval_secs: u64,
+    /// Responses are committed early once this many entries are buffered.
+    pub batch_size: usize,
+    /// The most entries buffered while commits keep failing, after which the
+    /// oldest are dropped.
+    pub max_pending: usize,
+    /// The secret mixed into visitor fingerprints.
+    ///
+    /// A random salt is used when none is set, so visitors are counted afresh
+    /// after every restart.
+    pub visitor_salt: Option<VisitorSalt>,
+}
+
+/// Uses the defaults of [`ResponseCounterConfig`], without a visitor salt.
+impl Default for ResponseCounterSettings {
+    fn default() -> Self {
+        let defaults = ResponseCounterConfig::default();
+        Self {
+            commit_interval_secs: defaults.commit_interval.as_secs(),
+            batch_size: defaults.batch_size,
+            max_pending: defaults.max_pending,
+            visitor_salt: None,
+        }
+    }
+}
+
+/// All settings for `httpd`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Settings for the HTTP server.
+    pub server: ServerConfig,
+    /// Settings for the database.
+    pub database: DatabaseConfig,
+    /// Where jobs are fetched from.
+    pub graph: GraphConfig,
+    /// How often the background maintenance tasks run.
+    pub tasks: TasksConfig,
+    /// How often buffered responses are committed.
+    pub response_counter: ResponseCounterSettings,
+    /// Settings for the cookies set by the service.
+    pub cookies: CookieConfig,
+    /// How shortcodes are generated for new jobs.
+    pub shortcodes: ShortCodeConfig,
+    /// Which manifest URLs are accepted, and how manifests are fetched.
+    pub manifests: ManifestPolicy,
+    /// Which URLs saved searches can post their alerts to.
+    pub webhooks: WebhookPolicy,
+    /// The tokens which rewards are shown in, with their symbols and decimals.
+    pub tokens: TokenList,
+}
+
+impl AppConfig {
+    /// The defaults, overridden by the TOML file and environment variables.
+    ///
+    /// The file at `path` must exist. When no path is provided, [`DEFAULT_CONFIG_FILE`]
+    /// is read if it exists.
+    pub fn figment(path: Option<&Path>) -> Result<Figment, ConfigError> {
+        let file = match path {
+            Some(path) if !path.exists() => return Err(ConfigError::MissingFile(path.into())),
+            Some(path) => path,
+            None => Path::new(DEFAULT_CONFIG_FILE),
+        };
+        Ok(Figment::from(Serialized::defaults(AppConfig::default()))
+            .merge(Toml::file(file))
+            .merge(Env::prefixed(ENV_PREFIX).split("__"))
+            .merge(
+                Env::raw()
+                    .only(&["database_url"])
+                    .map(|_| "database.url".into()),
+            )
+            .merge(
+                Env::raw()
+                    .only(&["admin_token", "log_format"])
+                    .map(|key| format!("server.{}", key.as_str().to_lowercase()).into()),
+            ))
+    }
+
+    /// Extract and validate the configuration.
+    pub fn from_figment(figment: &Figment) -> Result<Self, ConfigError> {
+        let config: Self = figment.extract()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that every setting has a usable value, reporting all problems at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_owned());
+            }
+        };
+
+        check(
+            self.server.template_directory.is_dir(),
+            &format!(
+                "server.template_directory: '{}' is not a directory",
+                self.server.template_directory.display()
+            ),
+        );
+        check(
+            Backend::from_url(&self.database.url).is_some(),
+            "database.url: must be a SQLite or PostgreSQL connection string starting with 'sqlite:' or 'postgres:'",
+        );
+        check(
+            reqwest::Url::parse(&self.graph.url)
+                .map(|url| matches!(url.scheme(), "http" | "https"))
+                .unwrap_or(false),
+            "graph.url: must be an http or https URL",
+        );
+        for (name, size) in [
+            ("graph.page_size", self.graph.page_size),
+            ("graph.initial_page_size", self.graph.initial_page_size),
+        ] {
+            check(
+                (1..=1000).contains(&size),
+                &format!("{}: must be between 1 and 1000", name),
+            );
+        }
+        for (name, secs) in [
+            ("tasks.sync_interval_secs", self.tasks.sync_interval_secs),
+            (
+                "tasks.expiry_purge_interval_secs",
+                self.tasks.expiry_purge_interval_secs,
+            ),
+            (
+                "tasks.stats_rollup_interval_secs",
+                self.tasks.stats_rollup_interval_secs,
+            ),
+            (
+                "tasks.optimize_interval_secs",
+                self.tasks.optimize_interval_secs,
+            ),
+            (
+                "tasks.manifest_verify_interval_secs",
+                self.tasks.manifest_verify_interval_secs,
+            ),
+            (
+                "tasks.alert_delivery_interval_secs",
+                self.tasks.alert_delivery_interval_secs,
+            ),
+            (
+                "tasks.claim_expiry_interval_secs",
+                self.tasks.claim_expiry_interval_secs,
+            ),
+            (
+                "response_counter.commit_interval_secs",
+                self.response_counter.commit_interval_secs,
+            ),
+        ] {
+            check(secs > 0, &format!("{}: must be greater than 0", name));
+        }
+        check(
+            self.tasks.visitor_retention_days > 0,
+            "tasks.visitor_retention_days: must be greater than 0",
+        );
+        check(
+            self.database.max_connections > 0,
+            "database.max_connections: must be greater than 0",
+        );
+        check(
+            self.database.min_connections <= self.database.max_connections,
+            "database.min_connections: must not be greater than database.max_connections",
+        );
+        check(
+            (6..=ShortCode::MAX_LENGTH).contains(&self.shortcodes.length),
+            &format!(
+                "shortcodes.length: must be between 6 and {}",
+                ShortCode::MAX_LENGTH
+            ),
+        );
+        check(
+            !self.manifests.schemes.is_empty()
+                && self
+                    .manifests
+                    .schemes
+                    .iter()
+                    .all(|scheme| ManifestPolicy::KNOWN_SCHEMES.contains(&scheme.as_str())),
+            &format!(
+                "manifests.schemes: must be some of {}",
+                ManifestPolicy::KNOWN_SCHEMES.join(", ")
+            ),
+        );
+        check(
+            !self.webhooks.schemes.is_empty()
+                && self
+                    .webhooks
+                    .schemes
+                    .iter()
+                    .all(|scheme| WebhookPolicy::KNOWN_SCHEMES.contains(&scheme.as_str())),
+            &format!(
+                "webhooks.schemes: must be some of {}",
+                WebhookPolicy::KNOWN_SCHEMES.join(", ")
+            ),
+        );
+        for (name, url) in [
+            ("manifests.ipfs_gateway", &self.manifests.ipfs_gateway),
+            ("manifests.s3_endpoint", &self.manifests.s3_endpoint),
+        ] {
+            check(
+                reqwest::Url::parse(url)
+                    .map(|url| matches!(url.scheme(), "http" | "https"))
+                    .unwrap_or(false),
+                &format!("{}: must be an http or https URL", name),
+            );
+        }
+        for (name, value) in [
+            ("manifests.max_length", self.manifests.max_length as u64),
+            (
+                "manifests.fetch_timeout_secs",
+                self.manifests.fetch_timeout_secs,
+            ),
+            (
+                "manifests.max_manifest_bytes",
+                self.manifests.max_manifest_bytes as u64,
+            ),
+            ("webhooks.timeout_secs", self.webhooks.timeout_secs),
+        ] {
+            check(value > 0, &format!("{}: must be greater than 0", name));
+        }
+        for (i, token) in self.tokens.0.iter().enumerate() {
+            check(
+                EscrowId::new(&token.address).is_ok(),
+                &format!("tokens[{}].address: must be a contract address", i),
+            );
+            check(
+                !token.symbol.trim().is_empty(),
+                &format!("tokens[{}].symbol: must not be empty", i),
+            );
+            check(
+                token.decimals <= MAX_TOKEN_DECIMALS,
+                &format!(
+                    "tokens[{}].decimals: must be at most {}",
+                    i, MAX_TOKEN_DECIMALS
+                ),
+            );
+        }
+        check(
+            self.response_counter.batch_size > 0,
+            "response_counter.batch_size: must be greater than 0",
+        );
+        check(
+            self.response_counter.max_pending >= self.response_counter.batch_size,
+            "response_counter.max_pending: must not be less than response_counter.batch_size",
+        );
+        check(
+            self.cookies.same_site != SameSitePolicy::None || self.cookies.secure,
+            "cookies.same_site: 'none' requires cookies.secure = true",
+        );
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Settings for the [`Maintenance`](crate::domain::maintenance::Maintenance) tasks.
+    pub fn maintenance(&self) -> MaintenanceConfig {
+        MaintenanceConfig {
+            sync_interval: Duration::from_secs(self.tasks.sync_interval_secs),
+            expiry_purge_interval: Duration::from_secs(self.tasks.expiry_purge_interval_secs),
+            stats_rollup_interval: Duration::from_secs(self.tasks.stats_rollup_interval_secs),
+            hourly_stats_retention: Duration::from_secs(
+                self.tasks.hourly_stats_retention_days * 24 * 60 * 60,
+            ),
+            visitor_retention: Duration::from_secs(
+                self.tasks.visitor_retention_days * 24 * 60 * 60,
+            ),
+            optimize_interval: Duration::from_secs(self.tasks.optimize_interval_secs),
+            manifest_verify_interval: Duration::from_secs(self.tasks.manifest_verify_interval_secs),
+            alert_delivery_interval: Duration::from_secs(self.tasks.alert_delivery_interval_secs),
+            claim_expiry_interval: Duration::from_secs(self.tasks.claim_expiry_interval_secs),
+            graph: self.graph.clone(),
+            manifests: self.manifests.clone(),
+            webhooks: self.webhooks.clone(),
+            shortcodes: self.shortcodes.clone(),
+            tokens: self.tokens.clone(),
+        }
+    }
+
+    /// Settings for the database connection pool.
+    pub fn pool(&self) -> PoolConfig {
+        PoolConfig {
+            max_connections: self.database.max_connections,
+            min_connections: self.database.min_connections,
+            journal_mode: self.database.journal_mode,
+            synchronous: self.database.synchronous,
+            busy_timeout: Duration::from_millis(self.database.busy_timeout_ms),
+            foreign_keys: true,
+        }
+    }
+
+    /// Settings for the [`ResponseCounter`](crate::web::ResponseCounter).
+    pub fn response_counter(&self) -> ResponseCounterConfig {
+        let visitor_salt = match &self.response_counter.visitor_salt {
+            Some(salt) => salt.clone(),
+            None => {
+                tracing::warn!(
+                    "response_counter.visitor_salt is not set; unique visitors are counted afresh after every restart"
+                );
+                VisitorSalt::random()
+            }
+        };
+        ResponseCounterConfig {
+            commit_interval: Duration::from_secs(self.response_counter.commit_interval_secs),
+            batch_size: self.response_counter.batch_size,
+            max_pending: self.response_counter.max_pending,
+            visitor_salt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AppConfig, ConfigError};
+    use crate::data::Backend;
+    use crate::logging::LogFormat;
+    use rocket::figment::providers::{Format, Serialized, Toml};
+    use rocket::figment::Figment;
+    use std::path::Path;
+
+    fn figment(toml: &str) -> Figment {
+        Figment::from(Serialized::defaults(AppConfig::default())).merge(Toml::string(toml))
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(AppConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let figment = Figment::from(Serialized::defaults(AppConfig::default()))
+            .merge(Toml::file("gptx.example.toml"));
+        let config = AppConfig::from_figment(&figment).unwrap();
+        assert_eq!(config.server.port, 8000);
+        assert_eq!(config.tasks.hourly_stats_retention_days, 7);
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let figment = figment(
+            r#"
+            [server]
+            port = 9000
+            log_format = "json"
+
+            [graph]
+            page_size = 100
+            "#,
+        )
+        .merge(Serialized::default("server.port", 9001));
+        let config = AppConfig::from_figment(&figment).unwrap();
+        assert_eq!(config.server.port, 9001);
+        assert_eq!(config.server.log_format, LogFormat::Json);
+        assert_eq!(config.graph.page_size, 100);
+        assert_eq!(config.graph.initial_page_size, 500);
+        assert_eq!(config.maintenance().graph.page_size, 100);
+    }
+
+    #[test]
+    fn reports_every_invalid_setting() {
+        let figment = figment(
+            r#"
+            [graph]
+            url = "ftp://example.com"
+            page_size = 0
+
+            [response_counter]
+            batch_size = 0
+
+            [manifests]
+            schemes = ["https", "ftp"]
+
+            [webhooks]
+            timeout_secs = 0
+
+            [[tokens]]
+            address = "0x1234"
+            symbol = "USDC"
+            decimals = 6
+            "#,
+        );
+        match AppConfig::from_figment(&figment) {
+            Err(ConfigError::Invalid(problems)) => {
+                assert_eq!(problems.len(), 6);
+                assert!(problems[0].starts_with("graph.url"));
+            }
+            other => panic!("expected invalid configuration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn postgres_urls_are_accepted() {
+        let figment = figment("[database]\nurl = \"postgres://localhost/gptx\"");
+        let config = AppConfig::from_figment(&figment).unwrap();
+        assert_eq!(
+            Backend::from_url(&config.database.url),
+            Some(Backend::Postgres)
+        );
+    }
+
+    #[test]
+    fn wrong_types_are_reported() {
+        let figment = figment("[server]\nport = \"eighty\"");
+        assert!(matches!(
+            AppConfig::from_figment(&figment),
+            Err(ConfigError::Load(_))
+        ));
+    }
+
+    #[test]
+    fn explicit_config_file_must_exist() {
+        assert!(matches!(
+            AppConfig::figment(Some(Path::new("does/not/exist.toml"))),
+            Err(ConfigError::MissingFile(_))
+        ));
+    }
+}
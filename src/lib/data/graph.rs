@@ -1,6 +1,7 @@
 //! Models for executing graph queries & returning data.
 use crate::data::query::get_last_fetched_escrow_id_time;
-use crate::data::DatabasePool;
+use crate::data::{DataError, DatabasePool};
+use crate::metrics;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
@@ -9,7 +10,29 @@ use serde_json::json;
 pub struct GraphJob {
     pub id: String,
     pub manifestUrl: Option<String>,
+    /// The hash of the manifest, as recorded by the escrow.
+    pub manifestHash: Option<String>,
+    /// The address of the token which the escrow is funded with.
+    pub eip20: Option<String>,
+    /// The amount of the token sent to the escrow, in its smallest unit.
+    pub amountAllocated: Option<String>,
+    /// The amount of the token paid out by the escrow, in its smallest unit.
+    pub amountPayout: Option<String>,
     pub timestamp: String,
+    /// The network of the subgraph, from the [`GraphConfig`] rather than the Graph.
+    #[serde(skip)]
+    pub network: Option<String>,
+}
+
+/// The block which a subgraph had indexed when it answered a query.
+#[derive(Debug, Deserialize)]
+struct Meta {
+    block: MetaBlock,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaBlock {
+    number: i64,
 }
 
 /// Graph response object for [`launchedEscrows`] query.
@@ -17,6 +40,17 @@ pub struct GraphJob {
 #[derive(Debug, Deserialize)]
 struct Data {
     launchedEscrows: Vec<GraphJob>,
+    #[serde(rename = "_meta")]
+    meta: Option<Meta>,
+}
+
+/// Launched escrows read from the subgraph.
+#[derive(Debug, Clone)]
+pub struct GraphPage {
+    /// The escrows, newest first.
+    pub jobs: Vec<GraphJob>,
+    /// The latest block the subgraph had indexed when the escrows were read.
+    pub block: Option<i64>,
 }
 
 // Graph query response
@@ -25,77 +59,191 @@ struct QueryResponse {
     data: Option<Data>,
 }
 
-pub async fn get_escrows_from_graph() -> Result<Vec<GraphJob>, Box<dyn std::error::Error>> {
-    let query = r#"
-        {
-            launchedEscrows(first: 500, orderBy: timestamp, orderDirection: desc) {
-                id
-                manifestUrl
-                timestamp
-           }
+/// Where, and how many, launched escrows are read from the Graph.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GraphConfig {
+    /// The subgraph which launched escrows are read from.
+    pub url: String,
+    /// The network which the subgraph indexes, recorded on each job read from it.
+    pub network: String,
+    /// The most escrows fetched by each sync.
+    pub page_size: u32,
+    /// The most escrows fetched when the database is empty.
+    pub initial_page_size: u32,
+}
+
+/// The Default implementation reads from the HUMAN Protocol Mumbai subgraph.
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://api.thegraph.com/subgraphs/name/humanprotocol/mumbai-v1".to_owned(),
+            network: "polygon-mumbai".to_owned(),
+            page_size: 50,
+            initial_page_size: 500,
         }
-    "#;
+    }
+}
 
+/// Runs a `launchedEscrows` query against the subgraph, recording its duration
+/// and whether it failed.
+///
+/// Each job is marked with the subgraph's network.
+#[tracing::instrument(level = "debug", skip_all)]
+async fn query_launched_escrows(config: &GraphConfig, query: &str) -> Result<GraphPage, DataError> {
+    let timer = metrics::GRAPH_QUERY_DURATION.start_timer();
+    let res = send_launched_escrows_query(&config.url, query).await;
+    timer.observe_duration();
+    if res.is_err() {
+        metrics::GRAPH_QUERY_FAILURES.inc();
+    }
+    let network = Some(config.network.trim())
+        .filter(|network| !network.is_empty())
+        .map(str::to_owned);
+    res.map(|page| GraphPage {
+        jobs: page
+            .jobs
+            .into_iter()
+            .map(|job| GraphJob {
+                network: network.clone(),
+                ..job
+            })
+            .collect(),
+        ..page
+    })
+}
+
+/// Sends a `launchedEscrows` query to the subgraph.
+async fn send_launched_escrows_query(url: &str, query: &str) -> Result<GraphPage, DataError> {
     let client = reqwest::Client::new();
 
     let res = client
-        .post("https://api.thegraph.com/subgraphs/name/humanprotocol/mumbai-v1")
+        .post(url)
         .json(&json!({
             "query": query,
         }))
         .send()
         .await?
+        .error_for_status()?
         .json::<QueryResponse>()
         .await?;
 
     match res.data {
-        None => Err("Response from server did not contain any data".into()),
+        None => Err(DataError::EmptyGraphResponse),
 
-        Some(data) => Ok(data.launchedEscrows),
+        Some(data) => Ok(GraphPage {
+            jobs: data.launchedEscrows,
+            block: data.meta.map(|meta| meta.block.number),
+        }),
     }
 }
 
+pub async fn get_escrows_from_graph(config: &GraphConfig) -> Result<GraphPage, DataError> {
+    let query = format!(
+        r#"
+        {{
+            _meta {{
+                block {{
+                    number
+                }}
+            }}
+            launchedEscrows(first: {}, orderBy: timestamp, orderDirection: desc) {{
+                id
+                manifestUrl
+                manifestHash
+                eip20
+                amountAllocated
+                amountPayout
+                timestamp
+           }}
+        }}
+    "#,
+        config.initial_page_size
+    );
+
+    query_launched_escrows(config, &query).await
+}
+
 pub async fn fetch_new_jobs_from_graph(
+    config: &GraphConfig,
     pool: &DatabasePool,
-) -> Result<Vec<GraphJob>, Box<dyn std::error::Error>> {
+) -> Result<GraphPage, DataError> {
     let last_escrow_id_time = get_last_fetched_escrow_id_time(pool).await?;
 
     let query = format!(
         r#"
         {{
+            _meta {{
+                block {{
+                    number
+                }}
+            }}
             launchedEscrows(
-                first: 50,
+                first: {},
                 orderBy: timestamp,
                 orderDirection: desc,
                 {}
             ) {{
                 id,
                 manifestUrl,
+                manifestHash,
+                eip20,
+                amountAllocated,
+                amountPayout,
                 timestamp
             }}
         }}
     "#,
+        config.page_size,
         match last_escrow_id_time {
             Some(posted) => format!(r#"where: {{ timestamp_gt: "{}" }}"#, posted),
             None => "".to_string(),
         }
     );
 
-    let client = reqwest::Client::new();
+    query_launched_escrows(config, &query).await
+}
 
-    let res = client
-        .post("https://api.thegraph.com/subgraphs/name/humanprotocol/mumbai-v1")
-        .json(&json!({
-            "query": query,
-        }))
-        .send()
-        .await?
-        .json::<QueryResponse>()
-        .await?;
+#[cfg(test)]
+mod test {
+    use super::{get_escrows_from_graph, GraphConfig};
+    use crate::test::async_runtime;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
 
-    match res.data {
-        None => Err("Response from server did not contain any data".into()),
+    /// Respond to one request with `body`, returning the URL of the server.
+    fn serve(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        format!("http://{}", addr)
+    }
 
-        Some(data) => Ok(data.launchedEscrows),
+    #[test]
+    fn escrows_are_read_with_the_indexed_block() {
+        let rt = async_runtime();
+        let config = GraphConfig {
+            url: serve(
+                r#"{"data": {
+                    "_meta": {"block": {"number": 1234}},
+                    "launchedEscrows": [{"id": "0x1", "timestamp": "5"}]
+                }}"#,
+            ),
+            ..GraphConfig::default()
+        };
+        let page = rt.block_on(get_escrows_from_graph(&config)).unwrap();
+        assert_eq!(page.block, Some(1234));
+        assert_eq!(page.jobs.len(), 1);
+        assert_eq!(page.jobs[0].network.as_deref(), Some("polygon-mumbai"));
     }
 }
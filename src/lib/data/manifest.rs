@@ -0,0 +1,348 @@
+//! Fetching manifests without letting their URLs reach private addresses.
+//!
+//! Manifest URLs come from job posters and the Graph, so every URL is checked
+//! against the [`ManifestPolicy`] before it is requested. Host names are
+//! resolved once and the connection is pinned to the checked addresses, so a
+//! second lookup can not return a different address. Redirects are followed
+//! by hand, checking each one in the same way.
+//!
+//! `ipfs://` and `s3://` manifests are [resolved](resolve) to the configured
+//! gateways first. Gateways are chosen by the operator, so they may be on
+//! private addresses, but any redirects they send are checked as usual.
+use crate::data::DataError;
+use crate::domain::job::field::{ManifestHash, ManifestPolicy};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// The most redirects which are followed when fetching a manifest.
+const MAX_REDIRECTS: usize = 5;
+
+/// Turn a manifest URL into the HTTP URL it is fetched from.
+///
+/// `ipfs://cid/path` becomes `<ipfs_gateway>/cid/path` and `s3://bucket/key`
+/// becomes `<s3_endpoint>/bucket/key`. Other URLs are returned unchanged.
+pub fn resolve(url: &Url, policy: &ManifestPolicy) -> Result<Url, DataError> {
+    let gateway = match url.scheme() {
+        "ipfs" => &policy.ipfs_gateway,
+        "s3" => &policy.s3_endpoint,
+        _ => return Ok(url.clone()),
+    };
+    let resolved = format!(
+        "{}/{}{}",
+        gateway.trim_end_matches('/'),
+        url.host_str().unwrap_or_default(),
+        url.path()
+    );
+    Url::parse(&resolved).map_err(|e| rejected(&format!("invalid gateway URL: {}", e)))
+}
+
+/// Fetch the manifest at `manifest_url`, which must be allowed by the policy.
+///
+/// When the escrow recorded a hash of the manifest, the manifest must match it.
+#[tracing::instrument(level = "debug", skip(policy))]
+pub async fn fetch(
+    manifest_url: &str,
+    hash: Option<&ManifestHash>,
+    policy: &ManifestPolicy,
+) -> Result<Vec<u8>, DataError> {
+    let url = policy
+        .check(manifest_url)
+        .map_err(|e| DataError::ManifestRejected(e.to_string()))?;
+    let is_gateway = matches!(url.scheme(), "ipfs" | "s3");
+    let manifest = fetch_url(resolve(&url, policy)?, is_gateway, policy).await?;
+    match hash {
+        Some(hash) if !hash.matches(&manifest) => Err(DataError::ManifestHashMismatch {
+            expected: hash.as_str().to_owned(),
+            actual: hash.algorithm().digest(&manifest),
+        }),
+        _ => Ok(manifest),
+    }
+}
+
+/// Fetch an HTTP URL, following redirects. The first request skips the
+/// address checks when it is to a configured gateway.
+async fn fetch_url(
+    mut url: Url,
+    mut is_gateway: bool,
+    policy: &ManifestPolicy,
+) -> Result<Vec<u8>, DataError> {
+    for _ in 0..=MAX_REDIRECTS {
+        let checked = check(url, is_gateway, policy).await?;
+        is_gateway = false;
+        let client = client(&checked, Duration::from_secs(policy.fetch_timeout_secs))
+            .map_err(fetch_error)?;
+        let mut res = client
+            .get(checked.url.clone())
+            .send()
+            .await
+            .map_err(fetch_error)?;
+        if res.status().is_redirection() {
+            let location = res
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| rejected("redirect without a location"))?;
+            let location = checked
+                .url
+                .join(location)
+                .map_err(|e| rejected(&format!("invalid redirect: {}", e)))?;
+            url = policy
+                .check(location.as_str())
+                .map_err(|e| DataError::ManifestRejected(e.to_string()))?;
+            continue;
+        }
+        res = res.error_for_status().map_err(fetch_error)?;
+
+        let too_large = || {
+            rejected(&format!(
+                "manifests can be at most {} bytes",
+                policy.max_manifest_bytes
+            ))
+        };
+        if res.content_length().unwrap_or(0) > policy.max_manifest_bytes as u64 {
+            return Err(too_large());
+        }
+        let mut body = vec![];
+        while let Some(chunk) = res.chunk().await.map_err(fetch_error)? {
+            if body.len() + chunk.len() > policy.max_manifest_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        return Ok(body);
+    }
+    Err(rejected(&format!("more than {} redirects", MAX_REDIRECTS)))
+}
+
+/// A manifest or webhook URL which has been checked, along with the addresses it may be fetched from.
+pub(super) struct CheckedUrl {
+    pub(super) url: Url,
+    domain: Option<String>,
+    addrs: Vec<SocketAddr>,
+}
+
+/// Resolve the host of a URL which has passed [`ManifestPolicy::check`],
+/// checking that every address is allowed unless it is a gateway.
+async fn check(
+    url: Url,
+    is_gateway: bool,
+    policy: &ManifestPolicy,
+) -> Result<CheckedUrl, DataError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(rejected(&format!(
+            "{} manifests can not be fetched directly",
+            url.scheme()
+        )));
+    }
+    pin(url, |ip| match is_gateway {
+        true => Ok(()),
+        false => policy.check_address(ip).map_err(|e| e.to_string()),
+    })
+    .await
+    .map_err(|reason| rejected(&reason))
+}
+
+/// Resolve the host of an `http` or `https` URL, checking every address with
+/// `check_address`. Returns why the URL is refused.
+pub(super) async fn pin(
+    url: Url,
+    check_address: impl Fn(IpAddr) -> Result<(), String>,
+) -> Result<CheckedUrl, String> {
+    let host = url.host_str().unwrap_or_default().to_owned();
+    let port = url.port_or_known_default().unwrap_or(443);
+    let (domain, addrs) = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => (None, vec![SocketAddr::new(ip, port)]),
+        Err(_) => (
+            Some(host.clone()),
+            tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .map_err(|e| format!("'{}' could not be resolved: {}", host, e))?
+                .collect(),
+        ),
+    };
+    if addrs.is_empty() {
+        return Err(format!("'{}' has no addresses", host));
+    }
+    for addr in &addrs {
+        check_address(addr.ip()).map_err(|e| format!("'{}' resolves to {}", host, e))?;
+    }
+    Ok(CheckedUrl { url, domain, addrs })
+}
+
+/// A client which only connects to the checked addresses, and does not follow redirects.
+pub(super) fn client(checked: &CheckedUrl, timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .no_proxy()
+        .timeout(timeout);
+    if let Some(domain) = &checked.domain {
+        builder = builder.resolve_to_addrs(domain, &checked.addrs);
+    }
+    builder.build()
+}
+
+fn rejected(reason: &str) -> DataError {
+    DataError::ManifestRejected(reason.to_owned())
+}
+
+fn fetch_error(e: reqwest::Error) -> DataError {
+    DataError::ManifestFetch(e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fetch, resolve};
+    use crate::data::DataError;
+    use crate::domain::job::field::{HashAlgorithm, ManifestHash, ManifestPolicy};
+    use crate::test::{async_runtime, ok, serve};
+    use reqwest::Url;
+
+    fn redirect(location: &str) -> String {
+        format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            location
+        )
+    }
+
+    fn local_policy() -> ManifestPolicy {
+        ManifestPolicy {
+            schemes: vec!["http".to_owned()],
+            allow_private_addresses: true,
+            ..ManifestPolicy::default()
+        }
+    }
+
+    /// A policy using a local server as both the IPFS gateway and the S3
+    /// endpoint, like a MinIO container would be.
+    fn gateway_policy(base: &str) -> ManifestPolicy {
+        ManifestPolicy {
+            ipfs_gateway: format!("{}/ipfs/", base),
+            s3_endpoint: base.to_owned(),
+            ..ManifestPolicy::default()
+        }
+    }
+
+    #[test]
+    fn private_addresses_are_not_fetched() {
+        let rt = async_runtime();
+        let (base, _) = serve(vec![ok("{}")]);
+        let policy = ManifestPolicy {
+            schemes: vec!["http".to_owned()],
+            ..ManifestPolicy::default()
+        };
+        for url in [
+            format!("{}/manifest.json", base),
+            "http://localhost/manifest.json".to_owned(),
+            "http://[::1]/manifest.json".to_owned(),
+            "http://[::127.0.0.1]/manifest.json".to_owned(),
+            "http://[::ffff:127.0.0.1]/manifest.json".to_owned(),
+            "http://[64:ff9b::7f00:1]/manifest.json".to_owned(),
+            "http://[2002:7f00:1::1]/manifest.json".to_owned(),
+        ] {
+            assert!(matches!(
+                rt.block_on(fetch(&url, None, &policy)),
+                Err(DataError::ManifestRejected(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn manifests_are_fetched_following_checked_redirects() {
+        let rt = async_runtime();
+        let (base, _) = serve(vec![redirect("/moved.json"), ok(r#"{"job":1}"#)]);
+        let policy = local_policy();
+        let body = rt
+            .block_on(fetch(&format!("{}/manifest.json", base), None, &policy))
+            .unwrap();
+        assert_eq!(body, br#"{"job":1}"#);
+
+        let (base, _) = serve(vec![redirect("file:///etc/passwd")]);
+        assert!(matches!(
+            rt.block_on(fetch(&format!("{}/manifest.json", base), None, &policy)),
+            Err(DataError::ManifestRejected(_))
+        ));
+
+        let (base, _) = serve(vec![redirect("/again"); 6]);
+        assert!(matches!(
+            rt.block_on(fetch(&format!("{}/manifest.json", base), None, &policy)),
+            Err(DataError::ManifestRejected(_))
+        ));
+    }
+
+    #[test]
+    fn large_manifests_are_rejected() {
+        let rt = async_runtime();
+        let (base, _) = serve(vec![ok(&"a".repeat(100))]);
+        let policy = ManifestPolicy {
+            max_manifest_bytes: 99,
+            ..local_policy()
+        };
+        assert!(matches!(
+            rt.block_on(fetch(&format!("{}/manifest.json", base), None, &policy)),
+            Err(DataError::ManifestRejected(_))
+        ));
+    }
+
+    #[test]
+    fn ipfs_and_s3_urls_resolve_to_gateways() {
+        let policy = ManifestPolicy::default();
+        let resolve = |url: &str| {
+            resolve(&Url::parse(url).unwrap(), &policy)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            resolve("ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/job.json"),
+            "https://ipfs.io/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/job.json"
+        );
+        assert_eq!(
+            resolve("s3://manifests/jobs/job.json"),
+            "https://s3.amazonaws.com/manifests/jobs/job.json"
+        );
+        assert_eq!(
+            resolve("https://example.com/job.json"),
+            "https://example.com/job.json"
+        );
+    }
+
+    #[test]
+    fn gateway_manifests_are_fetched_and_verified() {
+        let rt = async_runtime();
+        let manifest = r#"{"job":1}"#;
+        let hash = ManifestHash::new(&HashAlgorithm::Sha1.digest(manifest.as_bytes())).unwrap();
+
+        let (base, requests) = serve(vec![ok(manifest), ok(manifest)]);
+        let policy = gateway_policy(&base);
+        let body = rt
+            .block_on(fetch("s3://manifests/job.json", Some(&hash), &policy))
+            .unwrap();
+        assert_eq!(body, manifest.as_bytes());
+        assert_eq!(requests.recv().unwrap(), "GET /manifests/job.json HTTP/1.1");
+        rt.block_on(fetch("ipfs://bafybeigdyrzt", None, &policy))
+            .unwrap();
+        assert_eq!(requests.recv().unwrap(), "GET /ipfs/bafybeigdyrzt HTTP/1.1");
+
+        let (base, _) = serve(vec![ok(r#"{"job":2}"#)]);
+        let policy = gateway_policy(&base);
+        assert!(matches!(
+            rt.block_on(fetch("s3://manifests/job.json", Some(&hash), &policy)),
+            Err(DataError::ManifestHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn gateway_redirects_are_checked() {
+        let rt = async_runtime();
+        let (base, _) = serve(vec![redirect("http://127.0.0.1/admin")]);
+        let policy = ManifestPolicy {
+            schemes: vec!["http".to_owned(), "s3".to_owned()],
+            ..gateway_policy(&base)
+        };
+        assert!(matches!(
+            rt.block_on(fetch("s3://manifests/job.json", None, &policy)),
+            Err(DataError::ManifestRejected(_))
+        ));
+    }
+}
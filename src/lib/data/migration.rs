@@ -0,0 +1,230 @@
+//! Schema migrations.
+//!
+//! Each backend has its own migrations, in `migrations/sqlite` and
+//! `migrations/postgres`. They are embedded in the binary through
+//! [`SQLITE_MIGRATOR`](super::SQLITE_MIGRATOR) and
+//! [`POSTGRES_MIGRATOR`](super::POSTGRES_MIGRATOR), so they can be applied
+//! without the `sqlx` command line tool. A migration can only be reverted if it
+//! has a matching `.down.sql` file.
+
+use super::{query, DataError, DatabasePool};
+use serde::Serialize;
+
+/// [`Result`] alias for migration functions.
+type Result<T> = std::result::Result<T, DataError>;
+
+/// An embedded migration, and whether it has been applied to the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationStatus {
+    /// The version of the migration, which is the timestamp in its file name.
+    pub version: i64,
+    /// What the migration does, from its file name.
+    pub description: String,
+    /// Whether the migration has been applied.
+    pub applied: bool,
+    /// Whether the migration can be reverted.
+    pub reversible: bool,
+}
+
+/// Lists every embedded migration for the backend of the pool, oldest first.
+pub async fn status(pool: &DatabasePool) -> Result<Vec<MigrationStatus>> {
+    let applied = query::applied_migrations(pool).await?;
+    Ok(pool
+        .migrator()
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+            reversible: migration.migration_type.is_reversible(),
+        })
+        .collect())
+}
+
+/// Gets the versions of the embedded migrations which have not been applied.
+pub async fn pending(pool: &DatabasePool) -> Result<Vec<i64>> {
+    Ok(status(pool)
+        .await?
+        .into_iter()
+        .filter(|migration| !migration.applied)
+        .map(|migration| migration.version)
+        .collect())
+}
+
+/// Applies every pending migration, returning the versions which were applied.
+pub async fn run(pool: &DatabasePool) -> Result<Vec<i64>> {
+    let pending = pending(pool).await?;
+    match pool {
+        DatabasePool::Sqlite(sqlite) => pool.migrator().run(sqlite).await?,
+        DatabasePool::Postgres(postgres) => pool.migrator().run(postgres).await?,
+    }
+    Ok(pending)
+}
+
+/// Reverts the applied migrations newer than `target`, returning the versions
+/// which were reverted, newest first.
+///
+/// Only the most recently applied migration is reverted when no `target` is
+/// provided. Nothing is reverted if any of the migrations is irreversible.
+pub async fn revert(pool: &DatabasePool, target: Option<i64>) -> Result<Vec<i64>> {
+    let applied = query::applied_migrations(pool).await?;
+    let target = match target {
+        Some(target) => target,
+        None => match applied.as_slice() {
+            [.., previous, _] => *previous,
+            _ => 0,
+        },
+    };
+    let reverted: Vec<i64> = applied
+        .into_iter()
+        .rev()
+        .filter(|version| *version > target)
+        .collect();
+    for version in &reverted {
+        let reversible = pool.migrator().iter().any(|migration| {
+            migration.version == *version && migration.migration_type.is_down_migration()
+        });
+        if !reversible {
+            return Err(DataError::IrreversibleMigration(*version));
+        }
+    }
+    match pool {
+        DatabasePool::Sqlite(sqlite) => pool.migrator().undo(sqlite, target).await?,
+        DatabasePool::Postgres(postgres) => pool.migrator().undo(postgres, target).await?,
+    }
+    Ok(reverted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::test::{empty_db, sqlite_pool};
+    use crate::data::Database;
+    use crate::test::async_runtime;
+
+    const LEGACY_JOBS: &str = include_str!("../../../migrations/sqlite/20210716001908_api_key.sql");
+    const LOWERCASE_ESCROW_ADDRESSES: &str =
+        include_str!("../../../migrations/sqlite/20261018130000_lowercase_escrow_addresses.up.sql");
+
+    #[test]
+    fn run_applies_every_migration_once() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let db = empty_db().await;
+            let pool = db.get_pool();
+
+            let before = status(pool).await.unwrap();
+            assert!(before.iter().all(|migration| !migration.applied));
+
+            let applied = run(pool).await.unwrap();
+            assert_eq!(applied.len(), before.len());
+            assert!(pending(pool).await.unwrap().is_empty());
+            assert!(run(pool).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn reversible_migrations_are_reverted() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let db = empty_db().await;
+            let pool = db.get_pool();
+            run(pool).await.unwrap();
+
+            let latest = status(pool).await.unwrap().pop().unwrap();
+            assert!(latest.reversible);
+            assert_eq!(revert(pool, None).await.unwrap(), vec![latest.version]);
+            assert_eq!(pending(pool).await.unwrap(), vec![latest.version]);
+            assert_eq!(run(pool).await.unwrap(), vec![latest.version]);
+        });
+    }
+
+    /// Only the SQLite migrations have irreversible ones.
+    #[test]
+    fn irreversible_migrations_are_not_reverted() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let db = Database::new(":memory:").await.unwrap();
+            let pool = db.get_pool();
+            run(pool).await.unwrap();
+
+            let newest_irreversible = status(pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .rev()
+                .find(|migration| !migration.reversible)
+                .unwrap();
+            assert!(matches!(
+                revert(pool, Some(0)).await,
+                Err(DataError::IrreversibleMigration(version)) if version == newest_irreversible.version
+            ));
+            assert!(pending(pool).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn legacy_jobs_table_is_reconciled() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let db = Database::new(":memory:").await.unwrap();
+            let pool = sqlite_pool(&db);
+
+            sqlx::query(LEGACY_JOBS).execute(pool).await.unwrap();
+            sqlx::query(
+                "INSERT INTO jobs VALUES ('id', 'code', 'escrow', 'manifest', '2021-07-16 10:00:00', NULL, NULL, 3)",
+            )
+            .execute(pool)
+            .await
+            .unwrap();
+
+            run(db.get_pool()).await.unwrap();
+
+            let (manifest_url, posted): (Option<String>, i64) =
+                sqlx::query_as("SELECT manifest_url, posted FROM jobs WHERE shortcode = 'code'")
+                    .fetch_one(pool)
+                    .await
+                    .unwrap();
+            assert_eq!(manifest_url.as_deref(), Some("manifest"));
+            assert_eq!(posted, 1_626_429_600);
+        });
+    }
+
+    #[test]
+    fn escrow_addresses_are_lowercased() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let db = Database::new(":memory:").await.unwrap();
+            let pool = sqlite_pool(&db);
+            run(db.get_pool()).await.unwrap();
+
+            for (shortcode, escrow_id) in [
+                ("address", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+                ("other", "Escrow_ID"),
+            ] {
+                sqlx::query("INSERT INTO jobs (job_id, shortcode, escrow_id, posted, responses) VALUES (?, ?, ?, 1, 0)")
+                    .bind(shortcode)
+                    .bind(shortcode)
+                    .bind(escrow_id)
+                    .execute(pool)
+                    .await
+                    .unwrap();
+            }
+            sqlx::query(LOWERCASE_ESCROW_ADDRESSES)
+                .execute(pool)
+                .await
+                .unwrap();
+
+            let escrow_ids: Vec<String> =
+                sqlx::query_scalar("SELECT escrow_id FROM jobs ORDER BY shortcode")
+                    .fetch_all(pool)
+                    .await
+                    .unwrap();
+            assert_eq!(
+                escrow_ids,
+                ["0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", "Escrow_ID"]
+            );
+        });
+    }
+}
@@ -1,12 +1,24 @@
 //! Database models and queries.
 pub mod graph;
+pub mod manifest;
+pub mod migration;
 pub mod model;
 pub mod query;
+pub mod snapshot;
+pub mod webhook;
 
 use derive_more::{Display, From};
 use serde::{Deserialize, Serialize};
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::postgres::{PgPool, PgPoolOptions, Postgres};
+use sqlx::sqlite::{
+    SqliteAutoVacuum, SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions,
+    SqliteSynchronous,
+};
 use sqlx::Sqlite;
+use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
 /// The possible errors that may occur when working with a database.
@@ -15,40 +27,353 @@ pub enum DataError {
     /// Database error.
     #[error("database error: {0}")]
     Database(#[from] sqlx::Error),
+    /// The Graph could not be reached, or returned an error.
+    #[error("graph error: {0}")]
+    Graph(#[from] reqwest::Error),
+    /// The Graph responded without any data.
+    #[error("graph response did not contain any data")]
+    EmptyGraphResponse,
+    /// Migrations could not be applied or reverted.
+    #[error("migration error: {0}")]
+    Migrate(#[from] MigrateError),
+    /// The migration has no down migration, so it can not be reverted.
+    #[error("migration {0} can not be reverted")]
+    IrreversibleMigration(i64),
+    /// A file could not be read or written.
+    #[error("file error: {0}")]
+    Io(#[from] std::io::Error),
+    /// SQLite could not copy the database.
+    #[error("backup error: {0}")]
+    Backup(String),
+    /// The file is not a snapshot created by [`snapshot::create`].
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    /// The snapshot was taken at a different schema version than the database has.
+    #[error(
+        "snapshot schema version {snapshot} does not match database schema version {database}"
+    )]
+    SnapshotVersionMismatch {
+        /// The schema version of the snapshot.
+        snapshot: i64,
+        /// The schema version of the database.
+        database: i64,
+    },
+    /// The manifest URL, or a redirect from it, is not allowed by the
+    /// [`ManifestPolicy`](crate::domain::job::field::ManifestPolicy).
+    #[error("manifest rejected: {0}")]
+    ManifestRejected(String),
+    /// The manifest could not be fetched.
+    #[error("manifest fetch error: {0}")]
+    ManifestFetch(String),
+    /// The manifest does not have the hash recorded by its escrow.
+    #[error("manifest hash {actual} does not match {expected}")]
+    ManifestHashMismatch {
+        /// The hash recorded by the escrow.
+        expected: String,
+        /// The hash of the manifest which was fetched.
+        actual: String,
+    },
+    /// The webhook of a saved search could not be sent, or was not accepted.
+    #[error("webhook error: {0}")]
+    Webhook(String),
+    /// Another job already has the custom shortcode.
+    #[error("shortcode '{0}' is already taken")]
+    ShortCodeTaken(String),
+    /// The operation is not available with the database's [`Backend`].
+    #[error("{operation} is not supported with {backend}")]
+    UnsupportedBackend {
+        /// What was attempted.
+        operation: &'static str,
+        /// The backend of the database.
+        backend: Backend,
+    },
 }
 
+/// The kinds of database a connection string can refer to.
+///
+/// Connection strings without a recognized scheme, such as a bare file name or
+/// `:memory:`, are treated as SQLite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// SQLite, with a `sqlite:` connection string.
+    Sqlite,
+    /// PostgreSQL, with a `postgres://` or `postgresql://` connection string.
+    Postgres,
+}
+
+impl Backend {
+    /// Determine the backend from the scheme of a connection string.
+    pub fn from_url(url: &str) -> Option<Self> {
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("sqlite") => Some(Self::Sqlite),
+            Some("postgres" | "postgresql") => Some(Self::Postgres),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite => f.write_str("SQLite"),
+            Self::Postgres => f.write_str("PostgreSQL"),
+        }
+    }
+}
+
+/// How SQLite keeps its rollback journal.
+///
+/// See <https://www.sqlite.org/pragma.html#pragma_journal_mode>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    /// The journal is deleted at the end of each transaction.
+    Delete,
+    /// The journal is truncated at the end of each transaction.
+    Truncate,
+    /// The journal header is zeroed at the end of each transaction.
+    Persist,
+    /// The journal is kept in memory.
+    Memory,
+    /// A write-ahead log, which lets readers continue while a write is in progress.
+    Wal,
+    /// No journal is kept, so transactions can not be rolled back safely.
+    Off,
+}
+
+impl From<JournalMode> for SqliteJournalMode {
+    fn from(mode: JournalMode) -> Self {
+        match mode {
+            JournalMode::Delete => Self::Delete,
+            JournalMode::Truncate => Self::Truncate,
+            JournalMode::Persist => Self::Persist,
+            JournalMode::Memory => Self::Memory,
+            JournalMode::Wal => Self::Wal,
+            JournalMode::Off => Self::Off,
+        }
+    }
+}
+
+/// How often SQLite waits for writes to reach the disk.
+///
+/// See <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    /// Never wait.
+    Off,
+    /// Wait at critical moments. Safe from corruption when using [`JournalMode::Wal`].
+    Normal,
+    /// Wait after every transaction.
+    Full,
+    /// Like [`Synchronous::Full`], and also wait for the journal to be deleted.
+    Extra,
+}
+
+impl From<Synchronous> for SqliteSynchronous {
+    fn from(level: Synchronous) -> Self {
+        match level {
+            Synchronous::Off => Self::Off,
+            Synchronous::Normal => Self::Normal,
+            Synchronous::Full => Self::Full,
+            Synchronous::Extra => Self::Extra,
+        }
+    }
+}
+
+/// Settings for the connection pool, and for each SQLite connection in it.
+///
+/// Only the pool sizes apply to PostgreSQL.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// The most connections that are kept open at once.
+    pub max_connections: u32,
+    /// The fewest connections that are kept open at once.
+    pub min_connections: u32,
+    /// How the rollback journal is kept.
+    pub journal_mode: JournalMode,
+    /// How often writes wait to reach the disk.
+    pub synchronous: Synchronous,
+    /// How long a connection waits for a lock held by another connection
+    /// before failing with `SQLITE_BUSY`.
+    pub busy_timeout: Duration,
+    /// Whether foreign key constraints are enforced.
+    pub foreign_keys: bool,
+}
+
+/// The Default implementation uses up to 10 connections, a write-ahead log with
+/// [`Synchronous::Normal`], and a 5 second busy timeout.
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            journal_mode: JournalMode::Wal,
+            synchronous: Synchronous::Normal,
+            busy_timeout: Duration::from_secs(5),
+            foreign_keys: true,
+        }
+    }
+}
+
+/// The SQLite migrations, from `migrations/sqlite`.
+///
+/// They are embedded in the binary at compile time.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("migrations/sqlite");
+
+/// The PostgreSQL migrations, from `migrations/postgres`.
+///
+/// They are embedded in the binary at compile time.
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("migrations/postgres");
+
 /// Concrete database pool wrapper.
-pub type AppDatabase = Database<Sqlite>;
-/// Concrete database pool.
-pub type DatabasePool = sqlx::sqlite::SqlitePool;
-/// Concrete database transaction.
-pub type Transaction<'t> = sqlx::Transaction<'t, Sqlite>;
-/// Concrete database row.
-pub type AppDatabaseRow = sqlx::sqlite::SqliteRow;
-/// Concrete database query result.
-pub type AppQueryResult = sqlx::sqlite::SqliteQueryResult;
+pub type AppDatabase = Database;
+
+/// A pool of connections to either backend.
+#[derive(Debug, Clone)]
+pub enum DatabasePool {
+    /// A SQLite database.
+    Sqlite(SqlitePool),
+    /// A PostgreSQL database.
+    Postgres(PgPool),
+}
+
+impl DatabasePool {
+    /// The backend of the pool's database.
+    pub fn backend(&self) -> Backend {
+        match self {
+            Self::Sqlite(_) => Backend::Sqlite,
+            Self::Postgres(_) => Backend::Postgres,
+        }
+    }
+
+    /// The migrations which are expected to have been applied to the database.
+    pub fn migrator(&self) -> &'static Migrator {
+        match self {
+            Self::Sqlite(_) => &SQLITE_MIGRATOR,
+            Self::Postgres(_) => &POSTGRES_MIGRATOR,
+        }
+    }
+
+    /// Begins a new [`Transaction`].
+    pub async fn begin(&self) -> Result<Transaction, sqlx::Error> {
+        Ok(match self {
+            Self::Sqlite(pool) => Transaction::Sqlite(pool.begin().await?),
+            Self::Postgres(pool) => Transaction::Postgres(Box::new(pool.begin().await?)),
+        })
+    }
+
+    /// Closes every connection in the pool, waiting for those in use to be returned.
+    pub async fn close(&self) {
+        match self {
+            Self::Sqlite(pool) => pool.close().await,
+            Self::Postgres(pool) => pool.close().await,
+        }
+    }
+}
+
+/// A transaction on either backend.
+///
+/// It is rolled back if it is dropped without being committed.
+#[derive(Debug)]
+pub enum Transaction {
+    /// A transaction on a SQLite database.
+    Sqlite(sqlx::Transaction<'static, Sqlite>),
+    /// A transaction on a PostgreSQL database, boxed because it is much larger.
+    Postgres(Box<sqlx::Transaction<'static, Postgres>>),
+}
+
+impl Transaction {
+    /// Commits the transaction.
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::Sqlite(transaction) => transaction.commit().await,
+            Self::Postgres(transaction) => transaction.commit().await,
+        }
+    }
+
+    /// Rolls back the transaction.
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::Sqlite(transaction) => transaction.rollback().await,
+            Self::Postgres(transaction) => transaction.rollback().await,
+        }
+    }
+}
+
+/// Where a [`query`] runs: directly against the pool, or as part of a [`Transaction`].
+#[derive(Debug)]
+pub enum Conn<'c> {
+    /// Each statement uses whichever connection of the pool is free.
+    Pool(&'c DatabasePool),
+    /// Every statement runs in the transaction.
+    Transaction(&'c mut Transaction),
+}
+
+impl Conn<'_> {
+    /// Borrows the connection again, so it can be passed to more than one query.
+    pub fn reborrow(&mut self) -> Conn<'_> {
+        match self {
+            Self::Pool(pool) => Conn::Pool(pool),
+            Self::Transaction(transaction) => Conn::Transaction(transaction),
+        }
+    }
+}
+
+impl<'c> From<&'c DatabasePool> for Conn<'c> {
+    fn from(pool: &'c DatabasePool) -> Self {
+        Self::Pool(pool)
+    }
+}
+
+impl<'c> From<&'c mut Transaction> for Conn<'c> {
+    fn from(transaction: &'c mut Transaction) -> Self {
+        Self::Transaction(transaction)
+    }
+}
 
 /// Wrapper around a database pool.
-pub struct Database<D: sqlx::Database>(sqlx::Pool<D>);
-
-/// Implementation of the `Database` wrapper for `Sqlite`.
-impl Database<Sqlite> {
-    /// Create a new `Database` with the provided `connection_string`.
-    pub async fn new(connection_str: &str) -> Self {
-        let pool = sqlx::sqlite::SqlitePoolOptions::new()
-            .connect(connection_str)
-            .await;
-        match pool {
-            Ok(pool) => Self(pool),
-            Err(e) => {
-                eprintln!("{}\n", e);
-                eprintln!(
-                    "If the database has not yet been created, run: \n   $ sqlx database setup\n"
-                );
-                panic!("database connection error");
-            }
+pub struct Database(DatabasePool);
+
+impl Database {
+    /// Create a new `Database` with the provided `connection_string`, using the
+    /// default [`PoolConfig`].
+    ///
+    /// SQLite database files are created if they do not exist.
+    pub async fn new(connection_str: &str) -> Result<Self, DataError> {
+        Self::with_config(connection_str, &PoolConfig::default()).await
+    }
+
+    /// Create a new `Database` with the provided `connection_string`, whose
+    /// scheme selects the [`Backend`].
+    ///
+    /// SQLite database files are created if they do not exist. New SQLite
+    /// databases use incremental auto-vacuum, so that free pages can be
+    /// released by [`optimize`](query::optimize) without rewriting the whole file.
+    pub async fn with_config(connection_str: &str, config: &PoolConfig) -> Result<Self, DataError> {
+        if Backend::from_url(connection_str) == Some(Backend::Postgres) {
+            let pool = PgPoolOptions::new()
+                .max_connections(config.max_connections)
+                .min_connections(config.min_connections)
+                .connect(connection_str)
+                .await?;
+            return Ok(Self(DatabasePool::Postgres(pool)));
         }
+        let options = SqliteConnectOptions::from_str(connection_str)?
+            .create_if_missing(true)
+            .journal_mode(config.journal_mode.into())
+            .synchronous(config.synchronous.into())
+            .busy_timeout(config.busy_timeout)
+            .foreign_keys(config.foreign_keys)
+            .auto_vacuum(SqliteAutoVacuum::Incremental);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .connect_with(options)
+            .await?;
+        Ok(Self(DatabasePool::Sqlite(pool)))
     }
+
     /// Get a reference to the database connection pool.
     pub fn get_pool(&self) -> &DatabasePool {
         &self.0
@@ -97,17 +422,109 @@ impl FromStr for DbId {
 #[cfg(test)]
 pub mod test {
     use crate::data::*;
+    use sqlx::postgres::{PgConnectOptions, PgConnection};
+    use sqlx::Connection;
     use tokio::runtime::Handle;
 
+    #[test]
+    fn backend_is_selected_by_connection_string() {
+        assert_eq!(Backend::from_url("sqlite:data.db"), Some(Backend::Sqlite));
+        assert_eq!(Backend::from_url("sqlite::memory:"), Some(Backend::Sqlite));
+        assert_eq!(
+            Backend::from_url("postgres://localhost/gptx"),
+            Some(Backend::Postgres)
+        );
+        assert_eq!(
+            Backend::from_url("postgresql://localhost/gptx"),
+            Some(Backend::Postgres)
+        );
+        assert_eq!(Backend::from_url("mysql://localhost/gptx"), None);
+        assert_eq!(Backend::from_url("data.db"), None);
+    }
+
+    #[test]
+    fn postgres_connection_strings_connect_to_postgres() {
+        let rt = crate::test::async_runtime();
+        // Nothing listens on port 1, so this would only succeed by opening a SQLite file.
+        let res = rt.block_on(Database::new("postgres://127.0.0.1:1/gptx"));
+        assert!(matches!(res, Err(DataError::Database(_))));
+    }
+
+    #[test]
+    fn pool_config_is_applied_to_connections() {
+        let rt = crate::test::async_runtime();
+        rt.block_on(async {
+            let config = PoolConfig {
+                synchronous: Synchronous::Full,
+                busy_timeout: Duration::from_millis(1234),
+                ..PoolConfig::default()
+            };
+            let db = Database::with_config(":memory:", &config).await.unwrap();
+            let mut conn = sqlite_pool(&db).acquire().await.unwrap();
+
+            let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            assert_eq!(busy_timeout, 1234);
+            let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            assert_eq!(synchronous, 2);
+            let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+            assert!(foreign_keys);
+        });
+    }
+
+    /// The environment variable with the connection string of a PostgreSQL
+    /// server to run the tests against, instead of in-memory SQLite databases.
+    ///
+    /// Each test creates a database of its own on the server, which is not dropped.
+    pub const TEST_DATABASE_URL: &str = "GPTX_TEST_DATABASE_URL";
+
+    /// Creates a database with every migration applied, on the backend the tests run against.
     pub fn new_db(handle: &Handle) -> AppDatabase {
-        use sqlx::migrate::Migrator;
-        use std::path::Path;
         handle.block_on(async move {
-            let db = Database::new(":memory:").await;
-            let migrator = Migrator::new(Path::new("./migrations")).await.unwrap();
-            let pool = db.get_pool();
-            migrator.run(pool).await.unwrap();
+            let db = empty_db().await;
+            migration::run(db.get_pool()).await.unwrap();
             db
         })
     }
+
+    /// Creates a database without any migrations applied, on the backend the tests run against.
+    pub async fn empty_db() -> AppDatabase {
+        let url = match std::env::var(TEST_DATABASE_URL) {
+            Ok(url) => url,
+            Err(_) => return Database::new(":memory:").await.unwrap(),
+        };
+        let name = format!("gptx_test_{}", Uuid::new_v4().to_simple());
+        let mut server = PgConnection::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {}", name))
+            .execute(&mut server)
+            .await
+            .unwrap();
+        let options = PgConnectOptions::from_str(&url).unwrap().database(&name);
+        let pool = PgPoolOptions::new().connect_with(options).await.unwrap();
+        Database(DatabasePool::Postgres(pool))
+    }
+
+    /// The pool of a database which is known to be SQLite.
+    pub fn sqlite_pool(db: &AppDatabase) -> &SqlitePool {
+        match db.get_pool() {
+            DatabasePool::Sqlite(pool) => pool,
+            DatabasePool::Postgres(_) => panic!("not a SQLite database"),
+        }
+    }
+
+    /// Runs a statement which is the same on either backend.
+    pub async fn execute(pool: &DatabasePool, sql: &str) -> Result<u64, sqlx::Error> {
+        Ok(match pool {
+            DatabasePool::Sqlite(pool) => sqlx::query(sql).execute(pool).await?.rows_affected(),
+            DatabasePool::Postgres(pool) => sqlx::query(sql).execute(pool).await?.rows_affected(),
+        })
+    }
 }
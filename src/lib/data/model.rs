@@ -17,6 +17,17 @@ pub struct Job {
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
     pub(in crate::data) responses: i64,
+    pub(in crate::data) manifest_hash: Option<String>,
+    pub(in crate::data) manifest_status: String,
+    pub(in crate::data) manifest_checked: Option<i64>,
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) reward_token: Option<String>,
+    pub(in crate::data) reward_symbol: Option<String>,
+    pub(in crate::data) reward_decimals: i64,
+    pub(in crate::data) reward_balance: Option<String>,
+    pub(in crate::data) task_bid: Option<String>,
+    pub(in crate::data) total_tasks: Option<i64>,
+    pub(in crate::data) network: Option<String>,
 }
 
 /// Convert from a database model Job into a domain Job.
@@ -28,8 +39,40 @@ impl TryFrom<Job> for crate::domain::Job {
         Ok(Self {
             job_id: field::JobId::new(DbId::from_str(job.job_id.as_str())?),
             shortcode: field::ShortCode::from(job.shortcode),
-            escrow_id: field::EscrowId::new(job.escrow_id.as_str())?,
-            manifest_url: field::ManifestUrl::new(job.manifest_url),
+            escrow_id: field::EscrowId::legacy(job.escrow_id.as_str())?,
+            manifest_url: field::ManifestUrl::legacy(job.manifest_url),
+            manifest_verification: field::ManifestVerification {
+                hash: job
+                    .manifest_hash
+                    .as_deref()
+                    .map(field::ManifestHash::new)
+                    .transpose()?,
+                status: field::ManifestStatus::from_str(&job.manifest_status)?,
+                checked: job.manifest_checked.and_then(Time::from_timestamp),
+            },
+            task_type: job
+                .task_type
+                .as_deref()
+                .map(field::TaskType::from_str)
+                .transpose()?,
+            reward: field::Reward {
+                token: job.reward_token,
+                symbol: job.reward_symbol,
+                decimals: u8::try_from(job.reward_decimals)
+                    .map_err(|_| JobError::InvalidTokenAmount(job.reward_decimals.to_string()))?,
+                balance: job
+                    .reward_balance
+                    .as_deref()
+                    .map(field::TokenAmount::from_base_units)
+                    .transpose()?,
+                task_bid: job
+                    .task_bid
+                    .as_deref()
+                    .map(field::TokenAmount::from_base_units)
+                    .transpose()?,
+                total_tasks: job.total_tasks.map(u64::try_from).transpose()?,
+            },
+            network: job.network,
             posted: field::Posted::new(u64::try_from(job.posted)?),
             expires: field::Expires::new(job.expires.map(Time::from_naive_utc)),
             password: field::Password::new(job.password.unwrap_or_default())?,
@@ -38,15 +81,57 @@ impl TryFrom<Job> for crate::domain::Job {
     }
 }
 
-impl From<crate::data::graph::GraphJob> for NewJob {
-    fn from(req: crate::data::graph::GraphJob) -> Self {
+impl NewJob {
+    /// Data for a job read from the Graph, whose reward token is looked up in `tokens`.
+    ///
+    /// The manifest hash and amounts are only kept when they are well-formed, since
+    /// a job can not be rejected for what its escrow recorded.
+    pub fn from_graph(
+        req: crate::data::graph::GraphJob,
+        tokens: &crate::domain::job::field::TokenList,
+    ) -> Self {
+        use crate::domain::job::field::{
+            ManifestHash, ManifestStatus, TokenAmount, DEFAULT_DECIMALS,
+        };
+        let amount = |amount: Option<String>| {
+            amount.and_then(|amount| TokenAmount::from_base_units(&amount).ok())
+        };
+        let reward_balance = match (amount(req.amountAllocated), amount(req.amountPayout)) {
+            (Some(allocated), Some(payout)) => Some(allocated.saturating_sub(&payout)),
+            (allocated, _) => allocated,
+        };
+        let reward_token = req.eip20.map(|token| token.to_lowercase());
+        let token = reward_token
+            .as_deref()
+            .and_then(|address| tokens.find(address));
+        let manifest_url = req
+            .manifestUrl
+            .filter(|manifest_url| !manifest_url.trim().is_empty());
+        let manifest_hash = req
+            .manifestHash
+            .and_then(|hash| ManifestHash::new(&hash).ok())
+            .map(ManifestHash::into_inner);
         Self {
             job_id: DbId::new().into(),
             escrow_id: req.id,
-            manifest_url: req.manifestUrl.into(),
+            manifest_status: ManifestStatus::initial(
+                manifest_url.is_some(),
+                manifest_hash.is_some(),
+            )
+            .as_str()
+            .to_owned(),
+            manifest_url,
+            manifest_hash,
+            reward_symbol: token.as_ref().map(|token| token.symbol.clone()),
+            reward_decimals: token
+                .map_or(DEFAULT_DECIMALS, |token| token.decimals)
+                .into(),
+            reward_token,
+            reward_balance: reward_balance.map(TokenAmount::into_inner),
+            network: req.network,
             expires: None,
             password: None,
-            shortcode: ShortCode::default().into(),
+            shortcode: None,
             // parse as u64
             posted: req.timestamp.parse::<i64>().unwrap(),
         }
@@ -54,52 +139,79 @@ impl From<crate::data::graph::GraphJob> for NewJob {
 }
 
 /// Data required to run the [`get_job`](crate::data::query::get_job()) query to get a [`Job`] from the database.
-pub struct GetJob {
-    pub(in crate::data) shortcode: String,
+pub enum GetJob {
+    ShortCode(String),
+    /// The most recently posted job with the escrow_id.
+    EscrowId(String),
+    JobId(String),
 }
 
 impl From<crate::service::ask::GetJob> for GetJob {
     fn from(req: crate::service::ask::GetJob) -> Self {
-        Self {
-            shortcode: req.shortcode.into_inner(),
+        use crate::service::ask::JobRef;
+        match req.job {
+            JobRef::ShortCode(shortcode) => shortcode.into(),
+            JobRef::EscrowId(escrow_id) => GetJob::EscrowId(escrow_id.to_lowercase()),
         }
     }
 }
 
 impl From<ShortCode> for GetJob {
     fn from(shortcode: ShortCode) -> Self {
-        GetJob {
-            shortcode: shortcode.into_inner(),
-        }
+        GetJob::ShortCode(shortcode.into_inner())
+    }
+}
+
+impl From<crate::domain::job::field::JobId> for GetJob {
+    fn from(job_id: crate::domain::job::field::JobId) -> Self {
+        GetJob::JobId(job_id.into_inner().into())
     }
 }
 
 impl From<String> for GetJob {
     fn from(shortcode: String) -> Self {
-        GetJob { shortcode }
+        GetJob::ShortCode(shortcode)
     }
 }
 
 /// Data required to run the [`new_job`](crate::data::query::new_job()) query to add a [`Job`] to the database.
 pub struct NewJob {
     pub(in crate::data) job_id: String,
-    pub(in crate::data) shortcode: String,
+    /// The shortcode chosen by the client, otherwise one is generated.
+    pub(in crate::data) shortcode: Option<String>,
     pub(in crate::data) escrow_id: String,
     pub(in crate::data) manifest_url: Option<String>,
     pub(in crate::data) posted: i64,
     pub(in crate::data) expires: Option<i64>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) manifest_hash: Option<String>,
+    pub(in crate::data) manifest_status: String,
+    pub(in crate::data) reward_token: Option<String>,
+    pub(in crate::data) reward_symbol: Option<String>,
+    pub(in crate::data) reward_decimals: i64,
+    pub(in crate::data) reward_balance: Option<String>,
+    pub(in crate::data) network: Option<String>,
 }
 
+/// Jobs posted through the service have no recorded manifest hash, so their
+/// manifests can not be verified, and have no escrow balance.
 impl From<crate::service::ask::NewJob> for NewJob {
     fn from(req: crate::service::ask::NewJob) -> Self {
+        use crate::domain::job::field::{ManifestStatus, DEFAULT_DECIMALS};
         Self {
             job_id: DbId::new().into(),
-            escrow_id: req.escrow_id.into_inner(),
+            escrow_id: req.escrow_id.to_lowercase(),
             manifest_url: req.manifest_url.into_inner(),
             expires: req.expires.into_inner().map(|time| time.timestamp()),
             password: req.password.into_inner(),
-            shortcode: ShortCode::default().into(),
+            manifest_hash: None,
+            manifest_status: ManifestStatus::Unverifiable.as_str().to_owned(),
+            reward_token: None,
+            reward_symbol: None,
+            reward_decimals: DEFAULT_DECIMALS.into(),
+            reward_balance: None,
+            network: None,
+            shortcode: req.shortcode.into_inner().map(ShortCode::into_inner),
             posted: req.posted.into_inner() as i64,
         }
     }
@@ -117,7 +229,7 @@ pub struct UpdateJob {
 impl From<crate::service::ask::UpdateJob> for UpdateJob {
     fn from(req: crate::service::ask::UpdateJob) -> Self {
         Self {
-            escrow_id: req.escrow_id.into_inner(),
+            escrow_id: req.escrow_id.to_lowercase(),
             manifest_url: req.manifest_url.into_inner(),
             expires: req.expires.into_inner().map(|time| time.timestamp()),
             password: req.password.into_inner(),
@@ -125,3 +237,409 @@ impl From<crate::service::ask::UpdateJob> for UpdateJob {
         }
     }
 }
+
+/// A job whose manifest is waiting to be verified or classified.
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingManifest {
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) manifest_url: String,
+    pub(in crate::data) manifest_hash: Option<String>,
+    pub(in crate::data) reward_decimals: i64,
+}
+
+/// Convert from a database model PendingManifest into a domain PendingManifest.
+impl TryFrom<PendingManifest> for crate::domain::job::field::PendingManifest {
+    type Error = JobError;
+    fn try_from(pending: PendingManifest) -> Result<Self, Self::Error> {
+        use crate::domain::job::field;
+        use std::str::FromStr;
+        Ok(Self {
+            job_id: field::JobId::new(DbId::from_str(pending.job_id.as_str())?),
+            manifest_url: pending.manifest_url,
+            hash: pending
+                .manifest_hash
+                .as_deref()
+                .map(field::ManifestHash::new)
+                .transpose()?,
+            decimals: u8::try_from(pending.reward_decimals)
+                .map_err(|_| JobError::InvalidTokenAmount(pending.reward_decimals.to_string()))?,
+        })
+    }
+}
+
+/// Data required to run the [`record_manifest_check`](crate::data::query::record_manifest_check()) query.
+#[derive(Debug)]
+pub struct ManifestCheck {
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) manifest_status: String,
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) task_bid: Option<String>,
+    pub(in crate::data) total_tasks: Option<i64>,
+    pub(in crate::data) checked: i64,
+    pub(in crate::data) description: Option<String>,
+    pub(in crate::data) manifest_text: Option<String>,
+}
+
+impl From<crate::domain::job::field::ManifestCheck> for ManifestCheck {
+    fn from(check: crate::domain::job::field::ManifestCheck) -> Self {
+        let (description, manifest_text) = match check.search_text {
+            Some(text) => (text.description, Some(text.manifest)),
+            None => (None, None),
+        };
+        Self {
+            job_id: check.job_id.into_inner().into(),
+            manifest_status: check.status.as_str().to_owned(),
+            task_type: check
+                .task_type
+                .map(|task_type| task_type.as_str().to_owned()),
+            task_bid: check.task_bid.map(|task_bid| task_bid.into_inner()),
+            total_tasks: check
+                .total_tasks
+                .map(|total| i64::try_from(total).unwrap_or(i64::MAX)),
+            checked: check.checked.timestamp(),
+            description,
+            manifest_text,
+        }
+    }
+}
+
+/// Data required to run the [`list_jobs`](crate::data::query::list_jobs()) query to get a page of the job board.
+pub struct ListJobs {
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) search: Option<crate::domain::board::SearchQuery>,
+    pub(in crate::data) sort: Option<crate::domain::board::JobSort>,
+    pub(in crate::data) limit: i64,
+    pub(in crate::data) offset: i64,
+}
+
+impl From<crate::service::ask::ListJobs> for ListJobs {
+    fn from(req: crate::service::ask::ListJobs) -> Self {
+        use crate::domain::board::MAX_LIMIT;
+        Self {
+            task_type: req.task_type.map(|task_type| task_type.as_str().to_owned()),
+            search: req.search,
+            sort: req.sort,
+            limit: i64::from(req.limit.min(MAX_LIMIT)),
+            offset: i64::from(req.offset),
+        }
+    }
+}
+
+/// A job on the job board, as retrieved from the database.
+#[derive(Debug)]
+pub struct ListedJob {
+    pub(in crate::data) job: Job,
+    /// The matched text, with matches marked as in [`Snippet`](crate::domain::board::Snippet).
+    pub(in crate::data) snippet: Option<String>,
+}
+
+/// Convert from a database model ListedJob into a domain ListedJob.
+impl TryFrom<ListedJob> for crate::domain::board::ListedJob {
+    type Error = JobError;
+    fn try_from(listed: ListedJob) -> Result<Self, Self::Error> {
+        use crate::domain::board::Snippet;
+        Ok(Self {
+            job: listed.job.try_into()?,
+            snippet: listed.snippet.as_deref().map(Snippet::from_marked),
+        })
+    }
+}
+
+/// The number of listed jobs with a task type, as retrieved from the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct TaskTypeCount {
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) count: i64,
+}
+
+/// Convert from a database model TaskTypeCount into a domain TaskTypeFacet.
+impl TryFrom<TaskTypeCount> for crate::domain::board::TaskTypeFacet {
+    type Error = JobError;
+    fn try_from(count: TaskTypeCount) -> Result<Self, Self::Error> {
+        use crate::domain::job::field::TaskType;
+        use std::str::FromStr;
+        Ok(Self {
+            task_type: count
+                .task_type
+                .as_deref()
+                .map(TaskType::from_str)
+                .transpose()?,
+            count: u64::try_from(count.count)?,
+        })
+    }
+}
+
+/// A response bucket that is stored in, and retrieved from, the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ResponseBucket {
+    pub(in crate::data) bucket: i64,
+    pub(in crate::data) responses: i64,
+}
+
+/// Convert from a database model ResponseBucket into a domain ResponseBucket.
+impl TryFrom<ResponseBucket> for crate::domain::stats::ResponseBucket {
+    type Error = crate::domain::stats::StatsError;
+    fn try_from(bucket: ResponseBucket) -> Result<Self, Self::Error> {
+        use crate::domain::stats::StatsError;
+        Ok(Self {
+            start: Time::from_timestamp(bucket.bucket)
+                .ok_or(StatsError::InvalidBucket(bucket.bucket))?,
+            responses: u64::try_from(bucket.responses)
+                .map_err(|_| StatsError::InvalidBucket(bucket.bucket))?,
+        })
+    }
+}
+
+/// Data required to run the [`get_response_stats`](crate::data::query::get_response_stats()) query.
+pub struct GetResponseStats {
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) bucket_size: i64,
+    pub(in crate::data) since: i64,
+}
+
+impl From<crate::service::ask::GetResponseStats> for GetResponseStats {
+    fn from(req: crate::service::ask::GetResponseStats) -> Self {
+        let bucket_size = req.granularity.seconds();
+        let since = Time::now().timestamp() - i64::from(req.days) * 24 * 60 * 60;
+        Self {
+            shortcode: req.shortcode.map(ShortCode::into_inner),
+            bucket_size,
+            since: req.granularity.bucket_start(since),
+        }
+    }
+}
+
+/// The responses for a single job, split by channel, as retrieved from the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ResponseBreakdown {
+    pub(in crate::data) html: i64,
+    pub(in crate::data) raw: i64,
+    pub(in crate::data) api: i64,
+    pub(in crate::data) api_keys: i64,
+    pub(in crate::data) unique_visitors: i64,
+}
+
+/// Convert from a database model ResponseBreakdown into a domain ResponseBreakdown.
+impl TryFrom<ResponseBreakdown> for crate::domain::stats::ResponseBreakdown {
+    type Error = JobError;
+    fn try_from(breakdown: ResponseBreakdown) -> Result<Self, Self::Error> {
+        Ok(Self {
+            html: u64::try_from(breakdown.html)?,
+            raw: u64::try_from(breakdown.raw)?,
+            api: u64::try_from(breakdown.api)?,
+            api_keys: u64::try_from(breakdown.api_keys)?,
+            unique_visitors: u64::try_from(breakdown.unique_visitors)?,
+        })
+    }
+}
+
+/// The number of stored jobs, as retrieved from the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct JobCounts {
+    pub(in crate::data) total: i64,
+    pub(in crate::data) expired: i64,
+}
+
+/// Convert from a database model JobCounts into a domain JobCounts.
+impl TryFrom<JobCounts> for crate::domain::health::JobCounts {
+    type Error = JobError;
+    fn try_from(counts: JobCounts) -> Result<Self, Self::Error> {
+        Ok(Self {
+            total: u64::try_from(counts.total)?,
+            expired: u64::try_from(counts.expired)?,
+        })
+    }
+}
+
+/// Data required to run the [`save_search`](crate::data::query::save_search()) query to add a [`SavedSearch`] to the database.
+pub struct NewSavedSearch {
+    pub(in crate::data) search_id: String,
+    pub(in crate::data) api_key: Vec<u8>,
+    pub(in crate::data) network: Option<String>,
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) min_task_bid: Option<String>,
+    pub(in crate::data) webhook_url: Option<String>,
+    pub(in crate::data) created: i64,
+}
+
+impl NewSavedSearch {
+    /// A new saved search for the API key.
+    pub fn new(api_key: crate::web::api::ApiKey, req: crate::service::ask::NewSavedSearch) -> Self {
+        Self {
+            search_id: DbId::new().into(),
+            api_key: api_key.into_inner(),
+            network: req.network,
+            task_type: req.task_type.map(|task_type| task_type.as_str().to_owned()),
+            min_task_bid: req.min_task_bid,
+            webhook_url: req.webhook_url,
+            created: Time::now().timestamp(),
+        }
+    }
+}
+
+/// A saved search that is stored in, and retrieved from, the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct SavedSearch {
+    pub(in crate::data) search_id: String,
+    pub(in crate::data) network: Option<String>,
+    pub(in crate::data) task_type: Option<String>,
+    pub(in crate::data) min_task_bid: Option<String>,
+    pub(in crate::data) webhook_url: Option<String>,
+    pub(in crate::data) created: i64,
+}
+
+/// Convert from a database model SavedSearch into a domain SavedSearch.
+impl TryFrom<SavedSearch> for crate::domain::alert::SavedSearch {
+    type Error = JobError;
+    fn try_from(search: SavedSearch) -> Result<Self, Self::Error> {
+        use crate::domain::job::field::TaskType;
+        use std::str::FromStr;
+        Ok(Self {
+            search_id: DbId::from_str(search.search_id.as_str())?,
+            network: search.network,
+            task_type: search
+                .task_type
+                .as_deref()
+                .map(TaskType::from_str)
+                .transpose()?,
+            min_task_bid: search.min_task_bid,
+            webhook_url: search.webhook_url,
+            created: Time::from_timestamp(search.created)
+                .ok_or_else(|| JobError::InvalidDate(search.created.to_string()))?,
+        })
+    }
+}
+
+/// Data required to run the [`new_job_alert`](crate::data::query::new_job_alert()) query.
+pub struct NewJobAlert {
+    pub(in crate::data) search_id: String,
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) created: i64,
+    /// Alerts for searches with a webhook are sent straight away.
+    pub(in crate::data) next_attempt: Option<i64>,
+}
+
+impl NewJobAlert {
+    /// An alert that the job matched the search.
+    pub fn new(search: &crate::domain::alert::SavedSearch, job: &crate::domain::Job) -> Self {
+        let created = Time::now().timestamp();
+        Self {
+            search_id: search.search_id.clone().into(),
+            job_id: job.job_id.clone().into_inner().into(),
+            created,
+            next_attempt: search.webhook_url.as_ref().map(|_| created),
+        }
+    }
+}
+
+/// A job alert, with the job which matched, as retrieved from the database.
+#[derive(Debug)]
+pub struct JobAlert {
+    pub(in crate::data) job: Job,
+    pub(in crate::data) alert_id: i64,
+    pub(in crate::data) search_id: String,
+    pub(in crate::data) alert_created: i64,
+    pub(in crate::data) delivered: Option<i64>,
+}
+
+/// Convert from a database model JobAlert into a domain JobAlert.
+impl TryFrom<JobAlert> for crate::domain::alert::JobAlert {
+    type Error = JobError;
+    fn try_from(alert: JobAlert) -> Result<Self, Self::Error> {
+        use std::str::FromStr;
+        Ok(Self {
+            alert_id: alert.alert_id,
+            search_id: DbId::from_str(alert.search_id.as_str())?,
+            job: alert.job.try_into()?,
+            created: Time::from_timestamp(alert.alert_created)
+                .ok_or_else(|| JobError::InvalidDate(alert.alert_created.to_string()))?,
+            delivered: alert.delivered.and_then(Time::from_timestamp),
+        })
+    }
+}
+
+/// An alert waiting to be posted to its search's webhook, as retrieved from the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingWebhook {
+    pub(in crate::data) alert_id: i64,
+    pub(in crate::data) search_id: String,
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) webhook_url: String,
+    pub(in crate::data) created: i64,
+    pub(in crate::data) attempts: i64,
+}
+
+/// Convert from a database model PendingWebhook into a domain PendingWebhook.
+impl TryFrom<PendingWebhook> for crate::domain::alert::PendingWebhook {
+    type Error = JobError;
+    fn try_from(pending: PendingWebhook) -> Result<Self, Self::Error> {
+        use crate::domain::job::field::JobId;
+        use std::str::FromStr;
+        Ok(Self {
+            alert_id: pending.alert_id,
+            search_id: DbId::from_str(pending.search_id.as_str())?,
+            job_id: JobId::new(DbId::from_str(pending.job_id.as_str())?),
+            webhook_url: pending.webhook_url,
+            created: Time::from_timestamp(pending.created)
+                .ok_or_else(|| JobError::InvalidDate(pending.created.to_string()))?,
+            attempts: u32::try_from(pending.attempts)?,
+        })
+    }
+}
+
+/// Data required to run the [`new_claim`](crate::data::query::new_claim()) query to add a [`Claim`] to the database.
+pub struct NewClaim {
+    pub(in crate::data) claim_id: String,
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) api_key: Vec<u8>,
+    pub(in crate::data) created: i64,
+    pub(in crate::data) expires: i64,
+}
+
+impl NewClaim {
+    /// A new claim of a task of the job for the API key, which lasts for `lease`.
+    pub fn new(
+        api_key: crate::web::api::ApiKey,
+        job: &crate::domain::Job,
+        lease: std::time::Duration,
+    ) -> Self {
+        let created = Time::now().timestamp();
+        Self {
+            claim_id: DbId::new().into(),
+            job_id: job.job_id.clone().into_inner().into(),
+            shortcode: job.shortcode.clone().into_inner(),
+            api_key: api_key.into_inner(),
+            created,
+            expires: created + lease.as_secs() as i64,
+        }
+    }
+}
+
+/// A claim that is stored in, and retrieved from, the database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Claim {
+    pub(in crate::data) claim_id: String,
+    pub(in crate::data) job_id: String,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) created: i64,
+    pub(in crate::data) expires: i64,
+}
+
+/// Convert from a database model Claim into a domain Claim.
+impl TryFrom<Claim> for crate::domain::claim::Claim {
+    type Error = JobError;
+    fn try_from(claim: Claim) -> Result<Self, Self::Error> {
+        use crate::domain::job::field::JobId;
+        use std::str::FromStr;
+        Ok(Self {
+            claim_id: DbId::from_str(claim.claim_id.as_str())?,
+            job_id: JobId::new(DbId::from_str(claim.job_id.as_str())?),
+            shortcode: ShortCode::from(claim.shortcode),
+            created: Time::from_timestamp(claim.created)
+                .ok_or_else(|| JobError::InvalidDate(claim.created.to_string()))?,
+            expires: Time::from_timestamp(claim.expires)
+                .ok_or_else(|| JobError::InvalidDate(claim.expires.to_string()))?,
+        })
+    }
+}
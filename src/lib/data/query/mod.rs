@@ -0,0 +1,1324 @@
+//! Database queries.
+//!
+//! Each query runs on a [`Conn`], so it can run directly against the pool or as
+//! part of a [`Transaction`](crate::data::Transaction), and is passed on to the
+//! [`sqlite`] or [`postgres`] version of the query for the pool's backend.
+pub mod postgres;
+pub mod sqlite;
+
+use crate::data::{model, Conn, DataError, DatabasePool, Transaction};
+use crate::domain::board::SearchQuery;
+use crate::domain::job::field::ShortCodeConfig;
+use crate::domain::stats::Channel;
+use crate::web::api::ApiKey;
+use crate::ShortCode;
+
+/// [`Result`] alias for database query functions.
+type Result<T> = std::result::Result<T, DataError>;
+
+/// How many shortcodes are generated for a new job before giving up.
+const SHORTCODE_ATTEMPTS: usize = 5;
+
+/// The return value from the [`revoke_api_key`] function.
+pub enum RevocationStatus {
+    /// The [`ApiKey`] was successfully revoked.
+    Revoked,
+    /// The [`ApiKey`] was not found, so no revocation occuured.
+    NotFound,
+}
+
+/// Runs the version of the query `f` for the backend of `conn`, with `conn` as its last argument.
+macro_rules! dispatch {
+    ($conn:expr, $f:ident($($arg:expr),*)) => {
+        match $conn.into() {
+            Conn::Pool(DatabasePool::Sqlite(pool)) => sqlite::$f($($arg,)* pool).await,
+            Conn::Pool(DatabasePool::Postgres(pool)) => postgres::$f($($arg,)* pool).await,
+            Conn::Transaction(Transaction::Sqlite(transaction)) => {
+                sqlite::$f($($arg,)* &mut **transaction).await
+            }
+            Conn::Transaction(Transaction::Postgres(transaction)) => {
+                postgres::$f($($arg,)* &mut ***transaction).await
+            }
+        }
+    };
+}
+
+/// Increases the hit count for each [`crate::domain::Job`] as identified by the [`ShortCode`].
+pub async fn increase_hit_counts<'c>(
+    hits: &[(ShortCode, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(conn, increase_hit_counts(hits))
+}
+
+/// Adds responses to the hourly buckets of each [`crate::domain::Job`] as identified by the [`ShortCode`].
+///
+/// Each entry is `(shortcode, bucket start, responses)`.
+pub async fn record_response_stats<'c>(
+    stats: &[(ShortCode, i64, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(conn, record_response_stats(stats))
+}
+
+/// Gets the response time series for a single [`Job`](`crate::domain::Job`), or for all jobs when no shortcode is provided.
+pub async fn get_response_stats<'c, M>(
+    model: M,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::ResponseBucket>>
+where
+    M: Into<model::GetResponseStats>,
+{
+    dispatch!(conn, get_response_stats(model))
+}
+
+/// Merges the hourly buckets which start before `before` into one bucket per day.
+///
+/// Returns the number of hourly buckets that were merged.
+pub async fn rollup_response_stats<'c>(before: i64, conn: impl Into<Conn<'c>>) -> Result<u64> {
+    dispatch!(conn, rollup_response_stats(before))
+}
+
+/// Adds responses to the count for the [`Channel`] used to access each [`crate::domain::Job`].
+pub async fn record_channel_responses<'c>(
+    channels: &[(ShortCode, Channel, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(conn, record_channel_responses(channels))
+}
+
+/// Adds responses to the count for the [`ApiKey`] that fetched each [`crate::domain::Job`].
+pub async fn record_api_key_responses<'c>(
+    api_keys: &[(ShortCode, ApiKey, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(conn, record_api_key_responses(api_keys))
+}
+
+/// Records that each visitor accessed the [`crate::domain::Job`] on the day
+/// starting at the given timestamp. Repeat visits on the same day are ignored.
+///
+/// Each entry is `(shortcode, day start, visitor)`.
+pub async fn record_visitors<'c>(
+    visitors: &[(ShortCode, i64, String)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(conn, record_visitors(visitors))
+}
+
+/// Deletes the visitors recorded for days which start before `before`.
+pub async fn delete_old_visitors<'c>(before: i64, conn: impl Into<Conn<'c>>) -> Result<u64> {
+    dispatch!(conn, delete_old_visitors(before))
+}
+
+/// Gets the responses for a [`crate::domain::Job`], split by [`Channel`].
+pub async fn get_response_breakdown<'c>(
+    shortcode: &ShortCode,
+    conn: impl Into<Conn<'c>>,
+) -> Result<model::ResponseBreakdown> {
+    dispatch!(conn, get_response_breakdown(shortcode))
+}
+
+/// Checks that the database can answer a query.
+pub async fn ping<'c>(conn: impl Into<Conn<'c>>) -> Result<()> {
+    dispatch!(conn, ping())
+}
+
+/// Updates the query planner statistics, and releases free space where the
+/// backend does not do so itself.
+pub async fn optimize<'c>(conn: impl Into<Conn<'c>>) -> Result<()> {
+    dispatch!(conn, optimize())
+}
+
+/// Gets the versions of the migrations which have been applied successfully.
+///
+/// Nothing is returned if migrations have never been run against the database.
+pub async fn applied_migrations<'c>(conn: impl Into<Conn<'c>>) -> Result<Vec<i64>> {
+    dispatch!(conn, applied_migrations())
+}
+
+/// Counts the stored jobs, and how many of them have expired.
+pub async fn count_jobs<'c>(conn: impl Into<Conn<'c>>) -> Result<model::JobCounts> {
+    dispatch!(conn, count_jobs())
+}
+
+/// Gets a [`Job`](`crate::domain::Job`).
+pub async fn get_job<'c, M>(model: M, conn: impl Into<Conn<'c>>) -> Result<model::Job>
+where
+    M: Into<model::GetJob>,
+{
+    dispatch!(conn, get_job(model))
+}
+
+/// Adds a [`Job`](`crate::domain::Job`).
+///
+/// Jobs without a shortcode chosen by the client are given one generated with `shortcodes`.
+pub async fn new_job<'c, M>(
+    model: M,
+    shortcodes: &ShortCodeConfig,
+    conn: impl Into<Conn<'c>>,
+) -> Result<model::Job>
+where
+    M: Into<model::NewJob>,
+{
+    dispatch!(conn, new_job(model, shortcodes))
+}
+
+/// Fetches latest GraphJobs.
+pub async fn get_last_fetched_escrow_id_time<'c>(conn: impl Into<Conn<'c>>) -> Result<Option<i64>> {
+    dispatch!(conn, get_last_fetched_escrow_id_time())
+}
+
+/// Updates a [`Job`](`crate::domain::Job`).
+pub async fn update_job<'c, M>(model: M, conn: impl Into<Conn<'c>>) -> Result<model::Job>
+where
+    M: Into<model::UpdateJob>,
+{
+    dispatch!(conn, update_job(model))
+}
+
+/// Gets jobs whose manifests are waiting to be verified or classified.
+///
+/// These are jobs which have not been checked yet, and jobs whose manifests
+/// were unavailable when they were last checked before `retry_before`. Jobs
+/// without a recorded hash are only fetched once, to classify them.
+pub async fn get_pending_manifests<'c>(
+    retry_before: i64,
+    limit: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::PendingManifest>> {
+    dispatch!(conn, get_pending_manifests(retry_before, limit))
+}
+
+/// Records the result of fetching a job's manifest.
+///
+/// The job's searchable text is only replaced when the manifest was read.
+pub async fn record_manifest_check<'c, M>(model: M, conn: impl Into<Conn<'c>>) -> Result<()>
+where
+    M: Into<model::ManifestCheck> + std::fmt::Debug,
+{
+    dispatch!(conn, record_manifest_check(model))
+}
+
+/// Gets a page of the jobs on the job board.
+///
+/// Password protected and expired jobs are not listed.
+pub async fn list_jobs<'c, M>(model: M, conn: impl Into<Conn<'c>>) -> Result<Vec<model::ListedJob>>
+where
+    M: Into<model::ListJobs>,
+{
+    dispatch!(conn, list_jobs(model))
+}
+
+/// Counts the jobs on the job board with each task type, or only the jobs
+/// which match the `search`.
+pub async fn count_task_types<'c>(
+    search: Option<&SearchQuery>,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::TaskTypeCount>> {
+    dispatch!(conn, count_task_types(search))
+}
+
+/// Saves an [`ApiKey`].
+pub async fn save_api_key<'c>(api_key: ApiKey, conn: impl Into<Conn<'c>>) -> Result<ApiKey> {
+    dispatch!(conn, save_api_key(api_key))
+}
+
+/// Revokes an [`ApiKey`].
+pub async fn revoke_api_key<'c>(
+    api_key: ApiKey,
+    conn: impl Into<Conn<'c>>,
+) -> Result<RevocationStatus> {
+    dispatch!(conn, revoke_api_key(api_key))
+}
+
+/// Determines if the [`ApiKey`] is valid.
+pub async fn api_key_is_valid<'c>(api_key: ApiKey, conn: impl Into<Conn<'c>>) -> Result<bool> {
+    dispatch!(conn, api_key_is_valid(api_key))
+}
+
+/// Deletes all expired [`Jobs`](`crate::domain::Job`).
+pub async fn delete_expired<'c>(conn: impl Into<Conn<'c>>) -> Result<u64> {
+    dispatch!(conn, delete_expired())
+}
+
+/// Saves a search for an [`ApiKey`], unless the key already has `max_searches` saved searches.
+///
+/// Concurrent requests can not exceed the limit. Returns `None` when the limit has been reached.
+pub async fn save_search<'c>(
+    model: model::NewSavedSearch,
+    max_searches: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Option<model::SavedSearch>> {
+    dispatch!(conn, save_search(model, max_searches))
+}
+
+/// Gets the saved searches of an [`ApiKey`], oldest first.
+pub async fn get_saved_searches<'c>(
+    api_key: ApiKey,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::SavedSearch>> {
+    dispatch!(conn, get_saved_searches(api_key))
+}
+
+/// Deletes a saved search of an [`ApiKey`], along with its alerts.
+///
+/// Returns whether the search was found.
+pub async fn delete_saved_search<'c>(
+    search_id: &str,
+    api_key: ApiKey,
+    conn: impl Into<Conn<'c>>,
+) -> Result<bool> {
+    dispatch!(conn, delete_saved_search(search_id, api_key))
+}
+
+/// Gets the saved searches which could match a job with the task type: those
+/// for the task type, and those for any task type.
+pub async fn get_candidate_searches<'c>(
+    task_type: Option<&str>,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::SavedSearch>> {
+    dispatch!(conn, get_candidate_searches(task_type))
+}
+
+/// Records that a job matched a saved search.
+///
+/// Each job alerts a search at most once, so returns whether the alert is new.
+pub async fn new_job_alert<'c>(
+    model: model::NewJobAlert,
+    conn: impl Into<Conn<'c>>,
+) -> Result<bool> {
+    dispatch!(conn, new_job_alert(model))
+}
+
+/// Gets the alerts for the saved searches of an [`ApiKey`] which come after
+/// the alert `after`, oldest first.
+///
+/// Jobs which have been password protected since they matched are left out.
+pub async fn get_job_alerts<'c>(
+    api_key: ApiKey,
+    after: i64,
+    limit: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::JobAlert>> {
+    dispatch!(conn, get_job_alerts(api_key, after, limit))
+}
+
+/// Gets the id of the newest alert for the saved searches of an [`ApiKey`], or 0 when there are none.
+pub async fn get_latest_alert_id<'c>(api_key: ApiKey, conn: impl Into<Conn<'c>>) -> Result<i64> {
+    dispatch!(conn, get_latest_alert_id(api_key))
+}
+
+/// Gets alerts whose webhook is due to be sent at `now`.
+pub async fn get_pending_webhooks<'c>(
+    now: i64,
+    limit: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Vec<model::PendingWebhook>> {
+    dispatch!(conn, get_pending_webhooks(now, limit))
+}
+
+/// Records an attempt to send an alert's webhook.
+///
+/// `next_attempt` is when to send it again, and is `None` once it has been
+/// `delivered` or given up on.
+pub async fn record_webhook_attempt<'c>(
+    alert_id: i64,
+    delivered: Option<i64>,
+    next_attempt: Option<i64>,
+    conn: impl Into<Conn<'c>>,
+) -> Result<()> {
+    dispatch!(
+        conn,
+        record_webhook_attempt(alert_id, delivered, next_attempt)
+    )
+}
+
+/// Claims a task of a job for an [`ApiKey`], unless every task of the job is claimed.
+///
+/// Only claims whose lease lasts beyond `now` are counted against the job's
+/// `total_tasks`, and concurrent requests can not exceed it. Returns `None`
+/// when every task is claimed.
+pub async fn new_claim<'c>(
+    model: model::NewClaim,
+    now: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Option<model::Claim>> {
+    dispatch!(conn, new_claim(model, now))
+}
+
+/// Gets a claim of an [`ApiKey`] whose lease lasts beyond `now`.
+pub async fn get_claim<'c>(
+    claim_id: &str,
+    api_key: ApiKey,
+    now: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<Option<model::Claim>> {
+    dispatch!(conn, get_claim(claim_id, api_key, now))
+}
+
+/// Moves the end of the lease of a claim of an [`ApiKey`] to `expires`, as
+/// long as the claim is of the job with the shortcode and has not expired at `now`.
+///
+/// Returns whether the claim was found.
+pub async fn renew_claim<'c>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    expires: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<bool> {
+    dispatch!(
+        conn,
+        renew_claim(claim_id, shortcode, api_key, now, expires)
+    )
+}
+
+/// Releases a claim of an [`ApiKey`] on the job with the shortcode, freeing its task.
+///
+/// Returns whether the claim was found and had not expired at `now`.
+pub async fn release_claim<'c>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    conn: impl Into<Conn<'c>>,
+) -> Result<bool> {
+    dispatch!(conn, release_claim(claim_id, shortcode, api_key, now))
+}
+
+/// Deletes the claims whose lease ended at or before `now`.
+pub async fn delete_expired_claims<'c>(now: i64, conn: impl Into<Conn<'c>>) -> Result<u64> {
+    dispatch!(conn, delete_expired_claims(now))
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::data::test::*;
+    use crate::data::*;
+    use crate::domain::job::field::{ShortCodeConfig, TokenList};
+    use crate::test::async_runtime;
+
+    pub fn model_get_job(shortcode: &str) -> model::GetJob {
+        model::GetJob::ShortCode(shortcode.into())
+    }
+
+    pub fn model_new_job(shortcode: &str) -> model::NewJob {
+        use chrono::Utc;
+        model::NewJob {
+            job_id: DbId::new().into(),
+            escrow_id: format!("escrow_id for job '{}'", shortcode),
+            manifest_url: None,
+            shortcode: Some(shortcode.into()),
+            posted: Utc::now().timestamp(),
+            expires: None,
+            password: None,
+            manifest_hash: None,
+            manifest_status: "unverifiable".to_owned(),
+            reward_token: None,
+            reward_symbol: None,
+            reward_decimals: 18,
+            reward_balance: None,
+            network: None,
+        }
+    }
+
+    fn manifest_check(
+        job_id: &str,
+        status: &str,
+        task_type: Option<&str>,
+        checked: i64,
+    ) -> model::ManifestCheck {
+        model::ManifestCheck {
+            job_id: job_id.to_owned(),
+            manifest_status: status.to_owned(),
+            task_type: task_type.map(str::to_owned),
+            task_bid: None,
+            total_tasks: None,
+            checked,
+            description: None,
+            manifest_text: None,
+        }
+    }
+
+    #[test]
+    fn pending_manifests_are_selected_until_checked() {
+        use crate::data::graph::GraphJob;
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+        let hash = "A9993E364706816ABA3E25717850C26C9CD0D89D";
+
+        rt.block_on(async move {
+            let graph_job = |id: &str, hash: Option<&str>| {
+                let job = GraphJob {
+                    id: id.to_owned(),
+                    manifestUrl: Some(format!("https://example.com/{}.json", id)),
+                    manifestHash: hash.map(str::to_owned),
+                    eip20: None,
+                    amountAllocated: None,
+                    amountPayout: None,
+                    timestamp: "1".to_owned(),
+                    network: None,
+                };
+                model::NewJob::from_graph(job, &TokenList::default())
+            };
+            let pending = super::new_job(
+                graph_job("pending", Some(hash)),
+                &ShortCodeConfig::default(),
+                pool,
+            )
+            .await
+            .unwrap();
+            assert_eq!(pending.manifest_status, "pending");
+            assert_eq!(
+                pending.manifest_hash.as_deref(),
+                Some("a9993e364706816aba3e25717850c26c9cd0d89d")
+            );
+            let malformed = super::new_job(
+                graph_job("malformed", Some("abc")),
+                &ShortCodeConfig::default(),
+                pool,
+            )
+            .await
+            .unwrap();
+            assert_eq!(malformed.manifest_status, "unverifiable");
+            assert!(malformed.manifest_hash.is_none());
+            let retried = super::new_job(
+                graph_job("retried", Some(hash)),
+                &ShortCodeConfig::default(),
+                pool,
+            )
+            .await
+            .unwrap();
+            super::record_manifest_check(
+                manifest_check(&retried.job_id, "unavailable", None, 100),
+                pool,
+            )
+            .await
+            .unwrap();
+            let recent = super::new_job(
+                graph_job("recent", Some(hash)),
+                &ShortCodeConfig::default(),
+                pool,
+            )
+            .await
+            .unwrap();
+            super::record_manifest_check(
+                manifest_check(&recent.job_id, "unavailable", None, 300),
+                pool,
+            )
+            .await
+            .unwrap();
+
+            let mut found: Vec<String> = super::get_pending_manifests(200, 10, pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|pending| pending.job_id)
+                .collect();
+            found.sort();
+            // Jobs without a hash are still fetched once, to classify them.
+            let mut expected = vec![
+                pending.job_id.clone(),
+                malformed.job_id.clone(),
+                retried.job_id,
+            ];
+            expected.sort();
+            assert_eq!(found, expected);
+            super::record_manifest_check(
+                manifest_check(&malformed.job_id, "unverifiable", None, 300),
+                pool,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                super::get_pending_manifests(200, 10, pool)
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+
+            super::record_manifest_check(
+                model::ManifestCheck {
+                    task_bid: Some("25".to_owned()),
+                    total_tasks: Some(10),
+                    ..manifest_check(&pending.job_id, "verified", Some("text_free_entry"), 300)
+                },
+                pool,
+            )
+            .await
+            .unwrap();
+            let update = |manifest_url: &str| model::UpdateJob {
+                shortcode: pending.shortcode.clone(),
+                escrow_id: pending.escrow_id.clone(),
+                manifest_url: Some(manifest_url.to_owned()),
+                expires: None,
+                password: None,
+            };
+            let unchanged = super::update_job(update("https://example.com/pending.json"), pool)
+                .await
+                .unwrap();
+            assert_eq!(unchanged.manifest_status, "verified");
+            assert_eq!(unchanged.manifest_checked, Some(300));
+            assert_eq!(unchanged.task_type.as_deref(), Some("text_free_entry"));
+            assert_eq!(unchanged.task_bid.as_deref(), Some("25"));
+            // A manifest which could not be fetched again keeps its terms.
+            super::record_manifest_check(
+                manifest_check(&pending.job_id, "unavailable", None, 350),
+                pool,
+            )
+            .await
+            .unwrap();
+            let unavailable = super::get_job(model_get_job(&pending.shortcode), pool)
+                .await
+                .unwrap();
+            assert_eq!(unavailable.task_type.as_deref(), Some("text_free_entry"));
+            assert_eq!(unavailable.task_bid.as_deref(), Some("25"));
+            assert_eq!(unavailable.total_tasks, Some(10));
+            let changed = super::update_job(update("https://example.com/moved.json"), pool)
+                .await
+                .unwrap();
+            assert_eq!(changed.manifest_status, "pending");
+            assert!(changed.manifest_checked.is_none());
+            assert!(changed.task_type.is_none());
+            assert!(changed.task_bid.is_none());
+            assert!(changed.total_tasks.is_none());
+            // A new manifest without terms leaves none of the old ones behind.
+            super::record_manifest_check(
+                manifest_check(&pending.job_id, "verified", None, 400),
+                pool,
+            )
+            .await
+            .unwrap();
+            let checked = super::get_job(model_get_job(&pending.shortcode), pool)
+                .await
+                .unwrap();
+            assert!(checked.task_type.is_none());
+            assert!(checked.task_bid.is_none());
+            assert!(checked.total_tasks.is_none());
+        });
+    }
+
+    #[test]
+    fn job_board_lists_public_jobs_with_facets() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let jobs = [
+                ("binary1", 1, None, None),
+                ("binary2", 2, None, None),
+                ("text", 3, None, None),
+                ("unclassified", 4, None, None),
+                ("private", 5, Some("secret".to_owned()), None),
+                ("expired", 6, None, Some(1)),
+            ];
+            for (shortcode, posted, password, expires) in jobs {
+                let job = model::NewJob {
+                    posted,
+                    password,
+                    expires,
+                    ..model_new_job(shortcode)
+                };
+                let job = super::new_job(job, &ShortCodeConfig::default(), pool)
+                    .await
+                    .unwrap();
+                let task_type = match shortcode {
+                    "text" => "text_free_entry",
+                    "unclassified" => continue,
+                    _ => "image_label_binary",
+                };
+                super::record_manifest_check(
+                    manifest_check(&job.job_id, "verified", Some(task_type), 1),
+                    pool,
+                )
+                .await
+                .unwrap();
+            }
+
+            let list = |task_type: Option<&str>, limit, offset| model::ListJobs {
+                task_type: task_type.map(str::to_owned),
+                search: None,
+                sort: None,
+                limit,
+                offset,
+            };
+            let shortcodes = |jobs: Vec<model::ListedJob>| {
+                jobs.into_iter()
+                    .map(|listed| listed.job.shortcode)
+                    .collect::<Vec<_>>()
+            };
+            let all = super::list_jobs(list(None, 10, 0), pool).await.unwrap();
+            assert_eq!(
+                shortcodes(all),
+                ["unclassified", "text", "binary2", "binary1"]
+            );
+            let binary = super::list_jobs(list(Some("image_label_binary"), 10, 0), pool)
+                .await
+                .unwrap();
+            assert_eq!(shortcodes(binary), ["binary2", "binary1"]);
+            let page = super::list_jobs(list(Some("image_label_binary"), 1, 1), pool)
+                .await
+                .unwrap();
+            assert_eq!(shortcodes(page), ["binary1"]);
+
+            let facets: Vec<_> = super::count_task_types(None, pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|facet| (facet.task_type, facet.count))
+                .collect();
+            assert_eq!(
+                facets,
+                [
+                    (Some("image_label_binary".to_owned()), 2),
+                    (None, 1),
+                    (Some("text_free_entry".to_owned()), 1),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn job_board_sorts_by_exact_amounts() {
+        use crate::data::graph::GraphJob;
+        use crate::domain::board::JobSort;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            // Amounts which sort differently as strings and as numbers.
+            let jobs = [
+                ("0xsmall", "9", "1", Some("0.9")),
+                ("0xlarge", "100000000000000000000", "1", Some("10")),
+                ("0xnone", "5", "5", None),
+            ];
+            for (posted, (id, allocated, payout, bid)) in jobs.into_iter().enumerate() {
+                let graph_job = GraphJob {
+                    id: id.to_owned(),
+                    manifestUrl: None,
+                    manifestHash: None,
+                    eip20: Some("0xD1BA9BAC957322D6E8C07A160A3A8DA11A0D2867".to_owned()),
+                    amountAllocated: Some(allocated.to_owned()),
+                    amountPayout: Some(payout.to_owned()),
+                    timestamp: posted.to_string(),
+                    network: None,
+                };
+                let graph_job = model::NewJob::from_graph(graph_job, &TokenList::default());
+                let job = super::new_job(graph_job, &ShortCodeConfig::default(), pool)
+                    .await
+                    .unwrap();
+                assert_eq!(job.reward_symbol.as_deref(), Some("HMT"));
+                let check = model::ManifestCheck {
+                    task_bid: bid.map(|bid| {
+                        crate::domain::job::field::TokenAmount::from_decimal(bid, 18)
+                            .unwrap()
+                            .into_inner()
+                    }),
+                    ..manifest_check(&job.job_id, "unverifiable", None, 1)
+                };
+                super::record_manifest_check(check, pool).await.unwrap();
+            }
+
+            let escrows = |sort| async move {
+                let list = model::ListJobs {
+                    task_type: None,
+                    search: None,
+                    sort: Some(sort),
+                    limit: 10,
+                    offset: 0,
+                };
+                super::list_jobs(list, pool)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|listed| listed.job)
+                    .map(|job| (job.escrow_id, job.reward_balance, job.task_bid))
+                    .collect::<Vec<_>>()
+            };
+            let by_bid = escrows(JobSort::TaskBid).await;
+            assert_eq!(
+                by_bid.iter().map(|job| job.0.as_str()).collect::<Vec<_>>(),
+                ["0xlarge", "0xsmall", "0xnone"]
+            );
+            assert_eq!(by_bid[0].2.as_deref(), Some("10000000000000000000"));
+            let by_balance = escrows(JobSort::Balance).await;
+            assert_eq!(
+                by_balance
+                    .iter()
+                    .map(|job| (job.0.as_str(), job.1.as_deref()))
+                    .collect::<Vec<_>>(),
+                [
+                    ("0xlarge", Some("99999999999999999999")),
+                    ("0xsmall", Some("8")),
+                    ("0xnone", Some("0")),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn job_board_is_searched_by_text() {
+        use crate::domain::board::{SearchQuery, Snippet};
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let jobs = [
+                (
+                    "cats",
+                    "0xCA7",
+                    "Is there a cat in the photo?",
+                    "image_label_binary",
+                ),
+                (
+                    "dogs",
+                    "0xD06",
+                    "Draw a box around every dog",
+                    "image_label_area_select",
+                ),
+                ("private", "0xCA75", "Is there a cat?", "image_label_binary"),
+            ];
+            for (shortcode, escrow_id, description, task_type) in jobs {
+                let job = model::NewJob {
+                    escrow_id: escrow_id.to_owned(),
+                    password: (shortcode == "private").then(|| "secret".to_owned()),
+                    ..model_new_job(shortcode)
+                };
+                let job = super::new_job(job, &ShortCodeConfig::default(), pool)
+                    .await
+                    .unwrap();
+                let check = model::ManifestCheck {
+                    description: Some(description.to_owned()),
+                    manifest_text: Some(task_type.to_owned()),
+                    ..manifest_check(&job.job_id, "verified", Some(task_type), 1)
+                };
+                super::record_manifest_check(check, pool).await.unwrap();
+            }
+
+            let search = |search: &str| {
+                let list = model::ListJobs {
+                    task_type: None,
+                    search: SearchQuery::new(search),
+                    sort: None,
+                    limit: 10,
+                    offset: 0,
+                };
+                async move { super::list_jobs(list, pool).await.unwrap() }
+            };
+            let found = search("CAT").await;
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].job.shortcode, "cats");
+            let snippet = Snippet::from_marked(found[0].snippet.as_deref().unwrap());
+            assert!(snippet
+                .0
+                .iter()
+                .any(|part| part.matched && part.text == "cat"));
+            assert_eq!(search("0xd0").await[0].job.shortcode, "dogs");
+            assert_eq!(search("area select").await[0].job.shortcode, "dogs");
+            assert!(search("cat box").await.is_empty());
+            assert!(search("\"AND (").await.is_empty());
+
+            let facets = super::count_task_types(SearchQuery::new("box").as_ref(), pool)
+                .await
+                .unwrap();
+            assert_eq!(facets.len(), 1);
+            assert_eq!(
+                facets[0].task_type.as_deref(),
+                Some("image_label_area_select")
+            );
+
+            // Changing the manifest clears its text until it is fetched again.
+            let dogs = super::get_job(model_get_job("dogs"), pool).await.unwrap();
+            let update = model::UpdateJob {
+                shortcode: dogs.shortcode,
+                escrow_id: dogs.escrow_id,
+                manifest_url: Some("https://example.com/moved.json".to_owned()),
+                expires: None,
+                password: None,
+            };
+            super::update_job(update, pool).await.unwrap();
+            assert!(search("box").await.is_empty());
+            assert_eq!(search("0xd06").await.len(), 1);
+            execute(pool, "DELETE FROM jobs WHERE shortcode = 'cats'")
+                .await
+                .unwrap();
+            assert!(search("cat").await.is_empty());
+        });
+    }
+
+    #[test]
+    fn saved_searches_are_alerted_once_per_job() {
+        use crate::web::api::ApiKey;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let api_key = ApiKey::default();
+            super::save_api_key(api_key.clone(), pool).await.unwrap();
+            let search =
+                |task_type: Option<&str>, webhook_url: Option<&str>| model::NewSavedSearch {
+                    search_id: DbId::new().into(),
+                    api_key: api_key.clone().into_inner(),
+                    network: None,
+                    task_type: task_type.map(str::to_owned),
+                    min_task_bid: None,
+                    webhook_url: webhook_url.map(str::to_owned),
+                    created: 1,
+                };
+            let binary = super::save_search(search(Some("image_label_binary"), None), 2, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            let any = super::save_search(search(None, Some("https://example.com/hook")), 2, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(super::save_search(search(None, None), 2, pool)
+                .await
+                .unwrap()
+                .is_none());
+            assert_eq!(
+                super::get_saved_searches(api_key.clone(), pool)
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+
+            let candidates = |task_type: Option<&'static str>| async move {
+                let mut found: Vec<_> = super::get_candidate_searches(task_type, pool)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|search| search.search_id)
+                    .collect();
+                found.sort();
+                found
+            };
+            let mut both = vec![binary.search_id.clone(), any.search_id.clone()];
+            both.sort();
+            assert_eq!(candidates(Some("image_label_binary")).await, both);
+            assert_eq!(
+                candidates(Some("text_free_entry")).await,
+                [any.search_id.as_str()]
+            );
+            assert_eq!(candidates(None).await, [any.search_id.as_str()]);
+
+            let job = super::new_job(model_new_job("alerted"), &ShortCodeConfig::default(), pool)
+                .await
+                .unwrap();
+            let alert = |search: &model::SavedSearch| model::NewJobAlert {
+                search_id: search.search_id.clone(),
+                job_id: job.job_id.clone(),
+                created: 10,
+                next_attempt: search.webhook_url.as_ref().map(|_| 10),
+            };
+            assert!(super::new_job_alert(alert(&binary), pool).await.unwrap());
+            assert!(super::new_job_alert(alert(&any), pool).await.unwrap());
+            assert!(!super::new_job_alert(alert(&any), pool).await.unwrap());
+
+            let alerts = super::get_job_alerts(api_key.clone(), 0, 10, pool)
+                .await
+                .unwrap();
+            assert_eq!(alerts.len(), 2);
+            assert_eq!(alerts[0].job.shortcode, "alerted");
+            let latest = super::get_latest_alert_id(api_key.clone(), pool)
+                .await
+                .unwrap();
+            assert_eq!(latest, alerts[1].alert_id);
+            assert!(super::get_job_alerts(api_key.clone(), latest, 10, pool)
+                .await
+                .unwrap()
+                .is_empty());
+
+            // Only the search with a webhook is sent, once it is due.
+            assert!(super::get_pending_webhooks(9, 10, pool)
+                .await
+                .unwrap()
+                .is_empty());
+            let pending = super::get_pending_webhooks(10, 10, pool).await.unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].webhook_url, "https://example.com/hook");
+            super::record_webhook_attempt(pending[0].alert_id, Some(11), None, pool)
+                .await
+                .unwrap();
+            assert!(super::get_pending_webhooks(100, 10, pool)
+                .await
+                .unwrap()
+                .is_empty());
+
+            assert!(
+                super::delete_saved_search(&any.search_id, api_key.clone(), pool)
+                    .await
+                    .unwrap()
+            );
+            assert!(
+                !super::delete_saved_search(&any.search_id, api_key.clone(), pool)
+                    .await
+                    .unwrap()
+            );
+            assert_eq!(
+                super::get_job_alerts(api_key.clone(), 0, 10, pool)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+            // Revoking the key removes its searches and their alerts.
+            super::revoke_api_key(api_key.clone(), pool).await.unwrap();
+            assert!(super::get_saved_searches(api_key.clone(), pool)
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(super::get_latest_alert_id(api_key, pool).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn tasks_are_claimed_up_to_total_tasks() {
+        use crate::web::api::ApiKey;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let api_key = ApiKey::default();
+            super::save_api_key(api_key.clone(), pool).await.unwrap();
+            let job = super::new_job(model_new_job("claimed"), &ShortCodeConfig::default(), pool)
+                .await
+                .unwrap();
+            let claim = |expires: i64| model::NewClaim {
+                claim_id: DbId::new().into(),
+                job_id: job.job_id.clone(),
+                shortcode: job.shortcode.clone(),
+                api_key: api_key.clone().into_inner(),
+                created: 10,
+                expires,
+            };
+            // Jobs whose manifest has not been read have no tasks to claim.
+            assert!(super::new_claim(claim(100), 10, pool)
+                .await
+                .unwrap()
+                .is_none());
+            let check = model::ManifestCheck {
+                total_tasks: Some(2),
+                ..manifest_check(&job.job_id, "unverifiable", None, 10)
+            };
+            super::record_manifest_check(check, pool).await.unwrap();
+
+            let first = super::new_claim(claim(100), 10, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            let second = super::new_claim(claim(20), 10, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(super::new_claim(claim(100), 10, pool)
+                .await
+                .unwrap()
+                .is_none());
+            // Once the second lease runs out, its task can be claimed again.
+            assert!(
+                super::get_claim(&second.claim_id, api_key.clone(), 20, pool)
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+            let third = super::new_claim(claim(100), 20, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(super::delete_expired_claims(20, pool).await.unwrap(), 1);
+
+            assert!(
+                super::renew_claim(&first.claim_id, "claimed", api_key.clone(), 20, 200, pool)
+                    .await
+                    .unwrap()
+            );
+            assert!(
+                !super::renew_claim(&first.claim_id, "other", api_key.clone(), 20, 200, pool)
+                    .await
+                    .unwrap()
+            );
+            assert!(!super::renew_claim(
+                &first.claim_id,
+                "claimed",
+                ApiKey::default(),
+                20,
+                200,
+                pool
+            )
+            .await
+            .unwrap());
+            let renewed = super::get_claim(&first.claim_id, api_key.clone(), 150, pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(renewed.shortcode, "claimed");
+            assert_eq!(renewed.expires, 200);
+
+            assert!(
+                super::release_claim(&third.claim_id, "claimed", api_key.clone(), 20, pool)
+                    .await
+                    .unwrap()
+            );
+            assert!(
+                !super::release_claim(&third.claim_id, "claimed", api_key.clone(), 20, pool)
+                    .await
+                    .unwrap()
+            );
+            // Revoking the key releases its claims.
+            super::revoke_api_key(api_key.clone(), pool).await.unwrap();
+            assert!(super::get_claim(&first.claim_id, api_key, 20, pool)
+                .await
+                .unwrap()
+                .is_none());
+            assert_eq!(super::delete_expired_claims(1000, pool).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn job_new_and_get() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let job = rt.block_on(async move {
+            super::new_job(
+                model_new_job("1"),
+                &ShortCodeConfig::default(),
+                &pool.clone(),
+            )
+            .await
+        });
+        assert!(job.is_ok());
+        let job = job.unwrap();
+        assert!(job.shortcode == "1");
+        assert!(job.escrow_id == format!("escrow_id for job '1'"));
+    }
+
+    #[test]
+    fn job_get_by_escrow_id_is_most_recent() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            for (shortcode, posted) in [("old", 1), ("new", 2)] {
+                let job = model::NewJob {
+                    escrow_id: "0xescrow".to_owned(),
+                    posted,
+                    ..model_new_job(shortcode)
+                };
+                super::new_job(job, &ShortCodeConfig::default(), pool)
+                    .await
+                    .unwrap();
+            }
+            let job = super::get_job(model::GetJob::EscrowId("0xescrow".to_owned()), pool)
+                .await
+                .unwrap();
+            assert_eq!(job.shortcode, "new");
+            assert!(matches!(
+                super::get_job(model::GetJob::EscrowId("missing".to_owned()), pool).await,
+                Err(DataError::Database(sqlx::Error::RowNotFound))
+            ));
+        });
+    }
+
+    #[test]
+    fn taken_shortcodes_are_retried_or_rejected() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            super::new_job(model_new_job("taken"), &ShortCodeConfig::default(), pool)
+                .await
+                .unwrap();
+            assert!(matches!(
+                super::new_job(model_new_job("taken"), &ShortCodeConfig::default(), pool).await,
+                Err(DataError::ShortCodeTaken(shortcode)) if shortcode == "taken"
+            ));
+
+            let generated = model::NewJob {
+                shortcode: None,
+                ..model_new_job("taken")
+            };
+            let job = super::new_job(generated, &ShortCodeConfig::default(), pool)
+                .await
+                .unwrap();
+            assert_ne!(job.shortcode, "taken");
+        });
+    }
+
+    #[test]
+    fn response_stats_aggregate_by_granularity() {
+        use crate::domain::stats::Granularity;
+        use crate::{ShortCode, Time};
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let hour = Granularity::Hourly.bucket_start(Time::now().timestamp());
+        let stats = rt.block_on(async move {
+            let shortcode = ShortCode::from("1");
+            super::record_response_stats(&[(shortcode.clone(), hour, 2)], pool).await?;
+            super::record_response_stats(
+                &[(shortcode, hour, 3), (ShortCode::from("2"), hour, 4)],
+                pool,
+            )
+            .await?;
+
+            let job = model::GetResponseStats {
+                shortcode: Some("1".into()),
+                bucket_size: Granularity::Hourly.seconds(),
+                since: 0,
+            };
+            let all = model::GetResponseStats {
+                shortcode: None,
+                bucket_size: Granularity::Daily.seconds(),
+                since: 0,
+            };
+            Ok::<_, DataError>((
+                super::get_response_stats(job, pool).await?,
+                super::get_response_stats(all, pool).await?,
+            ))
+        });
+        let (job, all) = stats.unwrap();
+        assert_eq!(job.len(), 1);
+        assert_eq!(job[0].bucket, hour);
+        assert_eq!(job[0].responses, 5);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].bucket, Granularity::Daily.bucket_start(hour));
+        assert_eq!(all[0].responses, 9);
+    }
+
+    #[test]
+    fn response_breakdown_counts_channels() {
+        use crate::domain::stats::Channel;
+        use crate::web::api::ApiKey;
+        use crate::ShortCode;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let breakdown = rt.block_on(async move {
+            let shortcode = ShortCode::from("1");
+            let api_key = ApiKey::default();
+            super::record_channel_responses(
+                &[
+                    (shortcode.clone(), Channel::Html, 3),
+                    (shortcode.clone(), Channel::Api, 1),
+                ],
+                pool,
+            )
+            .await?;
+            super::record_channel_responses(&[(shortcode.clone(), Channel::Api, 1)], pool).await?;
+            super::record_api_key_responses(&[(shortcode.clone(), api_key, 2)], pool).await?;
+            super::record_visitors(
+                &[
+                    (shortcode.clone(), 0, "a".into()),
+                    (shortcode.clone(), 0, "b".into()),
+                ],
+                pool,
+            )
+            .await?;
+            super::record_visitors(&[(shortcode.clone(), 0, "a".into())], pool).await?;
+            // Visitors are counted once even when they return on another day.
+            super::record_visitors(&[(shortcode.clone(), 86400, "a".into())], pool).await?;
+            let breakdown = super::get_response_breakdown(&shortcode, pool).await?;
+            assert_eq!(super::delete_old_visitors(86400, pool).await?, 2);
+            let purged = super::get_response_breakdown(&shortcode, pool).await?;
+            Ok::<_, DataError>((breakdown, purged))
+        });
+        let (breakdown, purged) = breakdown.unwrap();
+        assert_eq!(breakdown.html, 3);
+        assert_eq!(breakdown.raw, 0);
+        assert_eq!(breakdown.api, 2);
+        assert_eq!(breakdown.api_keys, 1);
+        assert_eq!(breakdown.unique_visitors, 2);
+        assert_eq!(purged.unique_visitors, 1);
+    }
+
+    #[test]
+    fn hit_counts_follow_transaction() {
+        use crate::ShortCode;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let responses = rt.block_on(async move {
+            super::new_job(model_new_job("1"), &ShortCodeConfig::default(), pool).await?;
+            super::new_job(model_new_job("2"), &ShortCodeConfig::default(), pool).await?;
+            let hits = [(ShortCode::from("1"), 3), (ShortCode::from("2"), 5)];
+
+            let mut transaction = pool.begin().await?;
+            super::increase_hit_counts(&hits, &mut transaction).await?;
+            transaction.rollback().await?;
+            let rolled_back = super::get_job(model_get_job("1"), pool).await?.responses;
+
+            let mut transaction = pool.begin().await?;
+            super::increase_hit_counts(&hits, &mut transaction).await?;
+            transaction.commit().await?;
+            Ok::<_, DataError>((
+                rolled_back,
+                super::get_job(model_get_job("1"), pool).await?.responses,
+                super::get_job(model_get_job("2"), pool).await?.responses,
+            ))
+        });
+        assert_eq!(responses.unwrap(), (0, 3, 5));
+    }
+
+    #[test]
+    fn rollup_merges_old_hourly_buckets() {
+        use crate::domain::stats::Granularity;
+        use crate::ShortCode;
+
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        let day = Granularity::Daily.seconds();
+        let hour = Granularity::Hourly.seconds();
+        let stats = rt.block_on(async move {
+            let shortcode = ShortCode::from("1");
+            super::record_response_stats(
+                &[
+                    (shortcode.clone(), day + hour, 1),
+                    (shortcode.clone(), day + 2 * hour, 2),
+                    (shortcode.clone(), 2 * day + hour, 4),
+                ],
+                pool,
+            )
+            .await?;
+            let merged = super::rollup_response_stats(2 * day, pool).await?;
+            let all = model::GetResponseStats {
+                shortcode: None,
+                bucket_size: hour,
+                since: 0,
+            };
+            Ok::<_, DataError>((merged, super::get_response_stats(all, pool).await?))
+        });
+        let (merged, stats) = stats.unwrap();
+        assert_eq!(merged, 2);
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].bucket, stats[0].responses), (day, 3));
+        assert_eq!((stats[1].bucket, stats[1].responses), (2 * day + hour, 4));
+    }
+}
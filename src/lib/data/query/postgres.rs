@@ -0,0 +1,1218 @@
+//! PostgreSQL queries.
+//!
+//! These mirror the SQLite queries, but are checked when they run rather than
+//! at compile time, since the `query!` macros can only check against the one
+//! database named by `DATABASE_URL`. Batched writes bind one array per column
+//! and `UNNEST` them, so they need neither chunking nor numbered placeholders
+//! for each row.
+
+use super::{RevocationStatus, SHORTCODE_ATTEMPTS};
+use crate::data::{model, DataError};
+use crate::domain::board::{JobSort, SearchQuery};
+use crate::domain::job::field::ShortCodeConfig;
+use crate::domain::stats::{Channel, Granularity};
+use crate::web::api::ApiKey;
+use crate::ShortCode;
+use sqlx::{Acquire, Executor, Postgres, Row};
+use std::future::Future;
+
+/// [`Result`] alias for database query functions.
+type Result<T> = std::result::Result<T, DataError>;
+
+/// The `ts_headline` options which mark the matches of a search like the
+/// SQLite `snippet`, with `\u{2}` and `\u{3}`.
+const HEADLINE_OPTIONS: &str =
+    "'StartSel=' || chr(2) || ', StopSel=' || chr(3) || ', MaxWords=16, MinWords=8'";
+
+/// Increases the hit count for each [`crate::domain::Job`] as identified by the [`ShortCode`].
+pub fn increase_hit_counts<'a, 'c, A>(
+    hits: &'a [(ShortCode, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let (shortcodes, responses): (Vec<_>, Vec<_>) = hits
+            .iter()
+            .map(|(shortcode, responses)| (shortcode.as_str().to_owned(), i64::from(*responses)))
+            .unzip();
+        sqlx::query(
+            "UPDATE jobs SET responses = jobs.responses + hits.responses
+             FROM (SELECT shortcode, SUM(responses) AS responses
+                   FROM UNNEST($1::TEXT[], $2::BIGINT[]) AS hits (shortcode, responses)
+                   GROUP BY shortcode) AS hits
+             WHERE jobs.shortcode = hits.shortcode",
+        )
+        .bind(shortcodes)
+        .bind(responses)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Adds responses to the hourly buckets of each [`crate::domain::Job`] as identified by the [`ShortCode`].
+///
+/// Each entry is `(shortcode, bucket start, responses)`.
+pub fn record_response_stats<'a, 'c, A>(
+    stats: &'a [(ShortCode, i64, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let mut shortcodes = Vec::with_capacity(stats.len());
+        let mut buckets = Vec::with_capacity(stats.len());
+        let mut responses = Vec::with_capacity(stats.len());
+        for (shortcode, bucket, count) in stats {
+            shortcodes.push(shortcode.as_str().to_owned());
+            buckets.push(*bucket);
+            responses.push(i64::from(*count));
+        }
+        sqlx::query(
+            "INSERT INTO job_response_stats (shortcode, bucket, responses)
+             SELECT shortcode, bucket, SUM(responses)
+             FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::BIGINT[]) AS stats (shortcode, bucket, responses)
+             GROUP BY shortcode, bucket
+             ON CONFLICT (shortcode, bucket)
+             DO UPDATE SET responses = job_response_stats.responses + excluded.responses",
+        )
+        .bind(shortcodes)
+        .bind(buckets)
+        .bind(responses)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Gets the response time series for a single [`Job`](`crate::domain::Job`), or for all jobs when no shortcode is provided.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_response_stats<'e, M, E>(
+    model: M,
+    executor: E,
+) -> Result<Vec<model::ResponseBucket>>
+where
+    M: Into<model::GetResponseStats>,
+    E: Executor<'e, Database = Postgres>,
+{
+    let model = model.into();
+    Ok(sqlx::query_as(
+        "SELECT (bucket / $1) * $1 AS bucket, SUM(responses)::BIGINT AS responses
+         FROM job_response_stats
+         WHERE ($2::TEXT IS NULL OR shortcode = $2) AND bucket >= $3
+         GROUP BY 1
+         ORDER BY 1",
+    )
+    .bind(model.bucket_size)
+    .bind(model.shortcode)
+    .bind(model.since)
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Merges the hourly buckets which start before `before` into one bucket per day.
+///
+/// Each day's responses are moved into the bucket that starts at midnight. Returns
+/// the number of hourly buckets that were merged.
+pub fn rollup_response_stats<'a, 'c, A>(
+    before: i64,
+    conn: A,
+) -> impl Future<Output = Result<u64>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let day = Granularity::Daily.seconds();
+        let mut transaction = conn.begin().await?;
+        sqlx::query(
+            "INSERT INTO job_response_stats (shortcode, bucket, responses)
+             SELECT shortcode, bucket - bucket % $2, SUM(responses)
+             FROM job_response_stats
+             WHERE bucket < $1 AND bucket % $2 != 0
+             GROUP BY shortcode, bucket - bucket % $2
+             ON CONFLICT (shortcode, bucket)
+             DO UPDATE SET responses = job_response_stats.responses + excluded.responses",
+        )
+        .bind(before)
+        .bind(day)
+        .execute(&mut *transaction)
+        .await?;
+        let merged =
+            sqlx::query("DELETE FROM job_response_stats WHERE bucket < $1 AND bucket % $2 != 0")
+                .bind(before)
+                .bind(day)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        transaction.commit().await?;
+        Ok(merged)
+    }
+}
+
+/// Adds responses to the count for the [`Channel`] used to access each [`crate::domain::Job`].
+pub fn record_channel_responses<'a, 'c, A>(
+    channels: &'a [(ShortCode, Channel, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let mut shortcodes = Vec::with_capacity(channels.len());
+        let mut names = Vec::with_capacity(channels.len());
+        let mut responses = Vec::with_capacity(channels.len());
+        for (shortcode, channel, count) in channels {
+            shortcodes.push(shortcode.as_str().to_owned());
+            names.push(channel.as_str().to_owned());
+            responses.push(i64::from(*count));
+        }
+        sqlx::query(
+            "INSERT INTO job_response_channels (shortcode, channel, responses)
+             SELECT shortcode, channel, SUM(responses)
+             FROM UNNEST($1::TEXT[], $2::TEXT[], $3::BIGINT[]) AS channels (shortcode, channel, responses)
+             GROUP BY shortcode, channel
+             ON CONFLICT (shortcode, channel)
+             DO UPDATE SET responses = job_response_channels.responses + excluded.responses",
+        )
+        .bind(shortcodes)
+        .bind(names)
+        .bind(responses)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Adds responses to the count for the [`ApiKey`] that fetched each [`crate::domain::Job`].
+pub fn record_api_key_responses<'a, 'c, A>(
+    api_keys: &'a [(ShortCode, ApiKey, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let mut shortcodes = Vec::with_capacity(api_keys.len());
+        let mut keys = Vec::with_capacity(api_keys.len());
+        let mut responses = Vec::with_capacity(api_keys.len());
+        for (shortcode, api_key, count) in api_keys {
+            shortcodes.push(shortcode.as_str().to_owned());
+            keys.push(api_key.clone().into_inner());
+            responses.push(i64::from(*count));
+        }
+        sqlx::query(
+            "INSERT INTO job_response_api_keys (shortcode, api_key, responses)
+             SELECT shortcode, api_key, SUM(responses)
+             FROM UNNEST($1::TEXT[], $2::BYTEA[], $3::BIGINT[]) AS api_keys (shortcode, api_key, responses)
+             GROUP BY shortcode, api_key
+             ON CONFLICT (shortcode, api_key)
+             DO UPDATE SET responses = job_response_api_keys.responses + excluded.responses",
+        )
+        .bind(shortcodes)
+        .bind(keys)
+        .bind(responses)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Records that each visitor accessed the [`crate::domain::Job`] on the day
+/// starting at the given timestamp. Repeat visits on the same day are ignored.
+///
+/// Each entry is `(shortcode, day start, visitor)`.
+pub fn record_visitors<'a, 'c, A>(
+    visitors: &'a [(ShortCode, i64, String)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let mut shortcodes = Vec::with_capacity(visitors.len());
+        let mut days = Vec::with_capacity(visitors.len());
+        let mut names = Vec::with_capacity(visitors.len());
+        for (shortcode, day, visitor) in visitors {
+            shortcodes.push(shortcode.as_str().to_owned());
+            days.push(*day);
+            names.push(visitor.clone());
+        }
+        sqlx::query(
+            "INSERT INTO job_visitors (shortcode, day, visitor)
+             SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::TEXT[])
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(shortcodes)
+        .bind(days)
+        .bind(names)
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Deletes the visitors recorded for days which start before `before`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn delete_old_visitors<'e, E>(before: i64, executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query("DELETE FROM job_visitors WHERE day < $1")
+        .bind(before)
+        .execute(executor)
+        .await?
+        .rows_affected())
+}
+
+/// Gets the responses for a [`crate::domain::Job`], split by [`Channel`].
+pub async fn get_response_breakdown<'e, E>(
+    shortcode: &ShortCode,
+    executor: E,
+) -> Result<model::ResponseBreakdown>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = $1 AND channel = 'html'), 0) AS html,
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = $1 AND channel = 'raw'), 0) AS raw,
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = $1 AND channel = 'api'), 0) AS api,
+            (SELECT COUNT(*) FROM job_response_api_keys
+             WHERE shortcode = $1) AS api_keys,
+            (SELECT COUNT(DISTINCT visitor) FROM job_visitors
+             WHERE shortcode = $1) AS unique_visitors",
+    )
+    .bind(shortcode.as_str())
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Checks that the database can answer a query.
+pub async fn ping<'e, E>(executor: E) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query("SELECT 1").execute(executor).await?;
+    Ok(())
+}
+
+/// Updates the query planner statistics.
+///
+/// Free space is left to autovacuum.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn optimize<'e, E>(executor: E) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query("ANALYZE").execute(executor).await?;
+    Ok(())
+}
+
+/// Gets the versions of the migrations which have been applied successfully.
+///
+/// Nothing is returned if migrations have never been run against the database.
+pub fn applied_migrations<'a, 'c, A>(conn: A) -> impl Future<Output = Result<Vec<i64>>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let tracked: bool =
+            sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+                .fetch_one(&mut *conn)
+                .await?;
+        if !tracked {
+            return Ok(vec![]);
+        }
+        Ok(sqlx::query_scalar(
+            "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+}
+
+/// Counts the stored jobs, and how many of them have expired.
+pub async fn count_jobs<'e, E>(executor: E) -> Result<model::JobCounts>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT
+            COUNT(*) AS total,
+            COUNT(CASE WHEN expires < now() AT TIME ZONE 'UTC' THEN 1 END) AS expired
+         FROM jobs",
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Gets a [`Job`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_job<'e, M, E>(model: M, executor: E) -> Result<model::Job>
+where
+    M: Into<model::GetJob>,
+    E: Executor<'e, Database = Postgres>,
+{
+    let (sql, key) = match model.into() {
+        model::GetJob::ShortCode(shortcode) => {
+            ("SELECT * FROM jobs WHERE shortcode = $1", shortcode)
+        }
+        model::GetJob::EscrowId(escrow_id) => (
+            "SELECT * FROM jobs WHERE escrow_id = $1 ORDER BY posted DESC LIMIT 1",
+            escrow_id,
+        ),
+        model::GetJob::JobId(job_id) => ("SELECT * FROM jobs WHERE job_id = $1", job_id),
+    };
+    Ok(sqlx::query_as(sql).bind(key).fetch_one(executor).await?)
+}
+
+/// Adds a [`Job`](`crate::domain::Job`).
+///
+/// Jobs without a shortcode chosen by the client are given one generated with `shortcodes`.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn new_job<'a, 'c, M, A>(
+    model: M,
+    shortcodes: &'a ShortCodeConfig,
+    conn: A,
+) -> impl Future<Output = Result<model::Job>> + Send + 'a
+where
+    M: Into<model::NewJob>,
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let vanity = model.shortcode.is_some();
+        let mut shortcode = model
+            .shortcode
+            .clone()
+            .unwrap_or_else(|| ShortCode::generate(shortcodes).into_inner());
+        let mut conn = conn.acquire().await?;
+        let mut attempts = 0;
+        loop {
+            // A failed statement aborts the whole of an enclosing transaction,
+            // unless it runs in a savepoint of its own.
+            let mut savepoint = conn.begin().await?;
+            let inserted = sqlx::query(
+                "INSERT INTO jobs (
+                    job_id,
+                    shortcode,
+                    escrow_id,
+                    manifest_url,
+                    posted,
+                    expires,
+                    password,
+                    responses,
+                    manifest_hash,
+                    manifest_status,
+                    reward_token,
+                    reward_symbol,
+                    reward_decimals,
+                    reward_balance,
+                    network)
+                 VALUES ($1, $2, $3, $4, $5, to_timestamp($6::BIGINT) AT TIME ZONE 'UTC', $7, 0,
+                         $8, $9, $10, $11, $12, $13, $14)",
+            )
+            .bind(&model.job_id)
+            .bind(&shortcode)
+            .bind(&model.escrow_id)
+            .bind(&model.manifest_url)
+            .bind(model.posted)
+            .bind(model.expires)
+            .bind(&model.password)
+            .bind(&model.manifest_hash)
+            .bind(&model.manifest_status)
+            .bind(&model.reward_token)
+            .bind(&model.reward_symbol)
+            .bind(model.reward_decimals)
+            .bind(&model.reward_balance)
+            .bind(&model.network)
+            .execute(&mut *savepoint)
+            .await;
+            let e = match inserted {
+                Ok(_) => {
+                    savepoint.commit().await?;
+                    break;
+                }
+                Err(e) => e,
+            };
+            savepoint.rollback().await?;
+            attempts += 1;
+            if !is_shortcode_conflict(&e) {
+                return Err(e.into());
+            } else if vanity {
+                return Err(DataError::ShortCodeTaken(shortcode));
+            } else if attempts == SHORTCODE_ATTEMPTS {
+                return Err(e.into());
+            }
+            tracing::warn!(%shortcode, attempts, "generated shortcode is taken");
+            shortcode = ShortCode::generate(shortcodes).into_inner();
+        }
+        get_job(shortcode, &mut *conn).await
+    }
+}
+
+/// Whether the error is from inserting a job with a shortcode that is already taken.
+fn is_shortcode_conflict(err: &sqlx::Error) -> bool {
+    const UNIQUE_VIOLATION: &str = "23505";
+    match err {
+        sqlx::Error::Database(e) => {
+            e.code().as_deref() == Some(UNIQUE_VIOLATION)
+                && e.message().contains("jobs_shortcode_key")
+        }
+        _ => false,
+    }
+}
+
+/// Fetches latest GraphJobs.
+pub async fn get_last_fetched_escrow_id_time<'e, E>(executor: E) -> Result<Option<i64>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(
+        sqlx::query_scalar("SELECT posted FROM jobs ORDER BY posted DESC LIMIT 1")
+            .fetch_optional(executor)
+            .await?,
+    )
+}
+
+/// Updates a [`Job`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn update_job<'a, 'c, M, A>(
+    model: M,
+    conn: A,
+) -> impl Future<Output = Result<model::Job>> + Send + 'a
+where
+    M: Into<model::UpdateJob>,
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let mut conn = conn.acquire().await?;
+        sqlx::query(
+            "UPDATE jobs SET
+                escrow_id = $1,
+                expires = to_timestamp($2::BIGINT) AT TIME ZONE 'UTC',
+                password = $3,
+                manifest_status = CASE
+                    WHEN manifest_url IS NOT DISTINCT FROM $4 THEN manifest_status
+                    WHEN $4 IS NOT NULL AND manifest_hash IS NOT NULL THEN 'pending'
+                    ELSE 'unverifiable'
+                END,
+                manifest_checked = CASE
+                    WHEN manifest_url IS NOT DISTINCT FROM $4 THEN manifest_checked
+                END,
+                task_type = CASE WHEN manifest_url IS NOT DISTINCT FROM $4 THEN task_type END,
+                task_bid = CASE WHEN manifest_url IS NOT DISTINCT FROM $4 THEN task_bid END,
+                total_tasks = CASE WHEN manifest_url IS NOT DISTINCT FROM $4 THEN total_tasks END,
+                manifest_url = $4
+             WHERE shortcode = $5",
+        )
+        .bind(&model.escrow_id)
+        .bind(model.expires)
+        .bind(&model.password)
+        .bind(&model.manifest_url)
+        .bind(&model.shortcode)
+        .execute(&mut *conn)
+        .await?;
+        get_job(model.shortcode, &mut *conn).await
+    }
+}
+
+/// Gets jobs whose manifests are waiting to be verified or classified.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_pending_manifests<'e, E>(
+    retry_before: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::PendingManifest>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT job_id, manifest_url, manifest_hash, reward_decimals
+         FROM jobs
+         WHERE (manifest_status = 'pending'
+                OR (manifest_status = 'unavailable' AND manifest_checked < $1)
+                OR (manifest_status = 'unverifiable' AND manifest_checked IS NULL))
+           AND manifest_url IS NOT NULL
+         LIMIT $2",
+    )
+    .bind(retry_before)
+    .bind(limit)
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records the result of fetching a job's manifest.
+///
+/// The job's searchable text is only replaced when the manifest was read.
+/// Its task terms are kept when the manifest was unavailable, and otherwise
+/// replaced by the ones read from it, so terms a new manifest leaves out are
+/// cleared.
+#[tracing::instrument(level = "debug", skip(conn))]
+pub fn record_manifest_check<'a, 'c, M, A>(
+    model: M,
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    M: Into<model::ManifestCheck> + std::fmt::Debug,
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let mut conn = conn.acquire().await?;
+        sqlx::query(
+            "UPDATE jobs SET
+                manifest_status = $1,
+                task_type = CASE WHEN $1 = 'unavailable' THEN task_type ELSE $2 END,
+                task_bid = CASE WHEN $1 = 'unavailable' THEN task_bid ELSE $3 END,
+                total_tasks = CASE WHEN $1 = 'unavailable' THEN total_tasks ELSE $4 END,
+                manifest_checked = $5
+             WHERE job_id = $6",
+        )
+        .bind(&model.manifest_status)
+        .bind(&model.task_type)
+        .bind(&model.task_bid)
+        .bind(model.total_tasks)
+        .bind(model.checked)
+        .bind(&model.job_id)
+        .execute(&mut *conn)
+        .await?;
+        if model.manifest_text.is_some() {
+            sqlx::query(
+                "UPDATE job_documents SET description = $1, manifest = $2 WHERE job_id = $3",
+            )
+            .bind(&model.description)
+            .bind(&model.manifest_text)
+            .bind(&model.job_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The statement which lists the jobs on the job board, with or without a
+/// task type filter and a search.
+///
+/// Searches list the best matches first unless another order is given. The
+/// snippet of the matched text is only made for the jobs on the page, since
+/// `ts_headline` reads the whole manifest. It is given the text with a `chr(1)`
+/// after each `<`, like the search index, which is removed from the snippet.
+/// Amounts are sorted by their length first, since they are stored as strings
+/// without leading zeros.
+fn list_jobs_sql(filter_task_type: bool, search: bool, sort: Option<JobSort>) -> String {
+    // Columns are not qualified, so that the order applies to the page as well.
+    let order_by = match sort {
+        None if search => "rank DESC, posted DESC",
+        None | Some(JobSort::Posted) => "posted DESC",
+        Some(JobSort::TaskBid) => {
+            "length(task_bid) DESC NULLS LAST, task_bid DESC NULLS LAST, posted DESC"
+        }
+        Some(JobSort::Balance) => {
+            "length(reward_balance) DESC NULLS LAST, reward_balance DESC NULLS LAST, posted DESC"
+        }
+    };
+    let mut param = 0;
+    let mut next = || {
+        param += 1;
+        format!("${}", param)
+    };
+    let matches = if search {
+        format!(
+            "job_documents.search @@ to_tsquery('simple', {}) AND ",
+            next()
+        )
+    } else {
+        String::new()
+    };
+    let task_type = if filter_task_type {
+        format!("jobs.task_type = {} AND ", next())
+    } else {
+        String::new()
+    };
+    let (limit, offset) = (next(), next());
+    let filter = format!(
+        "WHERE {}{}jobs.password IS NULL
+           AND (jobs.expires IS NULL OR jobs.expires >= now() AT TIME ZONE 'UTC')
+         ORDER BY {}
+         LIMIT {} OFFSET {}",
+        matches, task_type, order_by, limit, offset
+    );
+    if !search {
+        return format!("SELECT jobs.*, NULL::TEXT AS snippet FROM jobs {}", filter);
+    }
+    format!(
+        "SELECT page.*, replace(ts_headline('simple', replace(
+            concat_ws(' ', job_documents.escrow_id, job_documents.task_type,
+                      job_documents.description, job_documents.manifest),
+            '<', '<' || chr(1)), to_tsquery('simple', $1), {}), chr(1), '') AS snippet
+         FROM (SELECT jobs.*, ts_rank(job_documents.search, to_tsquery('simple', $1)) AS rank
+               FROM jobs
+               JOIN job_documents ON job_documents.job_id = jobs.job_id
+               {}) AS page
+         JOIN job_documents ON job_documents.job_id = page.job_id
+         ORDER BY {}",
+        HEADLINE_OPTIONS, filter, order_by
+    )
+}
+
+/// Gets a page of the jobs on the job board.
+///
+/// Password protected and expired jobs are not listed.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn list_jobs<'e, M, E>(model: M, executor: E) -> Result<Vec<model::ListedJob>>
+where
+    M: Into<model::ListJobs>,
+    E: Executor<'e, Database = Postgres>,
+{
+    use sqlx::FromRow;
+    let model = model.into();
+    let sql = list_jobs_sql(
+        model.task_type.is_some(),
+        model.search.is_some(),
+        model.sort,
+    );
+    let mut query = sqlx::query(&sql);
+    if let Some(search) = model.search {
+        query = query.bind(search.to_tsquery());
+    }
+    if let Some(task_type) = model.task_type {
+        query = query.bind(task_type);
+    }
+    query
+        .bind(model.limit)
+        .bind(model.offset)
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(model::ListedJob {
+                job: model::Job::from_row(row)?,
+                snippet: row.try_get("snippet")?,
+            })
+        })
+        .collect()
+}
+
+/// Counts the jobs on the job board with each task type, or only the jobs
+/// which match the `search`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn count_task_types<'e, E>(
+    search: Option<&SearchQuery>,
+    executor: E,
+) -> Result<Vec<model::TaskTypeCount>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(match search {
+        None => {
+            sqlx::query_as(
+                "SELECT task_type, COUNT(*) AS count
+                 FROM jobs
+                 WHERE password IS NULL
+                   AND (expires IS NULL OR expires >= now() AT TIME ZONE 'UTC')
+                 GROUP BY task_type
+                 ORDER BY 2 DESC, 1 NULLS FIRST",
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        Some(search) => {
+            sqlx::query_as(
+                "SELECT jobs.task_type, COUNT(*) AS count
+                 FROM jobs
+                 JOIN job_documents ON job_documents.job_id = jobs.job_id
+                 WHERE job_documents.search @@ to_tsquery('simple', $1)
+                   AND jobs.password IS NULL
+                   AND (jobs.expires IS NULL OR jobs.expires >= now() AT TIME ZONE 'UTC')
+                 GROUP BY jobs.task_type
+                 ORDER BY 2 DESC, 1 NULLS FIRST",
+            )
+            .bind(search.to_tsquery())
+            .fetch_all(executor)
+            .await?
+        }
+    })
+}
+
+/// Saves an [`ApiKey`].
+pub async fn save_api_key<'e, E>(api_key: ApiKey, executor: E) -> Result<ApiKey>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query("INSERT INTO api_keys (api_key) VALUES ($1)")
+        .bind(api_key.clone().into_inner())
+        .execute(executor)
+        .await?;
+    Ok(api_key)
+}
+
+/// Revokes an [`ApiKey`].
+pub async fn revoke_api_key<'e, E>(api_key: ApiKey, executor: E) -> Result<RevocationStatus>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query("DELETE FROM api_keys WHERE api_key = $1")
+        .bind(api_key.into_inner())
+        .execute(executor)
+        .await?;
+    Ok(match result.rows_affected() {
+        0 => RevocationStatus::NotFound,
+        _ => RevocationStatus::Revoked,
+    })
+}
+
+/// Determines if the [`ApiKey`] is valid.
+pub async fn api_key_is_valid<'e, E>(api_key: ApiKey, executor: E) -> Result<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM api_keys WHERE api_key = $1)")
+            .bind(api_key.into_inner())
+            .fetch_one(executor)
+            .await?,
+    )
+}
+
+/// Deletes all expired [`Jobs`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn delete_expired<'e, E>(executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(
+        sqlx::query("DELETE FROM jobs WHERE expires < now() AT TIME ZONE 'UTC'")
+            .execute(executor)
+            .await?
+            .rows_affected(),
+    )
+}
+
+/// Saves a search for an [`ApiKey`], unless the key already has `max_searches` saved searches.
+///
+/// The key is locked while its searches are counted, so concurrent requests
+/// can not exceed the limit. Returns `None` when the limit has been reached.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn save_search<'a, 'c, A>(
+    model: model::NewSavedSearch,
+    max_searches: i64,
+    conn: A,
+) -> impl Future<Output = Result<Option<model::SavedSearch>>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut transaction = conn.begin().await?;
+        // Unlike SQLite, concurrent transactions do not wait for each other's
+        // inserts, so the key is locked while its searches are counted.
+        sqlx::query("SELECT 1 FROM api_keys WHERE api_key = $1 FOR UPDATE")
+            .bind(&model.api_key)
+            .execute(&mut *transaction)
+            .await?;
+        let inserted = sqlx::query(
+            "INSERT INTO saved_searches (
+                search_id,
+                api_key,
+                network,
+                task_type,
+                min_task_bid,
+                webhook_url,
+                created)
+             SELECT $1, $2, $3, $4, $5, $6, $7
+             WHERE (SELECT COUNT(*) FROM saved_searches WHERE api_key = $2) < $8",
+        )
+        .bind(&model.search_id)
+        .bind(&model.api_key)
+        .bind(&model.network)
+        .bind(&model.task_type)
+        .bind(&model.min_task_bid)
+        .bind(&model.webhook_url)
+        .bind(model.created)
+        .bind(max_searches)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        transaction.commit().await?;
+        Ok((inserted > 0).then_some(model::SavedSearch {
+            search_id: model.search_id,
+            network: model.network,
+            task_type: model.task_type,
+            min_task_bid: model.min_task_bid,
+            webhook_url: model.webhook_url,
+            created: model.created,
+        }))
+    }
+}
+
+/// Gets the saved searches of an [`ApiKey`], oldest first.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_saved_searches<'e, E>(
+    api_key: ApiKey,
+    executor: E,
+) -> Result<Vec<model::SavedSearch>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT search_id, network, task_type, min_task_bid, webhook_url, created
+         FROM saved_searches
+         WHERE api_key = $1
+         ORDER BY created, search_id",
+    )
+    .bind(api_key.into_inner())
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Deletes a saved search of an [`ApiKey`], along with its alerts.
+///
+/// Returns whether the search was found.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn delete_saved_search<'e, E>(
+    search_id: &str,
+    api_key: ApiKey,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(
+        sqlx::query("DELETE FROM saved_searches WHERE search_id = $1 AND api_key = $2")
+            .bind(search_id)
+            .bind(api_key.into_inner())
+            .execute(executor)
+            .await?
+            .rows_affected()
+            > 0,
+    )
+}
+
+/// Gets the saved searches which could match a job with the task type: those
+/// for the task type, and those for any task type.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_candidate_searches<'e, E>(
+    task_type: Option<&str>,
+    executor: E,
+) -> Result<Vec<model::SavedSearch>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT search_id, network, task_type, min_task_bid, webhook_url, created
+         FROM saved_searches
+         WHERE task_type = $1 OR task_type IS NULL",
+    )
+    .bind(task_type)
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records that a job matched a saved search.
+///
+/// Each job alerts a search at most once, so returns whether the alert is new.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn new_job_alert<'e, E>(model: model::NewJobAlert, executor: E) -> Result<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query(
+        "INSERT INTO job_alerts (search_id, job_id, created, next_attempt)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(model.search_id)
+    .bind(model.job_id)
+    .bind(model.created)
+    .bind(model.next_attempt)
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Gets the alerts for the saved searches of an [`ApiKey`] which come after
+/// the alert `after`, oldest first.
+///
+/// Jobs which have been password protected since they matched are left out.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn get_job_alerts<'e, E>(
+    api_key: ApiKey,
+    after: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::JobAlert>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    use sqlx::FromRow;
+    sqlx::query(
+        "SELECT jobs.*,
+            job_alerts.alert_id,
+            job_alerts.search_id,
+            job_alerts.created AS alert_created,
+            job_alerts.delivered
+         FROM saved_searches
+         JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+         JOIN jobs ON jobs.job_id = job_alerts.job_id
+         WHERE saved_searches.api_key = $1 AND job_alerts.alert_id > $2 AND jobs.password IS NULL
+         ORDER BY job_alerts.alert_id
+         LIMIT $3",
+    )
+    .bind(api_key.into_inner())
+    .bind(after)
+    .bind(limit)
+    .fetch_all(executor)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(model::JobAlert {
+            job: model::Job::from_row(row)?,
+            alert_id: row.try_get("alert_id")?,
+            search_id: row.try_get("search_id")?,
+            alert_created: row.try_get("alert_created")?,
+            delivered: row.try_get("delivered")?,
+        })
+    })
+    .collect()
+}
+
+/// Gets the id of the newest alert for the saved searches of an [`ApiKey`], or 0 when there are none.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_latest_alert_id<'e, E>(api_key: ApiKey, executor: E) -> Result<i64>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_scalar(
+        "SELECT COALESCE(MAX(job_alerts.alert_id), 0)
+         FROM saved_searches
+         JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+         WHERE saved_searches.api_key = $1",
+    )
+    .bind(api_key.into_inner())
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Gets alerts whose webhook is due to be sent at `now`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_pending_webhooks<'e, E>(
+    now: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::PendingWebhook>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT
+            job_alerts.alert_id,
+            job_alerts.search_id,
+            job_alerts.job_id,
+            saved_searches.webhook_url,
+            job_alerts.created,
+            job_alerts.attempts
+         FROM job_alerts
+         JOIN saved_searches ON saved_searches.search_id = job_alerts.search_id
+         WHERE job_alerts.next_attempt <= $1 AND saved_searches.webhook_url IS NOT NULL
+         ORDER BY job_alerts.next_attempt
+         LIMIT $2",
+    )
+    .bind(now)
+    .bind(limit)
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records an attempt to send an alert's webhook.
+///
+/// `next_attempt` is when to send it again, and is `None` once it has been
+/// `delivered` or given up on.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn record_webhook_attempt<'e, E>(
+    alert_id: i64,
+    delivered: Option<i64>,
+    next_attempt: Option<i64>,
+    executor: E,
+) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query(
+        "UPDATE job_alerts SET
+            attempts = attempts + 1,
+            delivered = $1,
+            next_attempt = $2
+         WHERE alert_id = $3",
+    )
+    .bind(delivered)
+    .bind(next_attempt)
+    .bind(alert_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Claims a task of a job for an [`ApiKey`], unless every task of the job is claimed.
+///
+/// Only claims whose lease lasts beyond `now` are counted against the job's
+/// `total_tasks`, and jobs without a `total_tasks` can not be claimed. The job
+/// is locked while its claims are counted, so concurrent requests can not
+/// exceed the limit. Returns `None` when every task is claimed.
+#[tracing::instrument(level = "debug", skip(model, conn))]
+pub fn new_claim<'a, 'c, A>(
+    model: model::NewClaim,
+    now: i64,
+    conn: A,
+) -> impl Future<Output = Result<Option<model::Claim>>> + Send + 'a
+where
+    A: Acquire<'c, Database = Postgres> + Send + 'a,
+{
+    async move {
+        let mut transaction = conn.begin().await?;
+        let total_tasks: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT total_tasks FROM jobs WHERE job_id = $1 FOR UPDATE")
+                .bind(&model.job_id)
+                .fetch_optional(&mut *transaction)
+                .await?;
+        let inserted = sqlx::query(
+            "INSERT INTO job_claims (claim_id, job_id, api_key, created, expires)
+             SELECT $1, $2, $3, $4, $5
+             WHERE (SELECT COUNT(*) FROM job_claims WHERE job_id = $2 AND expires > $6) < $7",
+        )
+        .bind(&model.claim_id)
+        .bind(&model.job_id)
+        .bind(&model.api_key)
+        .bind(model.created)
+        .bind(model.expires)
+        .bind(now)
+        .bind(total_tasks.flatten())
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        transaction.commit().await?;
+        Ok((inserted > 0).then_some(model::Claim {
+            claim_id: model.claim_id,
+            job_id: model.job_id,
+            shortcode: model.shortcode,
+            created: model.created,
+            expires: model.expires,
+        }))
+    }
+}
+
+/// Gets a claim of an [`ApiKey`] whose lease lasts beyond `now`.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn get_claim<'e, E>(
+    claim_id: &str,
+    api_key: ApiKey,
+    now: i64,
+    executor: E,
+) -> Result<Option<model::Claim>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query_as(
+        "SELECT
+            job_claims.claim_id,
+            job_claims.job_id,
+            jobs.shortcode,
+            job_claims.created,
+            job_claims.expires
+         FROM job_claims
+         JOIN jobs ON jobs.job_id = job_claims.job_id
+         WHERE job_claims.claim_id = $1 AND job_claims.api_key = $2 AND job_claims.expires > $3",
+    )
+    .bind(claim_id)
+    .bind(api_key.into_inner())
+    .bind(now)
+    .fetch_optional(executor)
+    .await?)
+}
+
+/// Moves the end of the lease of a claim of an [`ApiKey`] to `expires`, as
+/// long as the claim is of the job with the shortcode and has not expired at `now`.
+///
+/// Returns whether the claim was found.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn renew_claim<'e, E>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    expires: i64,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query(
+        "UPDATE job_claims SET expires = $1
+         WHERE claim_id = $2 AND api_key = $3 AND expires > $4
+             AND job_id = (SELECT job_id FROM jobs WHERE shortcode = $5)",
+    )
+    .bind(expires)
+    .bind(claim_id)
+    .bind(api_key.into_inner())
+    .bind(now)
+    .bind(shortcode)
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Releases a claim of an [`ApiKey`] on the job with the shortcode, freeing its task.
+///
+/// Returns whether the claim was found and had not expired at `now`.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn release_claim<'e, E>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query(
+        "DELETE FROM job_claims
+         WHERE claim_id = $1 AND api_key = $2 AND expires > $3
+             AND job_id = (SELECT job_id FROM jobs WHERE shortcode = $4)",
+    )
+    .bind(claim_id)
+    .bind(api_key.into_inner())
+    .bind(now)
+    .bind(shortcode)
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Deletes the claims whose lease ended at or before `now`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn delete_expired_claims<'e, E>(now: i64, executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    Ok(sqlx::query("DELETE FROM job_claims WHERE expires <= $1")
+        .bind(now)
+        .execute(executor)
+        .await?
+        .rows_affected())
+}
@@ -0,0 +1,1777 @@
+//! SQLite queries.
+//!
+//! Queries accept any executor, so they can run directly against the pool or
+//! on the connection of a transaction. Queries which run more than one
+//! statement accept anything that can [`Acquire`] a connection instead.
+//!
+//! The batched writes return `impl Future + Send` rather than being `async fn`,
+//! which avoids sqlx's "`Acquire` is not general enough" error when they are
+//! awaited inside a spawned task.
+
+use super::{RevocationStatus, SHORTCODE_ATTEMPTS};
+use crate::data::{model, DataError};
+use crate::domain::board::{JobSort, SearchQuery};
+use crate::domain::job::field::ShortCodeConfig;
+use crate::domain::stats::{Channel, Granularity};
+use crate::web::api::ApiKey;
+use crate::ShortCode;
+use libsqlite3_sys as ffi;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use sqlx::{Acquire, ConnectOptions, Connection, Executor, Row, Sqlite};
+use std::ffi::CStr;
+use std::future::Future;
+use std::os::raw::c_int;
+use std::ptr::NonNull;
+use std::time::Duration;
+
+/// [`Result`] alias for database query functions.
+type Result<T> = std::result::Result<T, DataError>;
+
+/// The maximum number of bind parameters used by a single batched statement.
+///
+/// This is the lowest limit of any SQLite version.
+const MAX_BIND_PARAMS: usize = 999;
+
+/// Returns `count` comma separated copies of `group`, for use in batched statements.
+fn placeholders(group: &str, count: usize) -> String {
+    vec![group; count].join(", ")
+}
+
+/// Increases the hit count for each [`crate::domain::Job`] as identified by the [`ShortCode`].
+///
+/// Each chunk of jobs is updated with a single `UPDATE ... CASE` statement.
+pub fn increase_hit_counts<'a, 'c, A>(
+    hits: &'a [(ShortCode, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        for chunk in hits.chunks(MAX_BIND_PARAMS / 3) {
+            let sql = format!(
+                "UPDATE jobs SET responses = responses + CASE shortcode {} ELSE 0 END
+                 WHERE shortcode IN ({})",
+                vec!["WHEN ? THEN ?"; chunk.len()].join(" "),
+                placeholders("?", chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for (shortcode, responses) in chunk {
+                query = query.bind(shortcode.as_str()).bind(responses);
+            }
+            for (shortcode, _) in chunk {
+                query = query.bind(shortcode.as_str());
+            }
+            query.execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds responses to the hourly buckets of each [`crate::domain::Job`] as identified by the [`ShortCode`].
+///
+/// Each entry is `(shortcode, bucket start, responses)`.
+pub fn record_response_stats<'a, 'c, A>(
+    stats: &'a [(ShortCode, i64, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        for chunk in stats.chunks(MAX_BIND_PARAMS / 3) {
+            let sql = format!(
+                "INSERT INTO job_response_stats (shortcode, bucket, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, bucket)
+                 DO UPDATE SET responses = responses + excluded.responses",
+                placeholders("(?, ?, ?)", chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for (shortcode, bucket, responses) in chunk {
+                query = query.bind(shortcode.as_str()).bind(bucket).bind(responses);
+            }
+            query.execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Gets the response time series for a single [`Job`](`crate::domain::Job`), or for all jobs when no shortcode is provided.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_response_stats<'e, M, E>(
+    model: M,
+    executor: E,
+) -> Result<Vec<model::ResponseBucket>>
+where
+    M: Into<model::GetResponseStats>,
+    E: Executor<'e, Database = Sqlite>,
+{
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::ResponseBucket,
+        r#"SELECT
+            (bucket / ?1) * ?1 AS "bucket!: i64",
+            SUM(responses) AS "responses!: i64"
+           FROM job_response_stats
+           WHERE (?2 IS NULL OR shortcode = ?2) AND bucket >= ?3
+           GROUP BY 1
+           ORDER BY 1"#,
+        model.bucket_size,
+        model.shortcode,
+        model.since
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Merges the hourly buckets which start before `before` into one bucket per day.
+///
+/// Each day's responses are moved into the bucket that starts at midnight. Returns
+/// the number of hourly buckets that were merged.
+pub fn rollup_response_stats<'a, 'c, A>(
+    before: i64,
+    conn: A,
+) -> impl Future<Output = Result<u64>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let day = Granularity::Daily.seconds();
+        let mut transaction = conn.begin().await?;
+        sqlx::query!(
+            r#"INSERT INTO job_response_stats (shortcode, bucket, responses)
+               SELECT shortcode, bucket - bucket % ?2, SUM(responses)
+               FROM job_response_stats
+               WHERE bucket < ?1 AND bucket % ?2 != 0
+               GROUP BY shortcode, bucket - bucket % ?2
+               ON CONFLICT (shortcode, bucket)
+               DO UPDATE SET responses = responses + excluded.responses"#,
+            before,
+            day
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let merged = sqlx::query!(
+            "DELETE FROM job_response_stats WHERE bucket < ?1 AND bucket % ?2 != 0",
+            before,
+            day
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        transaction.commit().await?;
+        Ok(merged)
+    }
+}
+
+/// Adds responses to the count for the [`Channel`] used to access each [`crate::domain::Job`].
+pub fn record_channel_responses<'a, 'c, A>(
+    channels: &'a [(ShortCode, Channel, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        for chunk in channels.chunks(MAX_BIND_PARAMS / 3) {
+            let sql = format!(
+                "INSERT INTO job_response_channels (shortcode, channel, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, channel)
+                 DO UPDATE SET responses = responses + excluded.responses",
+                placeholders("(?, ?, ?)", chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for (shortcode, channel, responses) in chunk {
+                query = query
+                    .bind(shortcode.as_str())
+                    .bind(channel.as_str())
+                    .bind(responses);
+            }
+            query.execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds responses to the count for the [`ApiKey`] that fetched each [`crate::domain::Job`].
+pub fn record_api_key_responses<'a, 'c, A>(
+    api_keys: &'a [(ShortCode, ApiKey, u32)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        for chunk in api_keys.chunks(MAX_BIND_PARAMS / 3) {
+            let sql = format!(
+                "INSERT INTO job_response_api_keys (shortcode, api_key, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, api_key)
+                 DO UPDATE SET responses = responses + excluded.responses",
+                placeholders("(?, ?, ?)", chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for (shortcode, api_key, responses) in chunk {
+                query = query
+                    .bind(shortcode.as_str())
+                    .bind(api_key.clone().into_inner())
+                    .bind(responses);
+            }
+            query.execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Records that each visitor accessed the [`crate::domain::Job`] on the day
+/// starting at the given timestamp. Repeat visits on the same day are ignored.
+///
+/// Each entry is `(shortcode, day start, visitor)`.
+pub fn record_visitors<'a, 'c, A>(
+    visitors: &'a [(ShortCode, i64, String)],
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        for chunk in visitors.chunks(MAX_BIND_PARAMS / 3) {
+            let sql = format!(
+                "INSERT OR IGNORE INTO job_visitors (shortcode, day, visitor) VALUES {}",
+                placeholders("(?, ?, ?)", chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for (shortcode, day, visitor) in chunk {
+                query = query
+                    .bind(shortcode.as_str())
+                    .bind(day)
+                    .bind(visitor.as_str());
+            }
+            query.execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Deletes the visitors recorded for days which start before `before`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn delete_old_visitors<'e, E>(before: i64, executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(
+        sqlx::query!("DELETE FROM job_visitors WHERE day < ?", before)
+            .execute(executor)
+            .await?
+            .rows_affected(),
+    )
+}
+
+/// Gets the responses for a [`crate::domain::Job`], split by [`Channel`].
+pub async fn get_response_breakdown<'e, E>(
+    shortcode: &ShortCode,
+    executor: E,
+) -> Result<model::ResponseBreakdown>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let shortcode = shortcode.as_str();
+    Ok(sqlx::query_as!(
+        model::ResponseBreakdown,
+        r#"SELECT
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'html'), 0) AS "html!: i64",
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'raw'), 0) AS "raw!: i64",
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'api'), 0) AS "api!: i64",
+            (SELECT COUNT(*) FROM job_response_api_keys
+             WHERE shortcode = ?1) AS "api_keys!: i64",
+            (SELECT COUNT(DISTINCT visitor) FROM job_visitors
+             WHERE shortcode = ?1) AS "unique_visitors!: i64""#,
+        shortcode
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Checks that the database can answer a query.
+pub async fn ping<'e, E>(executor: E) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query("SELECT 1").execute(executor).await?;
+    Ok(())
+}
+
+/// Updates the query planner statistics and releases free pages to the file system.
+///
+/// Free pages are only released if the database uses incremental auto-vacuum.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn optimize<'a, 'c, A>(conn: A) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        sqlx::query("PRAGMA optimize").execute(&mut *conn).await?;
+        sqlx::query("PRAGMA incremental_vacuum")
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The number of pages copied by each step of [`backup_into`].
+const BACKUP_STEP_PAGES: c_int = 256;
+
+/// How long [`backup_into`] waits before retrying a step when the database is locked.
+const BACKUP_BUSY_DELAY: Duration = Duration::from_millis(10);
+
+/// An SQLite online backup in progress, which is finished when dropped.
+struct Backup(NonNull<ffi::sqlite3_backup>);
+
+// The backup is only stepped while both of its connections are locked.
+unsafe impl Send for Backup {}
+
+impl Backup {
+    /// Releases the backup, returning the result of the last step which failed.
+    fn finish(self) -> c_int {
+        let code = unsafe { ffi::sqlite3_backup_finish(self.0.as_ptr()) };
+        std::mem::forget(self);
+        code
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_backup_finish(self.0.as_ptr()) };
+    }
+}
+
+/// Describes an SQLite result code.
+fn sqlite_error(code: c_int) -> DataError {
+    let message = unsafe { CStr::from_ptr(ffi::sqlite3_errstr(code)) };
+    DataError::Backup(message.to_string_lossy().into_owned())
+}
+
+/// Writes a consistent copy of the database to a new file at `path`, while it
+/// remains available to other connections.
+///
+/// The pages are copied with the SQLite online backup API, a few at a time, and
+/// the copy is restarted by SQLite if another connection writes to the database
+/// in between.
+///
+/// Fails if a non-empty file already exists at `path`.
+#[tracing::instrument(level = "debug", skip(conn))]
+pub fn backup_into<'a, 'c, A>(
+    path: &'a str,
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        if std::fs::metadata(path).is_ok_and(|file| file.len() > 0) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("'{}' already exists", path),
+            )
+            .into());
+        }
+        let mut source = conn.acquire().await?;
+        let mut target = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Delete)
+            .connect()
+            .await?;
+        let mut source_handle = source.lock_handle().await?;
+        let mut target_handle = target.lock_handle().await?;
+        let main = c"main".as_ptr();
+        let backup = unsafe {
+            ffi::sqlite3_backup_init(
+                target_handle.as_raw_handle().as_ptr(),
+                main,
+                source_handle.as_raw_handle().as_ptr(),
+                main,
+            )
+        };
+        let backup = match NonNull::new(backup) {
+            Some(backup) => Backup(backup),
+            None => {
+                let code = unsafe { ffi::sqlite3_errcode(target_handle.as_raw_handle().as_ptr()) };
+                return Err(sqlite_error(code));
+            }
+        };
+        loop {
+            match unsafe { ffi::sqlite3_backup_step(backup.0.as_ptr(), BACKUP_STEP_PAGES) } {
+                ffi::SQLITE_DONE => break,
+                ffi::SQLITE_OK => tokio::task::yield_now().await,
+                ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {
+                    tokio::time::sleep(BACKUP_BUSY_DELAY).await
+                }
+                code => return Err(sqlite_error(code)),
+            }
+        }
+        let code = backup.finish();
+        if code != ffi::SQLITE_OK {
+            return Err(sqlite_error(code));
+        }
+        drop(target_handle);
+        target.close().await?;
+        Ok(())
+    }
+}
+
+/// Gets the versions of the migrations which have been applied successfully.
+///
+/// Nothing is returned if migrations have never been run against the database.
+pub fn applied_migrations<'a, 'c, A>(conn: A) -> impl Future<Output = Result<Vec<i64>>> + Send + 'a
+where
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    async move {
+        let mut conn = conn.acquire().await?;
+        let tracked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !tracked {
+            return Ok(vec![]);
+        }
+        Ok(sqlx::query_scalar(
+            "SELECT version FROM _sqlx_migrations WHERE success = 1 ORDER BY version",
+        )
+        .fetch_all(&mut *conn)
+        .await?)
+    }
+}
+
+/// Counts the stored jobs, and how many of them have expired.
+pub async fn count_jobs<'e, E>(executor: E) -> Result<model::JobCounts>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(sqlx::query_as!(
+        model::JobCounts,
+        r#"SELECT
+            COUNT(*) AS "total!: i64",
+            COUNT(CASE WHEN strftime('%s', 'now') > expires THEN 1 END) AS "expired!: i64"
+        FROM jobs"#
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+/// Gets a [`Job`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_job<'e, M, E>(model: M, executor: E) -> Result<model::Job>
+where
+    M: Into<model::GetJob>,
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(match model.into() {
+        model::GetJob::ShortCode(shortcode) => {
+            sqlx::query_as!(
+                model::Job,
+                "SELECT * FROM jobs WHERE shortcode = ?",
+                shortcode
+            )
+            .fetch_one(executor)
+            .await?
+        }
+        model::GetJob::EscrowId(escrow_id) => {
+            sqlx::query_as!(
+                model::Job,
+                "SELECT * FROM jobs WHERE escrow_id = ? ORDER BY posted DESC LIMIT 1",
+                escrow_id
+            )
+            .fetch_one(executor)
+            .await?
+        }
+        model::GetJob::JobId(job_id) => {
+            sqlx::query_as!(model::Job, "SELECT * FROM jobs WHERE job_id = ?", job_id)
+                .fetch_one(executor)
+                .await?
+        }
+    })
+}
+
+/// Adds a [`Job`](`crate::domain::Job`).
+///
+/// Jobs without a shortcode chosen by the client are given one generated with `shortcodes`.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn new_job<'a, 'c, M, A>(
+    model: M,
+    shortcodes: &'a ShortCodeConfig,
+    conn: A,
+) -> impl Future<Output = Result<model::Job>> + Send + 'a
+where
+    M: Into<model::NewJob>,
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let vanity = model.shortcode.is_some();
+        let mut shortcode = model
+            .shortcode
+            .clone()
+            .unwrap_or_else(|| ShortCode::generate(shortcodes).into_inner());
+        let mut conn = conn.acquire().await?;
+        let mut attempts = 0;
+        while let Err(e) = sqlx::query!(
+            r#"INSERT INTO jobs (
+                job_id,
+                shortcode,
+                escrow_id,
+                manifest_url,
+                posted,
+                expires,
+                password,
+                responses,
+                manifest_hash,
+                manifest_status,
+                reward_token,
+                reward_symbol,
+                reward_decimals,
+                reward_balance,
+                network)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            model.job_id,
+            shortcode,
+            model.escrow_id,
+            model.manifest_url,
+            model.posted,
+            model.expires,
+            model.password,
+            0,
+            model.manifest_hash,
+            model.manifest_status,
+            model.reward_token,
+            model.reward_symbol,
+            model.reward_decimals,
+            model.reward_balance,
+            model.network
+        )
+        .execute(&mut *conn)
+        .await
+        {
+            attempts += 1;
+            if !is_shortcode_conflict(&e) {
+                return Err(e.into());
+            } else if vanity {
+                return Err(DataError::ShortCodeTaken(shortcode));
+            } else if attempts == SHORTCODE_ATTEMPTS {
+                return Err(e.into());
+            }
+            tracing::warn!(%shortcode, attempts, "generated shortcode is taken");
+            shortcode = ShortCode::generate(shortcodes).into_inner();
+        }
+        get_job(shortcode, &mut *conn).await
+    }
+}
+
+/// Whether the error is from inserting a job with a shortcode that is already taken.
+fn is_shortcode_conflict(err: &sqlx::Error) -> bool {
+    /// SQLITE_CONSTRAINT_UNIQUE
+    const UNIQUE_VIOLATION: &str = "2067";
+    match err {
+        sqlx::Error::Database(e) => {
+            e.code().as_deref() == Some(UNIQUE_VIOLATION) && e.message().contains("jobs.shortcode")
+        }
+        _ => false,
+    }
+}
+
+/// Fetches latest GraphJobs.
+pub async fn get_last_fetched_escrow_id_time<'e, E>(executor: E) -> Result<Option<i64>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let row = sqlx::query!("SELECT posted FROM jobs ORDER BY posted DESC LIMIT 1")
+        .fetch_optional(executor)
+        .await?;
+    Ok(row.map(|r| r.posted))
+}
+
+/// Updates a [`Job`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn update_job<'a, 'c, M, A>(
+    model: M,
+    conn: A,
+) -> impl Future<Output = Result<model::Job>> + Send + 'a
+where
+    M: Into<model::UpdateJob>,
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let mut conn = conn.acquire().await?;
+        let _ = sqlx::query!(
+            r#"UPDATE jobs SET
+                escrow_id = ?,
+                expires = ?,
+                password = ?,
+                manifest_status = CASE
+                    WHEN manifest_url IS ? THEN manifest_status
+                    WHEN ? IS NOT NULL AND manifest_hash IS NOT NULL THEN 'pending'
+                    ELSE 'unverifiable'
+                END,
+                manifest_checked = CASE WHEN manifest_url IS ? THEN manifest_checked END,
+                task_type = CASE WHEN manifest_url IS ? THEN task_type END,
+                task_bid = CASE WHEN manifest_url IS ? THEN task_bid END,
+                total_tasks = CASE WHEN manifest_url IS ? THEN total_tasks END,
+                manifest_url = ?
+               WHERE shortcode = ?"#,
+            model.escrow_id,
+            model.expires,
+            model.password,
+            model.manifest_url,
+            model.manifest_url,
+            model.manifest_url,
+            model.manifest_url,
+            model.manifest_url,
+            model.manifest_url,
+            model.manifest_url,
+            model.shortcode
+        )
+        .execute(&mut *conn)
+        .await?;
+        get_job(model.shortcode, &mut *conn).await
+    }
+}
+
+/// Gets jobs whose manifests are waiting to be verified or classified.
+///
+/// These are jobs which have not been checked yet, and jobs whose manifests
+/// were unavailable when they were last checked before `retry_before`. Jobs
+/// without a recorded hash are only fetched once, to classify them.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_pending_manifests<'e, E>(
+    retry_before: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::PendingManifest>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(sqlx::query_as!(
+        model::PendingManifest,
+        r#"SELECT job_id, manifest_url AS "manifest_url!", manifest_hash, reward_decimals
+           FROM jobs
+           WHERE (manifest_status = 'pending'
+                  OR (manifest_status = 'unavailable' AND manifest_checked < ?)
+                  OR (manifest_status = 'unverifiable' AND manifest_checked IS NULL))
+             AND manifest_url IS NOT NULL
+           LIMIT ?"#,
+        retry_before,
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records the result of fetching a job's manifest.
+///
+/// The job's searchable text is only replaced when the manifest was read.
+/// Its task terms are kept when the manifest was unavailable, and otherwise
+/// replaced by the ones read from it, so terms a new manifest leaves out are
+/// cleared.
+#[tracing::instrument(level = "debug", skip(conn))]
+pub fn record_manifest_check<'a, 'c, M, A>(
+    model: M,
+    conn: A,
+) -> impl Future<Output = Result<()>> + Send + 'a
+where
+    M: Into<model::ManifestCheck> + std::fmt::Debug,
+    A: Acquire<'c, Database = Sqlite> + Send + 'a,
+{
+    let model = model.into();
+    async move {
+        let mut conn = conn.acquire().await?;
+        sqlx::query!(
+            r#"UPDATE jobs SET
+                manifest_status = ?,
+                task_type = CASE WHEN ? = 'unavailable' THEN task_type ELSE ? END,
+                task_bid = CASE WHEN ? = 'unavailable' THEN task_bid ELSE ? END,
+                total_tasks = CASE WHEN ? = 'unavailable' THEN total_tasks ELSE ? END,
+                manifest_checked = ?
+               WHERE job_id = ?"#,
+            model.manifest_status,
+            model.manifest_status,
+            model.task_type,
+            model.manifest_status,
+            model.task_bid,
+            model.manifest_status,
+            model.total_tasks,
+            model.checked,
+            model.job_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        if model.manifest_text.is_some() {
+            sqlx::query!(
+                "UPDATE job_documents SET description = ?, manifest = ? WHERE job_id = ?",
+                model.description,
+                model.manifest_text,
+                model.job_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The statement which lists the jobs on the job board, with or without a
+/// task type filter and a search.
+///
+/// Searches list the best matches first unless another order is given, and
+/// select a snippet of the matched text. Amounts are sorted by their length
+/// first, since they are stored as strings without leading zeros.
+fn list_jobs_sql(filter_task_type: bool, search: bool, sort: Option<JobSort>) -> String {
+    let order_by = match sort {
+        None if search => "rank",
+        None | Some(JobSort::Posted) => "jobs.posted DESC",
+        Some(JobSort::TaskBid) => {
+            "length(jobs.task_bid) DESC, jobs.task_bid DESC, jobs.posted DESC"
+        }
+        Some(JobSort::Balance) => {
+            "length(jobs.reward_balance) DESC, jobs.reward_balance DESC, jobs.posted DESC"
+        }
+    };
+    let (snippet, from, matches) = if search {
+        (
+            "snippet(job_search, -1, char(2), char(3), '…', 16)",
+            "job_search
+         JOIN job_documents ON job_documents.doc_id = job_search.rowid
+         JOIN jobs ON jobs.job_id = job_documents.job_id",
+            "job_search MATCH ? AND ",
+        )
+    } else {
+        ("NULL", "jobs", "")
+    };
+    format!(
+        "SELECT jobs.*, {} AS snippet
+         FROM {}
+         WHERE {}{}jobs.password IS NULL
+           AND (jobs.expires IS NULL OR jobs.expires >= strftime('%s', 'now'))
+         ORDER BY {}
+         LIMIT ? OFFSET ?",
+        snippet,
+        from,
+        matches,
+        if filter_task_type {
+            "jobs.task_type = ? AND "
+        } else {
+            ""
+        },
+        order_by
+    )
+}
+
+/// Gets a page of the jobs on the job board.
+///
+/// Password protected and expired jobs are not listed.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn list_jobs<'e, M, E>(model: M, executor: E) -> Result<Vec<model::ListedJob>>
+where
+    M: Into<model::ListJobs>,
+    E: Executor<'e, Database = Sqlite>,
+{
+    use sqlx::FromRow;
+    let model = model.into();
+    let sql = list_jobs_sql(
+        model.task_type.is_some(),
+        model.search.is_some(),
+        model.sort,
+    );
+    let mut query = sqlx::query(&sql);
+    if let Some(search) = model.search {
+        query = query.bind(search.to_match());
+    }
+    if let Some(task_type) = model.task_type {
+        query = query.bind(task_type);
+    }
+    query
+        .bind(model.limit)
+        .bind(model.offset)
+        .fetch_all(executor)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(model::ListedJob {
+                job: model::Job::from_row(row)?,
+                snippet: row.try_get("snippet")?,
+            })
+        })
+        .collect()
+}
+
+/// Counts the jobs on the job board with each task type, or only the jobs
+/// which match the `search`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn count_task_types<'e, E>(
+    search: Option<&SearchQuery>,
+    executor: E,
+) -> Result<Vec<model::TaskTypeCount>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(match search.map(SearchQuery::to_match) {
+        None => {
+            sqlx::query_as!(
+                model::TaskTypeCount,
+                r#"SELECT task_type AS "task_type?", COUNT(*) AS "count!: i64"
+                   FROM jobs
+                   WHERE password IS NULL
+                     AND (expires IS NULL OR expires >= strftime('%s', 'now'))
+                   GROUP BY task_type
+                   ORDER BY 2 DESC, 1"#
+            )
+            .fetch_all(executor)
+            .await?
+        }
+        Some(search) => {
+            sqlx::query_as!(
+                model::TaskTypeCount,
+                r#"SELECT jobs.task_type AS "task_type?", COUNT(*) AS "count!: i64"
+                   FROM job_search
+                   JOIN job_documents ON job_documents.doc_id = job_search.rowid
+                   JOIN jobs ON jobs.job_id = job_documents.job_id
+                   WHERE job_search MATCH ?
+                     AND jobs.password IS NULL
+                     AND (jobs.expires IS NULL OR jobs.expires >= strftime('%s', 'now'))
+                   GROUP BY jobs.task_type
+                   ORDER BY 2 DESC, 1"#,
+                search
+            )
+            .fetch_all(executor)
+            .await?
+        }
+    })
+}
+
+/// Saves an [`ApiKey`].
+pub async fn save_api_key<'e, E>(api_key: ApiKey, executor: E) -> Result<ApiKey>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.clone().into_inner();
+    let _ = sqlx::query!("INSERT INTO api_keys (api_key) VALUES (?)", bytes)
+        .execute(executor)
+        .await
+        .map(|_| ())?;
+    Ok(api_key)
+}
+
+/// Revokes an [`ApiKey`].
+pub async fn revoke_api_key<'e, E>(api_key: ApiKey, executor: E) -> Result<RevocationStatus>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.clone().into_inner();
+    Ok(
+        sqlx::query!("DELETE FROM api_keys WHERE api_key == ?", bytes)
+            .execute(executor)
+            .await
+            .map(|result| match result.rows_affected() {
+                0 => RevocationStatus::NotFound,
+                _ => RevocationStatus::Revoked,
+            })?,
+    )
+}
+
+/// Determines if the [`ApiKey`] is valid.
+pub async fn api_key_is_valid<'e, E>(api_key: ApiKey, executor: E) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.clone().into_inner();
+    Ok(
+        sqlx::query("SELECT COUNT(api_key) FROM api_keys WHERE api_key = ?")
+            .bind(bytes)
+            .fetch_one(executor)
+            .await
+            .map(|row| {
+                let count: u32 = row.get(0);
+                count > 0
+            })?,
+    )
+}
+
+/// Deletes all expired [`Jobs`](`crate::domain::Job`).
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn delete_expired<'e, E>(executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(
+        sqlx::query!(r#"DELETE FROM jobs WHERE strftime('%s', 'now') > expires"#)
+            .execute(executor)
+            .await?
+            .rows_affected(),
+    )
+}
+
+/// Saves a search for an [`ApiKey`], unless the key already has `max_searches` saved searches.
+///
+/// The limit is checked by the insert itself, so concurrent requests can not exceed it.
+/// Returns `None` when the limit has been reached.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn save_search<'e, E>(
+    model: model::NewSavedSearch,
+    max_searches: i64,
+    executor: E,
+) -> Result<Option<model::SavedSearch>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let inserted = sqlx::query!(
+        r#"INSERT INTO saved_searches (
+            search_id,
+            api_key,
+            network,
+            task_type,
+            min_task_bid,
+            webhook_url,
+            created)
+           SELECT ?, ?, ?, ?, ?, ?, ?
+           WHERE (SELECT COUNT(*) FROM saved_searches WHERE api_key = ?) < ?"#,
+        model.search_id,
+        model.api_key,
+        model.network,
+        model.task_type,
+        model.min_task_bid,
+        model.webhook_url,
+        model.created,
+        model.api_key,
+        max_searches
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+    Ok((inserted > 0).then_some(model::SavedSearch {
+        search_id: model.search_id,
+        network: model.network,
+        task_type: model.task_type,
+        min_task_bid: model.min_task_bid,
+        webhook_url: model.webhook_url,
+        created: model.created,
+    }))
+}
+
+/// Gets the saved searches of an [`ApiKey`], oldest first.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_saved_searches<'e, E>(
+    api_key: ApiKey,
+    executor: E,
+) -> Result<Vec<model::SavedSearch>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query_as!(
+        model::SavedSearch,
+        r#"SELECT search_id AS "search_id!", network, task_type, min_task_bid, webhook_url, created
+           FROM saved_searches
+           WHERE api_key = ?
+           ORDER BY created, search_id"#,
+        bytes
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Deletes a saved search of an [`ApiKey`], along with its alerts.
+///
+/// Returns whether the search was found.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn delete_saved_search<'e, E>(
+    search_id: &str,
+    api_key: ApiKey,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query!(
+        "DELETE FROM saved_searches WHERE search_id = ? AND api_key = ?",
+        search_id,
+        bytes
+    )
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Gets the saved searches which could match a job with the task type: those
+/// for the task type, and those for any task type.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_candidate_searches<'e, E>(
+    task_type: Option<&str>,
+    executor: E,
+) -> Result<Vec<model::SavedSearch>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(sqlx::query_as!(
+        model::SavedSearch,
+        r#"SELECT search_id AS "search_id!", network, task_type, min_task_bid, webhook_url, created
+           FROM saved_searches
+           WHERE task_type = ? OR task_type IS NULL"#,
+        task_type
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records that a job matched a saved search.
+///
+/// Each job alerts a search at most once, so returns whether the alert is new.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn new_job_alert<'e, E>(model: model::NewJobAlert, executor: E) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(sqlx::query!(
+        r#"INSERT OR IGNORE INTO job_alerts (search_id, job_id, created, next_attempt)
+           VALUES (?, ?, ?, ?)"#,
+        model.search_id,
+        model.job_id,
+        model.created,
+        model.next_attempt
+    )
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Gets the alerts for the saved searches of an [`ApiKey`] which come after
+/// the alert `after`, oldest first.
+///
+/// Jobs which have been password protected since they matched are left out.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn get_job_alerts<'e, E>(
+    api_key: ApiKey,
+    after: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::JobAlert>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    use sqlx::FromRow;
+    sqlx::query(
+        "SELECT jobs.*,
+            job_alerts.alert_id,
+            job_alerts.search_id,
+            job_alerts.created AS alert_created,
+            job_alerts.delivered
+         FROM saved_searches
+         JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+         JOIN jobs ON jobs.job_id = job_alerts.job_id
+         WHERE saved_searches.api_key = ? AND job_alerts.alert_id > ? AND jobs.password IS NULL
+         ORDER BY job_alerts.alert_id
+         LIMIT ?",
+    )
+    .bind(api_key.into_inner())
+    .bind(after)
+    .bind(limit)
+    .fetch_all(executor)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(model::JobAlert {
+            job: model::Job::from_row(row)?,
+            alert_id: row.try_get("alert_id")?,
+            search_id: row.try_get("search_id")?,
+            alert_created: row.try_get("alert_created")?,
+            delivered: row.try_get("delivered")?,
+        })
+    })
+    .collect()
+}
+
+/// Gets the id of the newest alert for the saved searches of an [`ApiKey`], or 0 when there are none.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_latest_alert_id<'e, E>(api_key: ApiKey, executor: E) -> Result<i64>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query!(
+        r#"SELECT COALESCE(MAX(job_alerts.alert_id), 0) AS "alert_id!: i64"
+           FROM saved_searches
+           JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+           WHERE saved_searches.api_key = ?"#,
+        bytes
+    )
+    .fetch_one(executor)
+    .await?
+    .alert_id)
+}
+
+/// Gets alerts whose webhook is due to be sent at `now`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn get_pending_webhooks<'e, E>(
+    now: i64,
+    limit: i64,
+    executor: E,
+) -> Result<Vec<model::PendingWebhook>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(sqlx::query_as!(
+        model::PendingWebhook,
+        r#"SELECT
+            job_alerts.alert_id AS "alert_id!",
+            job_alerts.search_id,
+            job_alerts.job_id,
+            saved_searches.webhook_url AS "webhook_url!",
+            job_alerts.created,
+            job_alerts.attempts
+           FROM job_alerts
+           JOIN saved_searches ON saved_searches.search_id = job_alerts.search_id
+           WHERE job_alerts.next_attempt <= ? AND saved_searches.webhook_url IS NOT NULL
+           ORDER BY job_alerts.next_attempt
+           LIMIT ?"#,
+        now,
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Records an attempt to send an alert's webhook.
+///
+/// `next_attempt` is when to send it again, and is `None` once it has been
+/// `delivered` or given up on.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn record_webhook_attempt<'e, E>(
+    alert_id: i64,
+    delivered: Option<i64>,
+    next_attempt: Option<i64>,
+    executor: E,
+) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query!(
+        r#"UPDATE job_alerts SET
+            attempts = attempts + 1,
+            delivered = ?,
+            next_attempt = ?
+           WHERE alert_id = ?"#,
+        delivered,
+        next_attempt,
+        alert_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Claims a task of a job for an [`ApiKey`], unless every task of the job is claimed.
+///
+/// Only claims whose lease lasts beyond `now` are counted against the job's
+/// `total_tasks`, and jobs without a `total_tasks` can not be claimed. The
+/// limit is checked by the insert itself, so concurrent requests can not exceed
+/// it. Returns `None` when every task is claimed.
+#[tracing::instrument(level = "debug", skip(model, executor))]
+pub async fn new_claim<'e, E>(
+    model: model::NewClaim,
+    now: i64,
+    executor: E,
+) -> Result<Option<model::Claim>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let inserted = sqlx::query!(
+        r#"INSERT INTO job_claims (claim_id, job_id, api_key, created, expires)
+           SELECT ?, ?, ?, ?, ?
+           WHERE (SELECT COUNT(*) FROM job_claims WHERE job_id = ? AND expires > ?)
+               < (SELECT total_tasks FROM jobs WHERE job_id = ?)"#,
+        model.claim_id,
+        model.job_id,
+        model.api_key,
+        model.created,
+        model.expires,
+        model.job_id,
+        now,
+        model.job_id
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+    Ok((inserted > 0).then_some(model::Claim {
+        claim_id: model.claim_id,
+        job_id: model.job_id,
+        shortcode: model.shortcode,
+        created: model.created,
+        expires: model.expires,
+    }))
+}
+
+/// Gets a claim of an [`ApiKey`] whose lease lasts beyond `now`.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn get_claim<'e, E>(
+    claim_id: &str,
+    api_key: ApiKey,
+    now: i64,
+    executor: E,
+) -> Result<Option<model::Claim>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query_as!(
+        model::Claim,
+        r#"SELECT
+            job_claims.claim_id AS "claim_id!",
+            job_claims.job_id,
+            jobs.shortcode,
+            job_claims.created,
+            job_claims.expires
+           FROM job_claims
+           JOIN jobs ON jobs.job_id = job_claims.job_id
+           WHERE job_claims.claim_id = ? AND job_claims.api_key = ? AND job_claims.expires > ?"#,
+        claim_id,
+        bytes,
+        now
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+/// Moves the end of the lease of a claim of an [`ApiKey`] to `expires`, as
+/// long as the claim is of the job with the shortcode and has not expired at `now`.
+///
+/// Returns whether the claim was found.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn renew_claim<'e, E>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    expires: i64,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query!(
+        r#"UPDATE job_claims SET expires = ?
+           WHERE claim_id = ? AND api_key = ? AND expires > ?
+               AND job_id = (SELECT job_id FROM jobs WHERE shortcode = ?)"#,
+        expires,
+        claim_id,
+        bytes,
+        now,
+        shortcode
+    )
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Releases a claim of an [`ApiKey`] on the job with the shortcode, freeing its task.
+///
+/// Returns whether the claim was found and had not expired at `now`.
+#[tracing::instrument(level = "debug", skip(api_key, executor))]
+pub async fn release_claim<'e, E>(
+    claim_id: &str,
+    shortcode: &str,
+    api_key: ApiKey,
+    now: i64,
+    executor: E,
+) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let bytes = api_key.into_inner();
+    Ok(sqlx::query!(
+        r#"DELETE FROM job_claims
+           WHERE claim_id = ? AND api_key = ? AND expires > ?
+               AND job_id = (SELECT job_id FROM jobs WHERE shortcode = ?)"#,
+        claim_id,
+        bytes,
+        now,
+        shortcode
+    )
+    .execute(executor)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Deletes the claims whose lease ended at or before `now`.
+#[tracing::instrument(level = "debug", skip(executor))]
+pub async fn delete_expired_claims<'e, E>(now: i64, executor: E) -> Result<u64>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    Ok(
+        sqlx::query!("DELETE FROM job_claims WHERE expires <= ?", now)
+            .execute(executor)
+            .await?
+            .rows_affected(),
+    )
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::data::query::test::{model_get_job, model_new_job};
+    use crate::data::test::*;
+    use crate::data::*;
+    use crate::domain::job::field::ShortCodeConfig;
+    use crate::test::async_runtime;
+
+    #[test]
+    fn backup_is_a_copy_of_the_database() {
+        // In-memory databases write their backups to memory, so use files.
+        let rt = async_runtime();
+        let dir = std::env::temp_dir();
+        let source = dir.join(format!("gptx-source-{}.db", DbId::new()));
+        let backup = dir.join(format!("gptx-backup-{}.db", DbId::new()));
+        let backup_str = backup.to_string_lossy().into_owned();
+
+        let res = rt.block_on(async {
+            let db = Database::new(&format!("sqlite:{}", source.display())).await?;
+            let pool = sqlite_pool(&db);
+            SQLITE_MIGRATOR.run(pool).await?;
+            super::new_job(model_new_job("1"), &ShortCodeConfig::default(), pool).await?;
+            super::optimize(pool).await?;
+            super::backup_into(&backup_str, pool).await?;
+
+            let copy = Database::new(&format!("sqlite:{}", backup_str)).await?;
+            let job = super::get_job(model_get_job("1"), sqlite_pool(&copy)).await?;
+            let backup_again = super::backup_into(&backup_str, pool).await;
+            Ok::<_, DataError>((job.shortcode, backup_again.is_err()))
+        });
+        for path in [&source, &backup] {
+            for suffix in ["", "-wal", "-shm"] {
+                std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+            }
+        }
+        let (shortcode, backup_again_failed) = res.unwrap();
+        assert_eq!(shortcode, "1");
+        assert!(backup_again_failed);
+    }
+
+    /// Every statement in this module which has a query plan, as written above,
+    /// and the arguments that fill the `{}` of batched statements.
+    const PLANNED: &[(&str, &[&str])] = &[
+        (
+            "UPDATE jobs SET responses = responses + CASE shortcode {} ELSE 0 END
+                 WHERE shortcode IN ({})",
+            &["WHEN ? THEN ?", "?"],
+        ),
+        (
+            "INSERT INTO job_response_stats (shortcode, bucket, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, bucket)
+                 DO UPDATE SET responses = responses + excluded.responses",
+            &["(?, ?, ?)"],
+        ),
+        (
+            r#"SELECT
+            (bucket / ?1) * ?1 AS "bucket!: i64",
+            SUM(responses) AS "responses!: i64"
+           FROM job_response_stats
+           WHERE (?2 IS NULL OR shortcode = ?2) AND bucket >= ?3
+           GROUP BY 1
+           ORDER BY 1"#,
+            &[],
+        ),
+        (
+            r#"INSERT INTO job_response_stats (shortcode, bucket, responses)
+               SELECT shortcode, bucket - bucket % ?2, SUM(responses)
+               FROM job_response_stats
+               WHERE bucket < ?1 AND bucket % ?2 != 0
+               GROUP BY shortcode, bucket - bucket % ?2
+               ON CONFLICT (shortcode, bucket)
+               DO UPDATE SET responses = responses + excluded.responses"#,
+            &[],
+        ),
+        (
+            "DELETE FROM job_response_stats WHERE bucket < ?1 AND bucket % ?2 != 0",
+            &[],
+        ),
+        (
+            "INSERT INTO job_response_channels (shortcode, channel, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, channel)
+                 DO UPDATE SET responses = responses + excluded.responses",
+            &["(?, ?, ?)"],
+        ),
+        (
+            "INSERT INTO job_response_api_keys (shortcode, api_key, responses)
+                 VALUES {}
+                 ON CONFLICT (shortcode, api_key)
+                 DO UPDATE SET responses = responses + excluded.responses",
+            &["(?, ?, ?)"],
+        ),
+        (
+            "INSERT OR IGNORE INTO job_visitors (shortcode, day, visitor) VALUES {}",
+            &["(?, ?, ?)"],
+        ),
+        ("DELETE FROM job_visitors WHERE day < ?", &[]),
+        (
+            r#"SELECT
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'html'), 0) AS "html!: i64",
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'raw'), 0) AS "raw!: i64",
+            COALESCE((SELECT responses FROM job_response_channels
+                      WHERE shortcode = ?1 AND channel = 'api'), 0) AS "api!: i64",
+            (SELECT COUNT(*) FROM job_response_api_keys
+             WHERE shortcode = ?1) AS "api_keys!: i64",
+            (SELECT COUNT(DISTINCT visitor) FROM job_visitors
+             WHERE shortcode = ?1) AS "unique_visitors!: i64""#,
+            &[],
+        ),
+        ("SELECT 1", &[]),
+        (
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+            &[],
+        ),
+        (
+            "SELECT version FROM _sqlx_migrations WHERE success = 1 ORDER BY version",
+            &[],
+        ),
+        (
+            r#"SELECT
+            COUNT(*) AS "total!: i64",
+            COUNT(CASE WHEN strftime('%s', 'now') > expires THEN 1 END) AS "expired!: i64"
+        FROM jobs"#,
+            &[],
+        ),
+        ("SELECT * FROM jobs WHERE shortcode = ?", &[]),
+        ("SELECT * FROM jobs WHERE job_id = ?", &[]),
+        (
+            "SELECT * FROM jobs WHERE escrow_id = ? ORDER BY posted DESC LIMIT 1",
+            &[],
+        ),
+        (
+            r#"INSERT INTO jobs (
+            job_id,
+            shortcode,
+            escrow_id,
+            manifest_url,
+            posted,
+            expires,
+            password,
+            responses,
+            manifest_hash,
+            manifest_status,
+            reward_token,
+            reward_symbol,
+            reward_decimals,
+            reward_balance,
+            network)
+           VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            &[],
+        ),
+        ("SELECT posted FROM jobs ORDER BY posted DESC LIMIT 1", &[]),
+        (
+            r#"UPDATE jobs SET
+            escrow_id = ?,
+            expires = ?,
+            password = ?,
+            manifest_status = CASE
+                WHEN manifest_url IS ? THEN manifest_status
+                WHEN ? IS NOT NULL AND manifest_hash IS NOT NULL THEN 'pending'
+                ELSE 'unverifiable'
+            END,
+            manifest_checked = CASE WHEN manifest_url IS ? THEN manifest_checked END,
+            task_type = CASE WHEN manifest_url IS ? THEN task_type END,
+            task_bid = CASE WHEN manifest_url IS ? THEN task_bid END,
+            total_tasks = CASE WHEN manifest_url IS ? THEN total_tasks END,
+            manifest_url = ?
+           WHERE shortcode = ?"#,
+            &[],
+        ),
+        (
+            r#"SELECT job_id, manifest_url AS "manifest_url!", manifest_hash, reward_decimals
+           FROM jobs
+           WHERE (manifest_status = 'pending'
+                  OR (manifest_status = 'unavailable' AND manifest_checked < ?)
+                  OR (manifest_status = 'unverifiable' AND manifest_checked IS NULL))
+             AND manifest_url IS NOT NULL
+           LIMIT ?"#,
+            &[],
+        ),
+        (
+            r#"UPDATE jobs SET
+            manifest_status = ?,
+            task_type = CASE WHEN ? = 'unavailable' THEN task_type ELSE ? END,
+            task_bid = CASE WHEN ? = 'unavailable' THEN task_bid ELSE ? END,
+            total_tasks = CASE WHEN ? = 'unavailable' THEN total_tasks ELSE ? END,
+            manifest_checked = ?
+           WHERE job_id = ?"#,
+            &[],
+        ),
+        (
+            "UPDATE job_documents SET description = ?, manifest = ? WHERE job_id = ?",
+            &[],
+        ),
+        (
+            "SELECT jobs.*, {} AS snippet
+         FROM {}
+         WHERE {}{}jobs.password IS NULL
+           AND (jobs.expires IS NULL OR jobs.expires >= strftime('%s', 'now'))
+         ORDER BY {}
+         LIMIT ? OFFSET ?",
+            &[
+                "snippet(job_search, -1, char(2), char(3), '…', 16)",
+                "job_search
+                 JOIN job_documents ON job_documents.doc_id = job_search.rowid
+                 JOIN jobs ON jobs.job_id = job_documents.job_id",
+                "job_search MATCH ? AND ",
+                "jobs.task_type = ? AND ",
+                "rank",
+            ],
+        ),
+        (
+            r#"SELECT task_type AS "task_type?", COUNT(*) AS "count!: i64"
+           FROM jobs
+           WHERE password IS NULL
+             AND (expires IS NULL OR expires >= strftime('%s', 'now'))
+           GROUP BY task_type
+           ORDER BY 2 DESC, 1"#,
+            &[],
+        ),
+        (
+            r#"SELECT jobs.task_type AS "task_type?", COUNT(*) AS "count!: i64"
+           FROM job_search
+           JOIN job_documents ON job_documents.doc_id = job_search.rowid
+           JOIN jobs ON jobs.job_id = job_documents.job_id
+           WHERE job_search MATCH ?
+             AND jobs.password IS NULL
+             AND (jobs.expires IS NULL OR jobs.expires >= strftime('%s', 'now'))
+           GROUP BY jobs.task_type
+           ORDER BY 2 DESC, 1"#,
+            &[],
+        ),
+        ("INSERT INTO api_keys (api_key) VALUES (?)", &[]),
+        ("DELETE FROM api_keys WHERE api_key == ?", &[]),
+        (
+            "SELECT COUNT(api_key) FROM api_keys WHERE api_key = ?",
+            &[],
+        ),
+        (
+            r#"DELETE FROM jobs WHERE strftime('%s', 'now') > expires"#,
+            &[],
+        ),
+        (
+            r#"INSERT INTO saved_searches (
+            search_id,
+            api_key,
+            network,
+            task_type,
+            min_task_bid,
+            webhook_url,
+            created)
+           SELECT ?, ?, ?, ?, ?, ?, ?
+           WHERE (SELECT COUNT(*) FROM saved_searches WHERE api_key = ?) < ?"#,
+            &[],
+        ),
+        (
+            r#"SELECT search_id AS "search_id!", network, task_type, min_task_bid, webhook_url, created
+           FROM saved_searches
+           WHERE api_key = ?
+           ORDER BY created, search_id"#,
+            &[],
+        ),
+        (
+            "DELETE FROM saved_searches WHERE search_id = ? AND api_key = ?",
+            &[],
+        ),
+        (
+            r#"SELECT search_id AS "search_id!", network, task_type, min_task_bid, webhook_url, created
+           FROM saved_searches
+           WHERE task_type = ? OR task_type IS NULL"#,
+            &[],
+        ),
+        (
+            r#"INSERT OR IGNORE INTO job_alerts (search_id, job_id, created, next_attempt)
+           VALUES (?, ?, ?, ?)"#,
+            &[],
+        ),
+        (
+            "SELECT jobs.*,
+            job_alerts.alert_id,
+            job_alerts.search_id,
+            job_alerts.created AS alert_created,
+            job_alerts.delivered
+         FROM saved_searches
+         JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+         JOIN jobs ON jobs.job_id = job_alerts.job_id
+         WHERE saved_searches.api_key = ? AND job_alerts.alert_id > ? AND jobs.password IS NULL
+         ORDER BY job_alerts.alert_id
+         LIMIT ?",
+            &[],
+        ),
+        (
+            r#"SELECT COALESCE(MAX(job_alerts.alert_id), 0) AS "alert_id!: i64"
+           FROM saved_searches
+           JOIN job_alerts ON job_alerts.search_id = saved_searches.search_id
+           WHERE saved_searches.api_key = ?"#,
+            &[],
+        ),
+        (
+            r#"SELECT
+            job_alerts.alert_id AS "alert_id!",
+            job_alerts.search_id,
+            job_alerts.job_id,
+            saved_searches.webhook_url AS "webhook_url!",
+            job_alerts.created,
+            job_alerts.attempts
+           FROM job_alerts
+           JOIN saved_searches ON saved_searches.search_id = job_alerts.search_id
+           WHERE job_alerts.next_attempt <= ? AND saved_searches.webhook_url IS NOT NULL
+           ORDER BY job_alerts.next_attempt
+           LIMIT ?"#,
+            &[],
+        ),
+        (
+            r#"UPDATE job_alerts SET
+            attempts = attempts + 1,
+            delivered = ?,
+            next_attempt = ?
+           WHERE alert_id = ?"#,
+            &[],
+        ),
+        (
+            r#"INSERT INTO job_claims (claim_id, job_id, api_key, created, expires)
+           SELECT ?, ?, ?, ?, ?
+           WHERE (SELECT COUNT(*) FROM job_claims WHERE job_id = ? AND expires > ?)
+               < (SELECT total_tasks FROM jobs WHERE job_id = ?)"#,
+            &[],
+        ),
+        (
+            r#"SELECT
+            job_claims.claim_id AS "claim_id!",
+            job_claims.job_id,
+            jobs.shortcode,
+            job_claims.created,
+            job_claims.expires
+           FROM job_claims
+           JOIN jobs ON jobs.job_id = job_claims.job_id
+           WHERE job_claims.claim_id = ? AND job_claims.api_key = ? AND job_claims.expires > ?"#,
+            &[],
+        ),
+        (
+            r#"UPDATE job_claims SET expires = ?
+           WHERE claim_id = ? AND api_key = ? AND expires > ?
+               AND job_id = (SELECT job_id FROM jobs WHERE shortcode = ?)"#,
+            &[],
+        ),
+        (
+            r#"DELETE FROM job_claims
+           WHERE claim_id = ? AND api_key = ? AND expires > ?
+               AND job_id = (SELECT job_id FROM jobs WHERE shortcode = ?)"#,
+            &[],
+        ),
+        ("DELETE FROM job_claims WHERE expires <= ?", &[]),
+    ];
+
+    /// Statements in this module which have no query plan.
+    const UNPLANNED: &[&str] = &["PRAGMA optimize", "PRAGMA incremental_vacuum"];
+
+    /// Tables which are small enough to scan, or which are read in full on purpose.
+    const SCANNABLE: &[&str] = &["sqlite_master", "_sqlx_migrations"];
+
+    /// Collapses all whitespace, so statements can be compared regardless of indentation.
+    fn normalize(sql: &str) -> String {
+        sql.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn every_query_is_planned() {
+        let source = include_str!("sqlite.rs");
+        let source = &source[..source.find("#[cfg(test)]").unwrap()];
+        let normalized = normalize(source);
+
+        for sql in PLANNED
+            .iter()
+            .map(|(sql, _)| *sql)
+            .chain(UNPLANNED.iter().copied())
+        {
+            assert!(
+                normalized.contains(&normalize(sql)),
+                "statement is no longer used, update PLANNED: {}",
+                sql
+            );
+        }
+        assert_eq!(
+            source.matches("sqlx::query").count(),
+            PLANNED.len() + UNPLANNED.len(),
+            "every statement in data::query::sqlite must be listed in PLANNED or UNPLANNED"
+        );
+    }
+
+    #[test]
+    fn queries_do_not_scan_tables() {
+        use crate::domain::board::JobSort;
+        use sqlx::Row;
+
+        let rt = async_runtime();
+        let db = rt.block_on(Database::new(":memory:")).unwrap();
+        let pool = sqlite_pool(&db);
+        rt.block_on(SQLITE_MIGRATOR.run(pool)).unwrap();
+
+        let planned = PLANNED.iter().map(|(template, args)| {
+            args.iter()
+                .fold(template.to_string(), |sql, arg| sql.replacen("{}", arg, 1))
+        });
+        // Every variation of the job board listing, which PLANNED only has one of.
+        let sorts = JobSort::ALL.into_iter().map(Some).chain([None]);
+        let listings = [(false, false), (true, false), (false, true), (true, true)]
+            .into_iter()
+            .flat_map(|(filter_task_type, search)| {
+                sorts
+                    .clone()
+                    .map(move |sort| super::list_jobs_sql(filter_task_type, search, sort))
+            });
+        for sql in planned.chain(listings) {
+            let plan: Vec<String> = rt
+                .block_on(sqlx::query(&format!("EXPLAIN QUERY PLAN {}", sql)).fetch_all(pool))
+                .unwrap_or_else(|e| panic!("failed to plan {}: {}", sql, e))
+                .iter()
+                .map(|row| row.get("detail"))
+                .collect();
+            let scans: Vec<_> = plan
+                .iter()
+                // Virtual tables, such as the search index, are read through their own index.
+                .filter(|detail| detail.starts_with("SCAN ") && !detail.contains(" USING "))
+                .filter(|detail| !detail.contains(" VIRTUAL TABLE INDEX "))
+                .filter(|detail| {
+                    let table = detail.split_whitespace().nth(1).unwrap_or_default();
+                    table != "CONSTANT" && !SCANNABLE.contains(&table)
+                })
+                .collect();
+            assert!(
+                scans.is_empty(),
+                "{} scans a table: {:?}",
+                normalize(&sql),
+                plan
+            );
+        }
+    }
+}
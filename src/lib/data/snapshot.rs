@@ -0,0 +1,375 @@
+//! Portable snapshots of the database.
+//!
+//! A snapshot is a SQLite database file containing a consistent copy of every
+//! table in [`SNAPSHOT_TABLES`], along with a `gptx_snapshot` header table that
+//! records the schema version it was taken at. Snapshots can only be restored
+//! into a database with the same schema version.
+//!
+//! Only SQLite databases have snapshots. PostgreSQL databases are backed up
+//! with its own tools, such as `pg_dump`.
+
+use super::{query, Backend, DataError, Database, DatabasePool};
+use serde::Serialize;
+use sqlx::sqlite::SqlitePool;
+use sqlx::Connection;
+use std::path::Path;
+
+/// [`Result`] alias for snapshot functions.
+type Result<T> = std::result::Result<T, DataError>;
+
+/// The tables which are copied by a snapshot, in the order they are restored.
+///
+/// `job_documents` is restored after `jobs`, whose triggers add a document for
+/// every job without its manifest text. The search index is kept up to date by
+/// the triggers on `job_documents`. `saved_searches`, `job_alerts` and
+/// `job_claims` are restored last, since clearing `jobs` and `api_keys` also
+/// clears them.
+pub const SNAPSHOT_TABLES: &[&str] = &[
+    "jobs",
+    "job_documents",
+    "api_keys",
+    "job_response_stats",
+    "job_response_channels",
+    "job_response_api_keys",
+    "job_visitors",
+    "saved_searches",
+    "job_alerts",
+    "job_claims",
+];
+
+/// Describes a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct SnapshotHeader {
+    /// The version of the most recent migration applied when the snapshot was taken.
+    pub schema_version: i64,
+    /// When the snapshot was taken, in seconds since the Unix epoch.
+    pub created: i64,
+}
+
+/// Gets the SQLite pool, or fails with [`DataError::UnsupportedBackend`].
+fn require_sqlite(pool: &DatabasePool) -> Result<&SqlitePool> {
+    match pool {
+        DatabasePool::Sqlite(pool) => Ok(pool),
+        DatabasePool::Postgres(_) => Err(DataError::UnsupportedBackend {
+            operation: "snapshots",
+            backend: Backend::Postgres,
+        }),
+    }
+}
+
+/// Gets the version of the most recent migration applied to the database.
+async fn schema_version(pool: &DatabasePool) -> Result<i64> {
+    Ok(query::applied_migrations(pool)
+        .await?
+        .last()
+        .copied()
+        .unwrap_or_default())
+}
+
+/// Writes a snapshot of the database to a new file at `path`.
+///
+/// The database remains available while the snapshot is taken. Fails if a
+/// non-empty file already exists at `path`.
+pub async fn create(pool: &DatabasePool, path: &Path) -> Result<SnapshotHeader> {
+    let sqlite = require_sqlite(pool)?;
+    let header = SnapshotHeader {
+        schema_version: schema_version(pool).await?,
+        created: chrono::Utc::now().timestamp(),
+    };
+    query::sqlite::backup_into(&path.to_string_lossy(), sqlite).await?;
+    if !path.is_file() {
+        return Err(DataError::InvalidSnapshot(
+            "in-memory databases can not be backed up".to_owned(),
+        ));
+    }
+
+    let snapshot = Database::new(&format!("sqlite:{}", path.display())).await?;
+    let snapshot = require_sqlite(snapshot.get_pool())?;
+    sqlx::query(
+        "CREATE TABLE gptx_snapshot (schema_version BIGINT NOT NULL, created BIGINT NOT NULL)",
+    )
+    .execute(snapshot)
+    .await?;
+    sqlx::query("INSERT INTO gptx_snapshot (schema_version, created) VALUES (?, ?)")
+        .bind(header.schema_version)
+        .bind(header.created)
+        .execute(snapshot)
+        .await?;
+    snapshot.close().await;
+    Ok(header)
+}
+
+/// Replaces the contents of the database with the snapshot at `path`.
+///
+/// Everything is replaced in a single transaction, so the database is left
+/// unchanged if the restore fails. The snapshot is detached again whether or not
+/// the restore succeeds, and a connection which cannot detach it is closed
+/// instead of being returned to the pool.
+pub async fn restore(pool: &DatabasePool, path: &Path) -> Result<SnapshotHeader> {
+    let sqlite = require_sqlite(pool)?;
+    if !path.is_file() {
+        return Err(DataError::InvalidSnapshot(format!(
+            "'{}' does not exist",
+            path.display()
+        )));
+    }
+    let database_version = schema_version(pool).await?;
+
+    let mut conn = sqlite.acquire().await?;
+    sqlx::query("ATTACH DATABASE ? AS snapshot")
+        .bind(path.to_string_lossy())
+        .execute(&mut conn)
+        .await?;
+    let res = restore_attached(&mut conn, database_version).await;
+    if let Err(e) = sqlx::query("DETACH DATABASE snapshot")
+        .execute(&mut conn)
+        .await
+    {
+        tracing::warn!(error = %e, "failed to detach snapshot, closing connection");
+        conn.detach().close().await.ok();
+    }
+    res
+}
+
+/// Copies every table from the attached `snapshot` database.
+async fn restore_attached(
+    conn: &mut sqlx::pool::PoolConnection<sqlx::Sqlite>,
+    database_version: i64,
+) -> Result<SnapshotHeader> {
+    let header: SnapshotHeader =
+        sqlx::query_as("SELECT schema_version, created FROM snapshot.gptx_snapshot")
+            .fetch_optional(&mut *conn)
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| DataError::InvalidSnapshot("missing snapshot header".to_owned()))?;
+    if header.schema_version != database_version {
+        return Err(DataError::SnapshotVersionMismatch {
+            snapshot: header.schema_version,
+            database: database_version,
+        });
+    }
+
+    let mut transaction = conn.begin().await?;
+    for table in SNAPSHOT_TABLES {
+        sqlx::query(&format!("DELETE FROM main.{}", table))
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(&format!(
+            "INSERT INTO main.{0} SELECT * FROM snapshot.{0}",
+            table
+        ))
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::test::sqlite_pool;
+    use crate::data::{migration, DbId};
+    use crate::test::async_runtime;
+    use std::path::PathBuf;
+
+    /// A database file which is deleted when dropped.
+    ///
+    /// In-memory databases attach and back up to memory, so snapshots need files.
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("gptx-{}-{}.db", name, DbId::new())))
+        }
+
+        fn url(&self) -> String {
+            format!("sqlite:{}", self.0.display())
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                std::fs::remove_file(format!("{}{}", self.0.display(), suffix)).ok();
+            }
+        }
+    }
+
+    async fn count_jobs(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM jobs")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let rt = async_runtime();
+        let (source, target, snapshot) = (
+            TempDb::new("source"),
+            TempDb::new("target"),
+            TempDb::new("snapshot"),
+        );
+        rt.block_on(async {
+            let source_db = Database::new(&source.url()).await.unwrap();
+            migration::run(source_db.get_pool()).await.unwrap();
+            sqlx::query(
+                "INSERT INTO jobs (job_id, shortcode, escrow_id, posted, responses)
+                 VALUES ('id', 'code', 'escrow', 1, 3)",
+            )
+            .execute(sqlite_pool(&source_db))
+            .await
+            .unwrap();
+            sqlx::query("UPDATE job_documents SET manifest = 'searchable' WHERE job_id = 'id'")
+                .execute(sqlite_pool(&source_db))
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO api_keys VALUES (x'01')")
+                .execute(sqlite_pool(&source_db))
+                .await
+                .unwrap();
+
+            let created = create(source_db.get_pool(), &snapshot.0).await.unwrap();
+            assert_eq!(
+                created.schema_version,
+                *migration::status(source_db.get_pool())
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|migration| &migration.version)
+                    .max()
+                    .unwrap()
+            );
+
+            let target_db = Database::new(&target.url()).await.unwrap();
+            migration::run(target_db.get_pool()).await.unwrap();
+            sqlx::query(
+                "INSERT INTO jobs (job_id, shortcode, escrow_id, posted, responses)
+                 VALUES ('other', 'other', 'escrow', 1, 0)",
+            )
+            .execute(sqlite_pool(&target_db))
+            .await
+            .unwrap();
+
+            let restored = restore(target_db.get_pool(), &snapshot.0).await.unwrap();
+            assert_eq!(restored, created);
+            assert_eq!(count_jobs(sqlite_pool(&target_db)).await, 1);
+            let responses: i64 =
+                sqlx::query_scalar("SELECT responses FROM jobs WHERE shortcode = 'code'")
+                    .fetch_one(sqlite_pool(&target_db))
+                    .await
+                    .unwrap();
+            assert_eq!(responses, 3);
+            let found: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM job_search WHERE job_search MATCH 'searchable'",
+            )
+            .fetch_one(sqlite_pool(&target_db))
+            .await
+            .unwrap();
+            assert_eq!(found, 1);
+        });
+    }
+
+    #[test]
+    fn snapshots_must_match_schema_version() {
+        let rt = async_runtime();
+        let (source, target, snapshot) = (
+            TempDb::new("source"),
+            TempDb::new("target"),
+            TempDb::new("snapshot"),
+        );
+        rt.block_on(async {
+            let source_db = Database::new(&source.url()).await.unwrap();
+            migration::run(source_db.get_pool()).await.unwrap();
+            create(source_db.get_pool(), &snapshot.0).await.unwrap();
+
+            let target_db = Database::new(&target.url()).await.unwrap();
+            migration::run(target_db.get_pool()).await.unwrap();
+            migration::revert(target_db.get_pool(), None).await.unwrap();
+            assert!(matches!(
+                restore(target_db.get_pool(), &snapshot.0).await,
+                Err(DataError::SnapshotVersionMismatch { .. })
+            ));
+
+            assert!(matches!(
+                restore(target_db.get_pool(), &source.0).await,
+                Err(DataError::InvalidSnapshot(_))
+            ));
+            assert!(matches!(
+                restore(target_db.get_pool(), Path::new("does/not/exist.db")).await,
+                Err(DataError::InvalidSnapshot(_))
+            ));
+
+            let attached: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM pragma_database_list WHERE name = 'snapshot'",
+            )
+            .fetch_one(sqlite_pool(&target_db))
+            .await
+            .unwrap();
+            assert_eq!(attached, 0);
+        });
+    }
+
+    #[test]
+    fn snapshots_copy_large_databases_without_overwriting() {
+        let rt = async_runtime();
+        let (source, snapshot) = (TempDb::new("source"), TempDb::new("snapshot"));
+        rt.block_on(async {
+            let source_db = Database::new(&source.url()).await.unwrap();
+            migration::run(source_db.get_pool()).await.unwrap();
+            // Enough manifest text to take several steps of the backup.
+            sqlx::query(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+                 INSERT INTO jobs (job_id, shortcode, escrow_id, posted, responses)
+                 SELECT 'id' || i, 'code' || i, 'escrow', 1, 0 FROM n",
+            )
+            .execute(sqlite_pool(&source_db))
+            .await
+            .unwrap();
+            sqlx::query("UPDATE job_documents SET manifest = hex(randomblob(4096))")
+                .execute(sqlite_pool(&source_db))
+                .await
+                .unwrap();
+
+            create(source_db.get_pool(), &snapshot.0).await.unwrap();
+            let snapshot_db = Database::new(&snapshot.url()).await.unwrap();
+            assert_eq!(count_jobs(sqlite_pool(&snapshot_db)).await, 500);
+            let manifests: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM job_documents WHERE length(manifest) = 8192",
+            )
+            .fetch_one(sqlite_pool(&snapshot_db))
+            .await
+            .unwrap();
+            assert_eq!(manifests, 500);
+            snapshot_db.get_pool().close().await;
+
+            assert!(matches!(
+                create(source_db.get_pool(), &snapshot.0).await,
+                Err(DataError::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists
+            ));
+        });
+    }
+
+    #[test]
+    fn snapshots_are_sqlite_only() {
+        let rt = async_runtime();
+        rt.block_on(async {
+            let pool = DatabasePool::Postgres(
+                sqlx::postgres::PgPoolOptions::new()
+                    .connect_lazy("postgres://localhost/gptx")
+                    .unwrap(),
+            );
+            let path = Path::new("does/not/exist.db");
+            assert!(matches!(
+                create(&pool, path).await,
+                Err(DataError::UnsupportedBackend { .. })
+            ));
+            assert!(matches!(
+                restore(&pool, path).await,
+                Err(DataError::UnsupportedBackend { .. })
+            ));
+        });
+    }
+}
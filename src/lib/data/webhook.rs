@@ -0,0 +1,161 @@
+//! Posting job alerts to the webhooks of saved searches.
+//!
+//! Webhook URLs are chosen by API key holders, so they are checked against the
+//! [`WebhookPolicy`] again before every post, and the connection is pinned to
+//! the resolved addresses once they are checked too. Redirects are not
+//! followed, and only a `2xx` response counts as delivered.
+use super::manifest;
+use crate::data::DataError;
+use crate::domain::alert::WebhookPolicy;
+use crate::domain::job::field;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Post `body` as JSON to the webhook at `webhook_url`.
+#[tracing::instrument(level = "debug", skip(body, policy))]
+pub async fn post<T: Serialize>(
+    webhook_url: &str,
+    body: &T,
+    policy: &WebhookPolicy,
+) -> Result<(), DataError> {
+    let url = policy
+        .check(webhook_url)
+        .map_err(|e| rejected(&e.to_string()))?;
+    let checked = manifest::pin(url, |ip| {
+        field::check_address(ip, policy.allow_private_addresses)
+    })
+    .await
+    .map_err(|reason| rejected(&reason))?;
+    let client = manifest::client(&checked, Duration::from_secs(policy.timeout_secs))
+        .map_err(|e| rejected(&e.to_string()))?;
+    let res = client
+        .post(checked.url.clone())
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| rejected(&e.to_string()))?;
+    if !res.status().is_success() {
+        return Err(rejected(&format!("responded with {}", res.status())));
+    }
+    Ok(())
+}
+
+fn rejected(reason: &str) -> DataError {
+    DataError::Webhook(reason.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::post;
+    use crate::data::DataError;
+    use crate::domain::alert::WebhookPolicy;
+    use crate::test::async_runtime;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Respond to one request with `status`, returning the base URL of the
+    /// server and the request it receives.
+    fn serve(status: &'static str) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The headers and body may arrive separately, so read until the whole body has.
+            let mut request = String::new();
+            let mut buf = [0; 4096];
+            while let Ok(len) = stream.read(&mut buf) {
+                request.push_str(&String::from_utf8_lossy(&buf[..len]));
+                let complete = request.split_once("\r\n\r\n").map(|(head, body)| {
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|len| len.trim().parse().unwrap_or(0))
+                        })
+                        .unwrap_or(0);
+                    body.len() >= length
+                });
+                if len == 0 || complete == Some(true) {
+                    break;
+                }
+            }
+            let _ = tx.send(request);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    #[test]
+    fn alerts_are_posted_as_json() {
+        let rt = async_runtime();
+        let policy = WebhookPolicy {
+            schemes: vec!["http".to_owned()],
+            allow_private_addresses: true,
+            ..WebhookPolicy::default()
+        };
+        let (base, requests) = serve("204 No Content");
+        rt.block_on(post(
+            &format!("{}/hook", base),
+            &serde_json::json!({"alert_id": 1}),
+            &policy,
+        ))
+        .unwrap();
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1"));
+        assert!(request
+            .to_lowercase()
+            .contains("content-type: application/json"));
+        assert!(request.ends_with(r#"{"alert_id":1}"#));
+
+        let (base, _) = serve("500 Internal Server Error");
+        assert!(matches!(
+            rt.block_on(post(&base, &(), &policy)),
+            Err(DataError::Webhook(_))
+        ));
+    }
+
+    #[test]
+    fn private_addresses_are_not_posted_to() {
+        let rt = async_runtime();
+        let policy = WebhookPolicy {
+            schemes: vec!["http".to_owned()],
+            ..WebhookPolicy::default()
+        };
+        let (base, _) = serve("200 OK");
+        for url in [base.as_str(), "http://localhost/hook", "http://[::1]/hook"] {
+            assert!(matches!(
+                rt.block_on(post(url, &(), &policy)),
+                Err(DataError::Webhook(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn denied_hosts_are_not_posted_to() {
+        let rt = async_runtime();
+        let policy = WebhookPolicy {
+            schemes: vec!["http".to_owned()],
+            denied_hosts: vec!["localhost".to_owned()],
+            allow_private_addresses: true,
+            ..WebhookPolicy::default()
+        };
+        let (base, requests) = serve("200 OK");
+        let url = base.replace("127.0.0.1", "localhost");
+        match rt.block_on(post(&url, &(), &policy)) {
+            Err(DataError::Webhook(reason)) => assert!(
+                reason.contains("'localhost' is a denied host"),
+                "{}",
+                reason
+            ),
+            res => panic!("posted to a denied host: {:?}", res),
+        }
+        assert!(requests.try_recv().is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! The public job board, which lists the jobs that anyone can view.
+
+use crate::domain::job::field::TaskType;
+use crate::domain::Job;
+use crate::JobError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The number of jobs listed when no limit is given.
+pub const DEFAULT_LIMIT: u32 = 20;
+
+/// The most jobs that can be listed at once.
+pub const MAX_LIMIT: u32 = 100;
+
+/// The order that jobs are listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSort {
+    /// Most recently posted first.
+    Posted,
+    /// Highest task bid first.
+    TaskBid,
+    /// Largest escrow balance first.
+    Balance,
+}
+
+impl JobSort {
+    /// Every order, in the order they are shown.
+    pub const ALL: [JobSort; 3] = [Self::Posted, Self::TaskBid, Self::Balance];
+
+    /// The name of the order, as used in the `sort` query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Posted => "posted",
+            Self::TaskBid => "task_bid",
+            Self::Balance => "balance",
+        }
+    }
+}
+
+/// The Default implementation lists the most recently posted jobs first.
+impl Default for JobSort {
+    fn default() -> Self {
+        Self::Posted
+    }
+}
+
+impl FromStr for JobSort {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|sort| sort.as_str() == s)
+            .copied()
+            .ok_or_else(|| JobError::InvalidSort(s.to_owned()))
+    }
+}
+
+/// The most terms used from a search, so that searches stay cheap.
+pub const MAX_SEARCH_TERMS: usize = 16;
+
+/// A full-text search of the job board.
+///
+/// Searches match jobs whose escrow id, task type, requester description or
+/// manifest contain every term. Terms match the start of words, so part of an
+/// escrow id is enough to find it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SearchQuery(String);
+
+impl SearchQuery {
+    /// Create a new `SearchQuery` from the text a user searched for.
+    ///
+    /// Returns `None` when there is nothing to search for.
+    pub fn new(search: &str) -> Option<Self> {
+        let search = search.trim();
+        if search.chars().any(char::is_alphanumeric) {
+            Some(Self(search.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    /// The terms of the search which can match anything.
+    fn terms(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .split_whitespace()
+            .filter(|term| term.chars().any(char::is_alphanumeric))
+            .take(MAX_SEARCH_TERMS)
+    }
+
+    /// The FTS5 query for the search.
+    ///
+    /// Each term is quoted, so that punctuation in the search is never read as
+    /// query syntax.
+    pub fn to_match(&self) -> String {
+        self.terms()
+            .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The PostgreSQL `tsquery` for the search, in the `simple` configuration.
+    ///
+    /// Terms are quoted in the same way as [`to_match`](Self::to_match).
+    pub fn to_tsquery(&self) -> String {
+        self.terms()
+            .map(|term| format!("'{}':*", term.replace('\\', "\\\\").replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(" & ")
+    }
+
+    /// Return a reference to the underlying [`&str`].
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// A piece of a [`Snippet`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    /// Whether the text matched the search.
+    pub matched: bool,
+}
+
+/// An excerpt of a job's text around the terms it matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Snippet(pub Vec<SnippetPart>);
+
+impl Snippet {
+    /// Marks the start of a match in the text of a snippet from the database.
+    pub const MATCH_START: char = '\u{2}';
+    /// Marks the end of a match in the text of a snippet from the database.
+    pub const MATCH_END: char = '\u{3}';
+
+    /// Split text with matches between [`MATCH_START`](Self::MATCH_START) and
+    /// [`MATCH_END`](Self::MATCH_END) into parts.
+    pub fn from_marked(text: &str) -> Self {
+        let mut parts = vec![];
+        for (i, piece) in text.split(Self::MATCH_START).enumerate() {
+            let (matched, rest) = match piece.split_once(Self::MATCH_END) {
+                Some((matched, rest)) if i > 0 => (matched, rest),
+                _ => ("", piece),
+            };
+            for (text, matched) in [(matched, true), (rest, false)] {
+                if !text.is_empty() {
+                    parts.push(SnippetPart {
+                        text: text.to_owned(),
+                        matched,
+                    });
+                }
+            }
+        }
+        Self(parts)
+    }
+}
+
+/// A job on the job board.
+#[derive(Debug, Clone, Serialize)]
+pub struct ListedJob {
+    #[serde(flatten)]
+    pub job: Job,
+    /// Where the job matched the search, when the board is searched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+}
+
+/// The number of listed jobs with a single [`TaskType`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskTypeFacet {
+    /// The task type, or `None` for jobs which have not been classified.
+    pub task_type: Option<TaskType>,
+    /// The number of jobs with the task type.
+    pub count: u64,
+}
+
+/// A page of jobs from the job board.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobListing {
+    /// The jobs on this page, in the order that was asked for.
+    pub jobs: Vec<ListedJob>,
+    /// The number of jobs with each task type, across the whole board or
+    /// every job which matched the search.
+    ///
+    /// These are not narrowed by the task type filter, so that they can be
+    /// used to switch between filters.
+    pub facets: Vec<TaskTypeFacet>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SearchQuery, Snippet, SnippetPart};
+
+    #[test]
+    fn searches_are_quoted_prefix_queries() {
+        let search = SearchQuery::new(r#" 0x5aAe  bounding-box "cats" "#).unwrap();
+        assert_eq!(
+            search.to_match(),
+            r#""0x5aAe"* "bounding-box"* """cats"""*"#
+        );
+        assert_eq!(
+            SearchQuery::new(r#"cats ( -- )"#).unwrap().to_match(),
+            r#""cats"*"#
+        );
+        assert_eq!(
+            SearchQuery::new(r#"0x5aAe it's a\b ( "#)
+                .unwrap()
+                .to_tsquery(),
+            r#"'0x5aAe':* & 'it''s':* & 'a\\b':*"#
+        );
+        assert!(SearchQuery::new("").is_none());
+        assert!(SearchQuery::new(" \"* ").is_none());
+    }
+
+    #[test]
+    fn snippets_are_split_at_their_matches() {
+        let part = |text: &str, matched| SnippetPart {
+            text: text.to_owned(),
+            matched,
+        };
+        assert_eq!(
+            Snippet::from_marked("Label \u{2}cats\u{3} and \u{2}dogs\u{3}"),
+            Snippet(vec![
+                part("Label ", false),
+                part("cats", true),
+                part(" and ", false),
+                part("dogs", true),
+            ])
+        );
+        assert_eq!(
+            Snippet::from_marked("no matches"),
+            Snippet(vec![part("no matches", false)])
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Claims, which reserve a task slot of a job for a worker.
+
+use crate::data::DbId;
+use crate::domain::job::field::JobId;
+use crate::{ShortCode, Time};
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long a claim lasts when no lease is requested.
+pub const DEFAULT_LEASE_SECS: u32 = 15 * 60;
+
+/// The shortest lease which can be requested.
+pub const MIN_LEASE_SECS: u32 = 60;
+
+/// The longest lease which can be requested. Longer work renews the claim.
+pub const MAX_LEASE_SECS: u32 = 24 * 60 * 60;
+
+/// The possible errors that can occur when claiming a task.
+#[derive(Debug, thiserror::Error)]
+pub enum ClaimError {
+    /// The number of tasks is not known, since the job's manifest has not been read.
+    #[error("the number of tasks of the job is not known yet")]
+    UnknownTotalTasks,
+
+    /// Every task of the job is claimed.
+    #[error("all {0} tasks of the job are claimed")]
+    AllTasksClaimed(u64),
+
+    /// The job has expired.
+    #[error("the job has expired")]
+    JobExpired,
+
+    /// The requested lease is too short or too long.
+    #[error("invalid lease_secs: {0}, must be between 60 and 86400")]
+    InvalidLease(u32),
+}
+
+/// Checks a requested lease, returning the default lease when none is requested.
+pub fn lease(lease_secs: Option<u32>) -> Result<Duration, ClaimError> {
+    match lease_secs.unwrap_or(DEFAULT_LEASE_SECS) {
+        secs @ MIN_LEASE_SECS..=MAX_LEASE_SECS => Ok(Duration::from_secs(u64::from(secs))),
+        secs => Err(ClaimError::InvalidLease(secs)),
+    }
+}
+
+/// A task slot of a job which is reserved by an API key until the lease expires.
+///
+/// A job can have as many claims as it has tasks. Expired claims no longer
+/// count, so their slots can be claimed again.
+#[derive(Debug, Clone, Serialize)]
+pub struct Claim {
+    pub claim_id: DbId,
+    pub job_id: JobId,
+    pub shortcode: ShortCode,
+    pub created: Time,
+    /// When the lease runs out, unless the claim is renewed.
+    pub expires: Time,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn leases_are_checked() {
+        assert_eq!(lease(None).unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(lease(Some(60)).unwrap(), Duration::from_secs(60));
+        assert_eq!(
+            lease(Some(MAX_LEASE_SECS)).unwrap(),
+            Duration::from_secs(86400)
+        );
+        for secs in [0, 59, MAX_LEASE_SECS + 1] {
+            assert!(lease(Some(secs)).is_err(), "accepted {}", secs);
+        }
+    }
+}
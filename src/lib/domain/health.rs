@@ -0,0 +1,103 @@
+//! Service health and ingestion status.
+
+use crate::domain::time::Time;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Whether the service is able to handle requests.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    /// Whether the database answered a query.
+    pub database: bool,
+    /// Versions of the migrations which have not been applied to the database.
+    pub pending_migrations: Vec<i64>,
+}
+
+impl Readiness {
+    /// Returns whether the database is reachable and fully migrated.
+    pub fn is_ready(&self) -> bool {
+        self.database && self.pending_migrations.is_empty()
+    }
+}
+
+/// The number of [`Jobs`](crate::Job) stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobCounts {
+    /// All stored jobs.
+    pub total: u64,
+    /// Jobs which have expired but have not been deleted yet.
+    pub expired: u64,
+}
+
+/// How up to date the jobs from a single source are.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceStatus {
+    /// The name of the source.
+    pub name: &'static str,
+    /// When jobs were last fetched from the source without error.
+    pub last_success: Option<Time>,
+    /// The error from the most recent failed fetch.
+    pub last_error: Option<String>,
+    /// The number of failed fetches since the last success.
+    pub consecutive_failures: u32,
+    /// When the most recent job from the source was posted.
+    pub latest_job_posted: Option<Time>,
+    /// Seconds since the most recent job from the source was posted.
+    pub lag_seconds: Option<i64>,
+    /// The latest block indexed by the source, when it was last queried.
+    pub indexed_block: Option<i64>,
+    /// The block indexed by the source when jobs were last fetched from it without error.
+    pub synced_block: Option<i64>,
+    /// The number of blocks indexed by the source which have not been synced.
+    pub lag_blocks: Option<i64>,
+}
+
+/// The blocks seen by the sync of a job source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockProgress {
+    /// The latest block indexed by the source, when it was last queried.
+    pub indexed: Option<i64>,
+    /// The block indexed by the source when jobs were last fetched from it without error.
+    pub synced: Option<i64>,
+}
+
+impl BlockProgress {
+    /// The number of blocks indexed by the source which have not been synced.
+    pub fn lag(&self) -> Option<i64> {
+        Some(self.indexed? - self.synced?)
+    }
+}
+
+/// The [`BlockProgress`] of a sync, shared between the sync task and the status endpoint.
+#[derive(Debug, Default)]
+pub struct SyncProgress(Mutex<BlockProgress>);
+
+impl SyncProgress {
+    /// Record the latest block indexed by the source.
+    pub fn indexed(&self, block: i64) {
+        self.0.lock().indexed = Some(block);
+    }
+
+    /// Record that every job up to `block` has been synced.
+    pub fn synced(&self, block: i64) {
+        self.0.lock().synced = Some(block);
+    }
+
+    /// The blocks seen so far.
+    pub fn get(&self) -> BlockProgress {
+        *self.0.lock()
+    }
+}
+
+/// A summary of the state of the service.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    /// The sync state of each job source.
+    pub sources: Vec<SourceStatus>,
+    /// Whether the response counter is still accepting responses.
+    pub response_counter_running: bool,
+    /// The number of response entries waiting to be committed.
+    pub pending_responses: usize,
+    /// The number of jobs stored.
+    pub jobs: JobCounts,
+}
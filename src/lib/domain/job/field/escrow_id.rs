@@ -1,22 +1,73 @@
 use crate::domain::job::JobError;
 use rocket::form::{self, FromFormField, ValueField};
-use serde::{Deserialize, Serialize};
+use rocket::request::FromParam;
+use serde::{Deserialize, Deserializer, Serialize};
+use sha3::{Digest, Keccak256};
+use std::str::FromStr;
 
 /// The escrow_id field for a [`Job`](crate::domain::job::Job).
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// Escrow ids are the address of the escrow contract, in
+/// [EIP-55](https://eips.ethereum.org/EIPS/eip-55) checksum form. Jobs posted
+/// before addresses were validated may have any other escrow id, which is only
+/// accepted by [`EscrowId::legacy`].
+#[derive(Clone, Debug, Serialize)]
 pub struct EscrowId(String);
 
 impl EscrowId {
-    /// Create a new `EscrowId` field.
+    /// Create a new `EscrowId` field from an escrow address.
     ///
-    /// If the escrow_id provided is empty, then a [`JobError`] will be returned.
+    /// The address must be `0x` followed by 40 hex digits. Addresses in mixed
+    /// case must have a valid checksum, and every address is converted to
+    /// checksum form. Otherwise a [`JobError`] will be returned.
     pub fn new(escrow_id: &str) -> Result<Self, JobError> {
-        if !escrow_id.trim().is_empty() {
-            Ok(Self(escrow_id.to_owned()))
+        let escrow_id = escrow_id.trim();
+        if escrow_id.is_empty() {
+            return Err(JobError::EmptyEscrowId);
+        }
+        let hex = match escrow_id
+            .strip_prefix("0x")
+            .or_else(|| escrow_id.strip_prefix("0X"))
+        {
+            Some(hex) if hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()) => hex,
+            _ => return Err(JobError::InvalidEscrowAddress(escrow_id.to_owned())),
+        };
+        let checksummed = checksum(hex);
+        let is_mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+            && hex.chars().any(|c| c.is_ascii_uppercase());
+        if is_mixed_case && hex != &checksummed[2..] {
+            Err(JobError::InvalidEscrowChecksum(escrow_id.to_owned()))
         } else {
-            Err(JobError::EmptyEscrowId)
+            Ok(Self(checksummed))
+        }
+    }
+
+    /// Create a new `EscrowId` field for a job which was posted before addresses were validated.
+    ///
+    /// Addresses are converted to checksum form, as with [`EscrowId::new`], and
+    /// anything else is kept as it is. Only empty escrow ids are rejected.
+    pub fn legacy(escrow_id: &str) -> Result<Self, JobError> {
+        match Self::new(escrow_id) {
+            Ok(escrow_id) => Ok(escrow_id),
+            Err(JobError::EmptyEscrowId) => Err(JobError::EmptyEscrowId),
+            Err(_) => Ok(Self(escrow_id.to_owned())),
         }
     }
+
+    /// Returns whether the escrow_id is an escrow address, rather than a legacy escrow id.
+    pub fn is_address(&self) -> bool {
+        Self::new(&self.0).is_ok()
+    }
+
+    /// Return the escrow_id as it is stored and looked up, which is in lower case for addresses.
+    pub fn to_lowercase(&self) -> String {
+        if self.is_address() {
+            self.0.to_ascii_lowercase()
+        } else {
+            self.0.clone()
+        }
+    }
+
     /// Return the underlying [`String`].
     pub fn into_inner(self) -> String {
         self.0
@@ -27,6 +78,41 @@ impl EscrowId {
     }
 }
 
+/// Returns the EIP-55 checksum form of an address, given its 40 hex digits.
+///
+/// Each letter is upper case when the matching nibble of the Keccak-256 hash
+/// of the lower case address is 8 or more.
+fn checksum(hex: &str) -> String {
+    let hex = hex.to_ascii_lowercase();
+    let hash = Keccak256::digest(hex.as_bytes());
+    let mut checksummed = String::with_capacity(42);
+    checksummed.push_str("0x");
+    for (i, c) in hex.chars().enumerate() {
+        let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+        checksummed.push(if nibble >= 8 {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        });
+    }
+    checksummed
+}
+
+impl FromStr for EscrowId {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+/// Only escrow addresses are accepted, as with [`EscrowId::new`].
+impl<'de> Deserialize<'de> for EscrowId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let escrow_id = String::deserialize(deserializer)?;
+        Self::new(&escrow_id).map_err(serde::de::Error::custom)
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromFormField<'r> for EscrowId {
     fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
@@ -34,12 +120,196 @@ impl<'r> FromFormField<'r> for EscrowId {
     }
 }
 
+impl<'r> FromParam<'r> for EscrowId {
+    type Error = JobError;
+
+    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+        Self::new(param)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::EscrowId;
+    use crate::domain::job::JobError;
+    use rand::prelude::*;
+
+    /// The examples from EIP-55.
+    const CHECKSUMMED: &[&str] = &[
+        "0x52908400098527886E0F7030069857D2E4169EE7",
+        "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+        "0xde709f2102306220921060314715629080e2fb77",
+        "0x27b1fdb04752bbc536007a920d24acb045561c26",
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    /// The number of random cases each property is checked against.
+    const CASES: usize = 256;
+
+    /// Set to the seed printed by a failed property to check the same cases again.
+    const TEST_SEED: &str = "GPTX_TEST_SEED";
+
+    /// A random generator, with the seed it was created from for failure messages.
+    fn seeded_rng() -> (u64, StdRng) {
+        let seed = std::env::var(TEST_SEED)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| thread_rng().gen());
+        (seed, StdRng::seed_from_u64(seed))
+    }
+
+    /// Any `char` half of the time, and otherwise a character found in addresses.
+    fn arbitrary_char(rng: &mut impl Rng) -> char {
+        if rng.gen() {
+            rng.gen()
+        } else {
+            *b"0123456789abcdefABCDEFxX".choose(rng).unwrap() as char
+        }
+    }
+
+    fn random_address(rng: &mut impl Rng) -> String {
+        let digits = b"0123456789abcdef";
+        let hex: String = (0..40)
+            .map(|_| *digits.choose(rng).unwrap() as char)
+            .collect();
+        format!("0x{}", hex)
+    }
 
     #[test]
     fn disallow_empty_escrow_id() {
         assert!(EscrowId::new("").is_err());
+        assert!(EscrowId::legacy(" ").is_err());
+    }
+
+    #[test]
+    fn eip55_examples_are_accepted_unchanged() {
+        for address in CHECKSUMMED {
+            assert_eq!(EscrowId::new(address).unwrap().as_str(), *address);
+            let lowercase = address.to_lowercase();
+            assert_eq!(EscrowId::new(&lowercase).unwrap().as_str(), *address);
+            assert_eq!(EscrowId::new(address).unwrap().to_lowercase(), lowercase);
+        }
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        for escrow_id in [
+            "escrow_id",
+            "0x1234",
+            "5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAedaa",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeg",
+        ] {
+            assert!(matches!(
+                EscrowId::new(escrow_id),
+                Err(JobError::InvalidEscrowAddress(_))
+            ));
+        }
+        assert!(matches!(
+            EscrowId::new("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            Err(JobError::InvalidEscrowChecksum(_))
+        ));
+    }
+
+    #[test]
+    fn legacy_escrow_ids_are_kept() {
+        let legacy = EscrowId::legacy("escrow_id for job '1'").unwrap();
+        assert_eq!(legacy.as_str(), "escrow_id for job '1'");
+        assert!(!legacy.is_address());
+        assert_eq!(
+            EscrowId::legacy(&CHECKSUMMED[4].to_lowercase())
+                .unwrap()
+                .as_str(),
+            CHECKSUMMED[4]
+        );
+    }
+
+    #[test]
+    fn any_case_of_an_address_normalizes_to_the_same_checksum() {
+        let (seed, mut rng) = seeded_rng();
+        for _ in 0..CASES {
+            let address = random_address(&mut rng);
+            let checksummed = EscrowId::new(&address).unwrap();
+            assert_eq!(checksummed.to_lowercase(), address, "seed {}", seed);
+            assert_eq!(
+                EscrowId::new(&address.to_uppercase().replacen("0X", "0x", 1))
+                    .unwrap()
+                    .as_str(),
+                checksummed.as_str(),
+                "seed {}",
+                seed
+            );
+            assert_eq!(
+                EscrowId::new(checksummed.as_str()).unwrap().as_str(),
+                checksummed.as_str(),
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn changing_the_case_of_a_checksummed_letter_is_rejected() {
+        let (seed, mut rng) = seeded_rng();
+        for _ in 0..CASES {
+            let checksummed = EscrowId::new(&random_address(&mut rng)).unwrap();
+            let letters: Vec<usize> = checksummed
+                .as_str()
+                .char_indices()
+                .skip(2)
+                .filter(|(_, c)| c.is_ascii_alphabetic())
+                .map(|(i, _)| i)
+                .collect();
+            let is_mixed_case = |address: &str| {
+                address[2..].chars().any(|c| c.is_ascii_lowercase())
+                    && address[2..].chars().any(|c| c.is_ascii_uppercase())
+            };
+            if let Some(&i) = letters.choose(&mut rng) {
+                let mut tampered: Vec<char> = checksummed.as_str().chars().collect();
+                tampered[i] = if tampered[i].is_ascii_uppercase() {
+                    tampered[i].to_ascii_lowercase()
+                } else {
+                    tampered[i].to_ascii_uppercase()
+                };
+                let tampered: String = tampered.into_iter().collect();
+                if is_mixed_case(&tampered) {
+                    assert!(
+                        matches!(
+                            EscrowId::new(&tampered),
+                            Err(JobError::InvalidEscrowChecksum(_))
+                        ),
+                        "seed {}: {}",
+                        seed,
+                        tampered
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arbitrary_input_never_panics() {
+        let (seed, mut rng) = seeded_rng();
+        for _ in 0..CASES {
+            let len = rng.gen_range(0..64);
+            let input: String = (0..len).map(|_| arbitrary_char(&mut rng)).collect();
+            let prefixed = format!("0x{}", input);
+            for input in [input, prefixed] {
+                if let Ok(escrow_id) = EscrowId::new(&input) {
+                    assert_eq!(escrow_id.as_str().len(), 42, "seed {}: {:?}", seed, input);
+                    assert!(escrow_id.is_address(), "seed {}: {:?}", seed, input);
+                }
+                assert_eq!(
+                    EscrowId::legacy(&input).is_ok(),
+                    !input.trim().is_empty(),
+                    "seed {}: {:?}",
+                    seed,
+                    input
+                );
+            }
+        }
     }
 }
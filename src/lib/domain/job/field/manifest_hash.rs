@@ -0,0 +1,113 @@
+use crate::domain::job::JobError;
+use serde::{Deserialize, Deserializer, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// The hash functions which manifest hashes can be made with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-1, which the HUMAN escrow libraries record.
+    Sha1,
+    /// SHA-256.
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Hash `data`, returning the digest as lower case hex.
+    pub fn digest(&self, data: &[u8]) -> String {
+        let digest = match self {
+            Self::Sha1 => Sha1::digest(data).to_vec(),
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// The hash of a manifest, as recorded by its escrow.
+///
+/// The algorithm is identified by the length of the hash: 40 hex digits are
+/// SHA-1 and 64 hex digits are SHA-256.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestHash(String);
+
+impl ManifestHash {
+    /// Create a new `ManifestHash` from hex digits, optionally prefixed with `0x`.
+    ///
+    /// The hash is stored in lower case.
+    pub fn new(hash: &str) -> Result<Self, JobError> {
+        let hash = hash.trim();
+        let hex = hash.strip_prefix("0x").unwrap_or(hash);
+        if matches!(hex.len(), 40 | 64) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(hex.to_ascii_lowercase()))
+        } else {
+            Err(JobError::InvalidManifestHash(hash.to_owned()))
+        }
+    }
+
+    /// The hash function which made this hash.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        if self.0.len() == 40 {
+            HashAlgorithm::Sha1
+        } else {
+            HashAlgorithm::Sha256
+        }
+    }
+
+    /// Returns whether `manifest` has this hash.
+    pub fn matches(&self, manifest: &[u8]) -> bool {
+        self.algorithm().digest(manifest) == self.0
+    }
+
+    /// Return a reference to the underlying [`&str`].
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Return the underlying [`String`].
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl FromStr for ManifestHash {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for ManifestHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hash = String::deserialize(deserializer)?;
+        Self::new(&hash).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HashAlgorithm, ManifestHash};
+
+    #[test]
+    fn hashes_are_checked_with_their_algorithm() {
+        let sha1 = ManifestHash::new("A9993E364706816ABA3E25717850C26C9CD0D89D").unwrap();
+        assert_eq!(sha1.algorithm(), HashAlgorithm::Sha1);
+        assert_eq!(sha1.as_str(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert!(sha1.matches(b"abc"));
+        assert!(!sha1.matches(b"abd"));
+
+        let sha256 =
+            ManifestHash::new("0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                .unwrap();
+        assert_eq!(sha256.algorithm(), HashAlgorithm::Sha256);
+        assert!(sha256.matches(b"abc"));
+    }
+
+    #[test]
+    fn malformed_hashes_are_rejected() {
+        for hash in ["", "abc", "0x1234", &"g".repeat(40), &"a".repeat(41)] {
+            assert!(ManifestHash::new(hash).is_err(), "accepted '{}'", hash);
+        }
+    }
+}
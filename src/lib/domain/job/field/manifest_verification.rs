@@ -0,0 +1,136 @@
+use super::{JobId, ManifestHash, SearchText, TaskType, TokenAmount};
+use crate::domain::job::JobError;
+use crate::Time;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Whether a job's manifest matches the hash recorded by its escrow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestStatus {
+    /// The manifest has not been checked yet.
+    Pending,
+    /// The manifest matches the recorded hash.
+    Verified,
+    /// The manifest does not match the recorded hash.
+    Flagged,
+    /// The manifest could not be fetched, and will be checked again later.
+    Unavailable,
+    /// There is no manifest_url or no recorded hash, so there is nothing to check.
+    Unverifiable,
+}
+
+impl ManifestStatus {
+    /// The status of a job before its manifest has been checked.
+    pub fn initial(has_manifest_url: bool, has_hash: bool) -> Self {
+        if has_manifest_url && has_hash {
+            Self::Pending
+        } else {
+            Self::Unverifiable
+        }
+    }
+
+    /// The name of the status, as it is stored.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Verified => "verified",
+            Self::Flagged => "flagged",
+            Self::Unavailable => "unavailable",
+            Self::Unverifiable => "unverifiable",
+        }
+    }
+}
+
+impl FromStr for ManifestStatus {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "verified" => Ok(Self::Verified),
+            "flagged" => Ok(Self::Flagged),
+            "unavailable" => Ok(Self::Unavailable),
+            "unverifiable" => Ok(Self::Unverifiable),
+            other => Err(JobError::InvalidManifestStatus(other.to_owned())),
+        }
+    }
+}
+
+/// The manifest verification field for a [`Job`](crate::domain::job::Job).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManifestVerification {
+    /// The manifest hash recorded by the escrow, if there is one.
+    pub hash: Option<ManifestHash>,
+    /// The result of the last check.
+    pub status: ManifestStatus,
+    /// When the manifest was last checked.
+    pub checked: Option<Time>,
+}
+
+/// The Default implementation is an unverifiable manifest, without a recorded hash.
+impl Default for ManifestVerification {
+    fn default() -> Self {
+        Self {
+            hash: None,
+            status: ManifestStatus::Unverifiable,
+            checked: None,
+        }
+    }
+}
+
+/// A job whose manifest is waiting to be verified or classified.
+#[derive(Debug, Clone)]
+pub struct PendingManifest {
+    /// The job which the manifest belongs to.
+    pub job_id: JobId,
+    /// Where the manifest is fetched from.
+    pub manifest_url: String,
+    /// The hash recorded by the escrow. Manifests without one are only classified.
+    pub hash: Option<ManifestHash>,
+    /// The number of decimal places of the escrow's token, for reading the task bid.
+    pub decimals: u8,
+}
+
+/// The result of fetching a job's manifest.
+#[derive(Debug, Clone)]
+pub struct ManifestCheck {
+    /// The job which the manifest belongs to.
+    pub job_id: JobId,
+    /// Whether the manifest matched the hash recorded by the escrow.
+    pub status: ManifestStatus,
+    /// The task type of the manifest. `None` keeps the job's current task type.
+    pub task_type: Option<TaskType>,
+    /// The amount paid for each task. `None` keeps the job's current task bid.
+    pub task_bid: Option<TokenAmount>,
+    /// The number of tasks. `None` keeps the job's current number of tasks.
+    pub total_tasks: Option<u64>,
+    /// The searchable text of the manifest. `None` keeps the job's current text.
+    pub search_text: Option<SearchText>,
+    /// When the manifest was fetched.
+    pub checked: Time,
+}
+
+#[cfg(test)]
+mod test {
+    use super::ManifestStatus;
+    use std::str::FromStr;
+
+    #[test]
+    fn statuses_round_trip_through_their_names() {
+        for status in [
+            ManifestStatus::Pending,
+            ManifestStatus::Verified,
+            ManifestStatus::Flagged,
+            ManifestStatus::Unavailable,
+            ManifestStatus::Unverifiable,
+        ] {
+            assert_eq!(ManifestStatus::from_str(status.as_str()).unwrap(), status);
+        }
+        assert!(ManifestStatus::from_str("unknown").is_err());
+        assert_eq!(ManifestStatus::initial(true, true), ManifestStatus::Pending);
+        assert_eq!(
+            ManifestStatus::initial(false, true),
+            ManifestStatus::Unverifiable
+        );
+    }
+}
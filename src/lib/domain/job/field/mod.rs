@@ -4,13 +4,37 @@ mod job_id;
 pub use job_id::JobId;
 
 mod shortcode;
-pub use shortcode::ShortCode;
+pub use shortcode::{Alphabet, ShortCode, ShortCodeConfig};
+
+mod vanity_code;
+pub use vanity_code::VanityCode;
 
 mod escrow_id;
 pub use escrow_id::EscrowId;
 
 mod manifest_url;
-pub use manifest_url::ManifestUrl;
+pub(crate) use manifest_url::{check_address, check_host};
+pub use manifest_url::{ManifestPolicy, ManifestUrl};
+
+mod manifest_hash;
+pub use manifest_hash::{HashAlgorithm, ManifestHash};
+
+mod manifest_verification;
+pub use manifest_verification::{
+    ManifestCheck, ManifestStatus, ManifestVerification, PendingManifest,
+};
+
+mod task_type;
+pub use task_type::TaskType;
+
+mod token_amount;
+pub use token_amount::TokenAmount;
+
+mod reward;
+pub use reward::{ManifestTerms, Reward, Token, TokenList, DEFAULT_DECIMALS};
+
+mod search_text;
+pub use search_text::{SearchText, MAX_SEARCH_TEXT};
 
 mod posted;
 pub use posted::Posted;
@@ -4,9 +4,19 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 /// The password field for a [`Job`](crate::domain::job::Job).
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
 pub struct Password(Option<String>);
 
+/// The password itself is never included in debug output, so that it cannot end up in logs.
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Password(<redacted>)"),
+            None => f.write_str("Password(None)"),
+        }
+    }
+}
+
 impl Password {
     /// Create a new `Password` field.
     ///
@@ -77,4 +87,10 @@ mod test {
     fn accepts_valid_password() {
         assert!(Password::new("123".to_owned()).unwrap().has_password());
     }
+
+    #[test]
+    fn debug_output_is_redacted() {
+        let password = Password::new("hunter2".to_owned()).unwrap();
+        assert!(!format!("{:?}", password).contains("hunter2"));
+    }
 }
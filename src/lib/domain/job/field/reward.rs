@@ -0,0 +1,258 @@
+use super::TokenAmount;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+/// The number of decimal places assumed for tokens which are not configured,
+/// which is the ERC-20 default.
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+/// An ERC-20 token which escrows are funded with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Token {
+    /// The address of the token contract.
+    pub address: String,
+    /// The symbol shown next to amounts of the token.
+    pub symbol: String,
+    /// The number of decimal places of the token.
+    pub decimals: u8,
+}
+
+impl Token {
+    /// The HUMAN Protocol Token on Ethereum and Polygon.
+    pub fn known() -> Vec<Token> {
+        [
+            "0xd1ba9BAC957322D6e8c07a160a3A8dA11A0d2867",
+            "0xc748B2A084F8eFc47E086ccdDD9b7e67aEb571BF",
+        ]
+        .iter()
+        .map(|address| Token {
+            address: (*address).to_owned(),
+            symbol: "HMT".to_owned(),
+            decimals: 18,
+        })
+        .collect()
+    }
+}
+
+/// The tokens which rewards are recognised in.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct TokenList(pub Vec<Token>);
+
+/// The Default implementation is the [`known`](Token::known) tokens.
+impl Default for TokenList {
+    fn default() -> Self {
+        Self(Token::known())
+    }
+}
+
+impl TokenList {
+    /// Find the token with the address, regardless of case.
+    pub fn find(&self, address: &str) -> Option<&Token> {
+        self.0
+            .iter()
+            .find(|token| token.address.eq_ignore_ascii_case(address))
+    }
+}
+
+/// The reward field for a [`Job`](crate::domain::job::Job).
+///
+/// Amounts are in the smallest unit of the token, and are formatted with the
+/// token's `decimals` for display.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Reward {
+    /// The address of the token which the escrow is funded with.
+    pub token: Option<String>,
+    /// The symbol of the token, if it is a configured token.
+    pub symbol: Option<String>,
+    /// The number of decimal places of the token.
+    pub decimals: u8,
+    /// The amount of the token held by the escrow.
+    pub balance: Option<TokenAmount>,
+    /// The amount paid for each task, from the manifest.
+    pub task_bid: Option<TokenAmount>,
+    /// The number of tasks in the job, from the manifest.
+    pub total_tasks: Option<u64>,
+}
+
+/// The Default implementation is a job without any reward information.
+impl Default for Reward {
+    fn default() -> Self {
+        Self {
+            token: None,
+            symbol: None,
+            decimals: DEFAULT_DECIMALS,
+            balance: None,
+            task_bid: None,
+            total_tasks: None,
+        }
+    }
+}
+
+impl Reward {
+    /// Formats an amount of this reward's token, such as `0.05 HMT`.
+    pub fn format(&self, amount: &TokenAmount) -> String {
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", amount.format(self.decimals), symbol),
+            None => amount.format(self.decimals),
+        }
+    }
+}
+
+/// The fields of a HUMAN manifest which hold its reward terms.
+///
+/// The price is kept as raw JSON, so that numbers are not rounded to an `f64`.
+#[derive(Deserialize)]
+struct RawTerms<'a> {
+    #[serde(borrow)]
+    task_bid_price: Option<&'a RawValue>,
+    job_total_tasks: Option<serde_json::Value>,
+}
+
+/// The most digits a JSON number's exponent can shift its decimal point by,
+/// so that huge exponents are not expanded into huge strings.
+const MAX_EXPONENT: u32 = 100;
+
+/// Writes a JSON number without an exponent, so `1.5e-5` becomes `0.000015`.
+///
+/// Returns `None` for negative numbers, and for exponents above [`MAX_EXPONENT`].
+fn plain_decimal(number: &str) -> Option<String> {
+    let (mantissa, exponent) = match number.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => return Some(number.to_owned()),
+    };
+    if mantissa.starts_with('-') || exponent.unsigned_abs() > MAX_EXPONENT {
+        return None;
+    }
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    let point = whole.len() as i32 + exponent;
+    Some(if point <= 0 {
+        format!("0.{}{}", "0".repeat(point.unsigned_abs() as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}", digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    })
+}
+
+/// The reward terms of a HUMAN manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestTerms {
+    /// The `task_bid_price`, in whole tokens.
+    pub task_bid_price: Option<String>,
+    /// The `job_total_tasks`.
+    pub total_tasks: Option<u64>,
+}
+
+impl ManifestTerms {
+    /// Read the reward terms from a manifest.
+    ///
+    /// Prices may be JSON strings or numbers, and anything else is left out.
+    /// Numbers are read exactly as written, including in exponent form.
+    pub fn from_manifest(manifest: &[u8]) -> Self {
+        let manifest: RawTerms = match serde_json::from_slice(manifest) {
+            Ok(manifest) => manifest,
+            Err(_) => return Self::default(),
+        };
+        let task_bid_price = manifest
+            .task_bid_price
+            .and_then(|price| match serde_json::from_str(price.get()).ok()? {
+                serde_json::Value::String(price) => Some(price.trim().to_owned()),
+                serde_json::Value::Number(_) => plain_decimal(price.get()),
+                _ => None,
+            });
+        let total_tasks = manifest.job_total_tasks.and_then(|total| {
+            total
+                .as_u64()
+                .or_else(|| total.as_str().and_then(|total| total.trim().parse().ok()))
+        });
+        Self {
+            task_bid_price,
+            total_tasks,
+        }
+    }
+
+    /// The task bid in the smallest unit of a token with `decimals` decimal places,
+    /// if the price is a valid amount of the token.
+    pub fn task_bid(&self, decimals: u8) -> Option<TokenAmount> {
+        self.task_bid_price
+            .as_deref()
+            .and_then(|price| TokenAmount::from_decimal(price, decimals).ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ManifestTerms, Reward, Token, TokenAmount, TokenList};
+
+    #[test]
+    fn manifest_terms_are_read_from_strings_or_numbers() {
+        let terms =
+            ManifestTerms::from_manifest(br#"{"task_bid_price": "0.05", "job_total_tasks": 200}"#);
+        assert_eq!(terms.total_tasks, Some(200));
+        assert_eq!(terms.task_bid(18).unwrap().as_str(), "50000000000000000");
+        let terms =
+            ManifestTerms::from_manifest(br#"{"task_bid_price": 1.5, "job_total_tasks": "3"}"#);
+        assert_eq!(terms.task_bid(2).unwrap().as_str(), "150");
+        assert_eq!(terms.total_tasks, Some(3));
+        let terms = ManifestTerms::from_manifest(
+            br#"{"task_bid_price": 0.123456789012345678, "job_total_tasks": 1}"#,
+        );
+        assert_eq!(
+            terms.task_bid_price.as_deref(),
+            Some("0.123456789012345678")
+        );
+        assert_eq!(terms.task_bid(18).unwrap().as_str(), "123456789012345678");
+        let terms = ManifestTerms::from_manifest(br#"{"task_bid_price": 1e-5}"#);
+        assert_eq!(terms.task_bid(18).unwrap().as_str(), "10000000000000");
+        let terms = ManifestTerms::from_manifest(br#"{"task_bid_price": 2.5E+1}"#);
+        assert_eq!(terms.task_bid(0).unwrap().as_str(), "25");
+        let terms = ManifestTerms::from_manifest(br#"{"task_bid_price": 1.25e-20}"#);
+        assert!(terms.task_bid(18).is_none());
+        let terms = ManifestTerms::from_manifest(br#"{"task_bid_price": -1}"#);
+        assert!(terms.task_bid(18).is_none());
+        let terms = ManifestTerms::from_manifest(br#"{"task_bid_price": "free"}"#);
+        assert!(terms.task_bid(18).is_none());
+        assert_eq!(
+            ManifestTerms::from_manifest(b"not json"),
+            ManifestTerms::default()
+        );
+    }
+
+    #[test]
+    fn tokens_are_found_in_the_list_regardless_of_case() {
+        let usdc = Token {
+            address: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_owned(),
+            symbol: "USDC".to_owned(),
+            decimals: 6,
+        };
+        let tokens = TokenList(vec![usdc.clone()]);
+        assert_eq!(
+            tokens.find("0x2791bca1f2de4661ed88a30c99a7a9449aa84174"),
+            Some(&usdc)
+        );
+        assert!(tokens
+            .find("0xD1BA9BAC957322D6E8C07A160A3A8DA11A0D2867")
+            .is_none());
+    }
+
+    #[test]
+    fn amounts_are_formatted_with_the_token() {
+        let tokens = TokenList::default();
+        let hmt = tokens
+            .find("0xD1BA9BAC957322D6E8C07A160A3A8DA11A0D2867")
+            .cloned()
+            .unwrap();
+        let reward = Reward {
+            token: Some(hmt.address),
+            symbol: Some(hmt.symbol),
+            decimals: hmt.decimals,
+            ..Reward::default()
+        };
+        let amount = TokenAmount::from_base_units("1250000000000000000").unwrap();
+        assert_eq!(reward.format(&amount), "1.25 HMT");
+        assert_eq!(Reward::default().format(&amount), "1.25");
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+/// The most bytes of a manifest's text which are kept for searching.
+pub const MAX_SEARCH_TEXT: usize = 16 * 1024;
+
+/// The text of a manifest which the job board is searched by.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SearchText {
+    /// The manifest's `requester_description`.
+    pub description: Option<String>,
+    /// Every other string in the manifest, one per line, leaving out links.
+    pub manifest: String,
+}
+
+impl SearchText {
+    /// Read the searchable text of a manifest.
+    ///
+    /// Manifests which are not JSON are searched as plain text, if they are UTF-8.
+    pub fn from_manifest(manifest: &[u8]) -> Self {
+        let json: serde_json::Value = match serde_json::from_slice(manifest) {
+            Ok(json) => json,
+            Err(_) => {
+                return Self {
+                    description: None,
+                    manifest: truncate(std::str::from_utf8(manifest).unwrap_or_default()),
+                }
+            }
+        };
+        let description = json
+            .get("requester_description")
+            .and_then(|description| description.as_str())
+            .map(|description| truncate(description.trim()))
+            .filter(|description| !description.is_empty());
+        let mut lines = vec![];
+        collect_strings(&json, &mut lines);
+        Self {
+            description,
+            manifest: truncate(&lines.join("\n")),
+        }
+    }
+}
+
+/// Adds every string in `value` to `lines`, except the requester description and links.
+fn collect_strings<'a>(value: &'a serde_json::Value, lines: &mut Vec<&'a str>) {
+    match value {
+        serde_json::Value::String(text) => {
+            let text = text.trim();
+            if !text.is_empty() && !text.contains("://") {
+                lines.push(text);
+            }
+        }
+        serde_json::Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_strings(value, lines)),
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .filter(|(key, _)| key.as_str() != "requester_description")
+            .for_each(|(_, value)| collect_strings(value, lines)),
+        _ => (),
+    }
+}
+
+/// Shortens `text` to at most [`MAX_SEARCH_TEXT`] bytes, without splitting a character.
+fn truncate(text: &str) -> String {
+    let mut end = text.len().min(MAX_SEARCH_TEXT);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SearchText, MAX_SEARCH_TEXT};
+
+    #[test]
+    fn manifest_strings_are_searchable() {
+        let text = SearchText::from_manifest(
+            br#"{
+                "requester_description": " Is there a cat? ",
+                "request_type": "image_label_binary",
+                "requester_restricted_answer_set": {"cat": {"en": "Cat"}},
+                "taskdata_uri": "https://example.com/tasks.json",
+                "job_total_tasks": 10
+            }"#,
+        );
+        assert_eq!(text.description.as_deref(), Some("Is there a cat?"));
+        assert_eq!(text.manifest, "image_label_binary\nCat");
+
+        let text = SearchText::from_manifest("plain ünïcode".repeat(2000).as_bytes());
+        assert!(text.description.is_none());
+        assert!(text.manifest.len() <= MAX_SEARCH_TEXT);
+        assert!(text.manifest.starts_with("plain ünïcode"));
+        assert_eq!(
+            SearchText::from_manifest(&[0xff, 0xfe]),
+            SearchText::default()
+        );
+    }
+}
@@ -2,35 +2,107 @@ use crate::domain::job::JobError;
 use derive_more::From;
 use rocket::request::FromParam;
 use rocket::{UriDisplayPath, UriDisplayQuery};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::str::FromStr;
 
+/// Paths which are routes of their own, so can not be used as custom shortcodes.
+const RESERVED: &[&str] = &["key", "stats"];
+
+/// The characters that generated shortcodes are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Alphabet {
+    /// Bitcoin's base58, which leaves out `0`, `O`, `I` and `l`.
+    Base58,
+    /// Crockford's base32 in lower case, which leaves out `i`, `l`, `o` and `u`.
+    Crockford,
+}
+
+impl Alphabet {
+    /// The characters in the alphabet.
+    pub fn chars(&self) -> &'static [u8] {
+        match self {
+            Self::Base58 => b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+            Self::Crockford => b"0123456789abcdefghjkmnpqrstvwxyz",
+        }
+    }
+
+    /// The number of bits of randomness in each character.
+    pub fn bits_per_char(&self) -> f64 {
+        (self.chars().len() as f64).log2()
+    }
+}
+
+/// Settings for generating new shortcodes.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShortCodeConfig {
+    /// The characters that shortcodes are drawn from.
+    pub alphabet: Alphabet,
+    /// The number of characters in a shortcode.
+    pub length: usize,
+}
+
+/// Ten Crockford base32 characters, which is 50 bits.
+impl Default for ShortCodeConfig {
+    fn default() -> Self {
+        Self {
+            alphabet: Alphabet::Crockford,
+            length: 10,
+        }
+    }
+}
+
 /// The shortcode field for a [`Job`](crate::domain::job::Job).
 ///
 /// The shortcode is utilized by clients to locate the `Job` within the service.
-#[derive(
-    Debug, Clone, Deserialize, Serialize, From, UriDisplayQuery, UriDisplayPath, Hash, Eq, PartialEq,
-)]
+#[derive(Debug, Clone, Serialize, From, UriDisplayQuery, UriDisplayPath, Hash, Eq, PartialEq)]
 pub struct ShortCode(String);
 
 impl ShortCode {
-    /// Create a new `ShortCode` field.
-    pub fn new() -> Self {
+    /// The longest shortcode which is accepted.
+    pub const MAX_LENGTH: usize = 32;
+
+    /// The shortest custom shortcode which is accepted.
+    pub const MIN_VANITY_LENGTH: usize = 4;
+
+    /// Create a new randomly generated `ShortCode` field.
+    pub fn generate(config: &ShortCodeConfig) -> Self {
         use rand::prelude::*;
-        let allowed_chars = ['a', 'b', 'c', 'd', '1', '2', '3', '4'];
+        let allowed_chars = config.alphabet.chars();
 
         let mut rng = thread_rng();
-        let mut shortcode = String::with_capacity(10);
-        for _ in 0..10 {
-            shortcode.push(
+        let shortcode = (0..config.length)
+            .map(|_| {
                 *allowed_chars
                     .choose(&mut rng)
-                    .expect("sampling array should have values"),
-            );
-        }
+                    .expect("sampling array should have values") as char
+            })
+            .collect();
         Self(shortcode)
     }
 
+    /// Create a `ShortCode` field chosen by the client.
+    ///
+    /// Custom shortcodes must be at least [`MIN_VANITY_LENGTH`](Self::MIN_VANITY_LENGTH) characters long,
+    /// and can not be one of the other routes below `/api/job/`.
+    pub fn vanity(shortcode: &str) -> Result<Self, JobError> {
+        let shortcode = Self::from_str(shortcode)?;
+        if shortcode.0.len() < Self::MIN_VANITY_LENGTH {
+            Err(JobError::InvalidShortCode(format!(
+                "custom shortcodes must be at least {} characters long",
+                Self::MIN_VANITY_LENGTH
+            )))
+        } else if RESERVED.contains(&shortcode.0.to_lowercase().as_str()) {
+            Err(JobError::InvalidShortCode(format!(
+                "'{}' is reserved",
+                shortcode.0
+            )))
+        } else {
+            Ok(shortcode)
+        }
+    }
+
     /// Return the underlying [`&str`].
     pub fn as_str(&self) -> &str {
         self.0.as_str()
@@ -42,10 +114,10 @@ impl ShortCode {
     }
 }
 
-/// The Default implementation is a new randomly generated shortcode.
+/// The Default implementation is a new shortcode generated with the default [`ShortCodeConfig`].
 impl Default for ShortCode {
     fn default() -> Self {
-        Self::new()
+        Self::generate(&ShortCodeConfig::default())
     }
 }
 
@@ -55,6 +127,7 @@ impl From<ShortCode> for String {
     }
 }
 
+/// Does not validate the shortcode, so should only be used for trusted values.
 impl From<&str> for ShortCode {
     fn from(shortcode: &str) -> Self {
         ShortCode(shortcode.to_owned())
@@ -62,16 +135,106 @@ impl From<&str> for ShortCode {
 }
 
 impl<'r> FromParam<'r> for ShortCode {
-    type Error = &'r str;
+    type Error = JobError;
 
     fn from_param(param: &'r str) -> Result<Self, Self::Error> {
-        Ok(ShortCode::from(param))
+        Self::from_str(param)
     }
 }
 
+/// Accepts up to [`MAX_LENGTH`](ShortCode::MAX_LENGTH) ASCII letters, digits, `-` and `_`.
+///
+/// This is independent of the [`ShortCodeConfig`], so that shortcodes
+/// generated with a previous configuration remain valid.
 impl FromStr for ShortCode {
     type Err = JobError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(s.into()))
+        if s.is_empty() {
+            Err(JobError::InvalidShortCode("empty shortcode".to_owned()))
+        } else if s.len() > Self::MAX_LENGTH {
+            Err(JobError::InvalidShortCode(format!(
+                "shortcodes can be at most {} characters long",
+                Self::MAX_LENGTH
+            )))
+        } else if !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Err(JobError::InvalidShortCode(
+                "shortcodes can only contain letters, digits, '-' and '_'".to_owned(),
+            ))
+        } else {
+            Ok(Self(s.into()))
+        }
+    }
+}
+
+/// Only accepts well-formed shortcodes, as with [`ShortCode::from_str`].
+impl<'de> Deserialize<'de> for ShortCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let shortcode = String::deserialize(deserializer)?;
+        Self::from_str(&shortcode).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_shortcodes_use_the_alphabet_and_length() {
+        for alphabet in [Alphabet::Base58, Alphabet::Crockford] {
+            let config = ShortCodeConfig {
+                alphabet,
+                length: 16,
+            };
+            let shortcode = ShortCode::generate(&config);
+            assert_eq!(shortcode.as_str().len(), 16);
+            assert!(shortcode
+                .as_str()
+                .bytes()
+                .all(|c| alphabet.chars().contains(&c)));
+            assert!(ShortCode::from_str(shortcode.as_str()).is_ok());
+        }
+    }
+
+    #[test]
+    fn default_shortcodes_have_at_least_48_bits() {
+        let config = ShortCodeConfig::default();
+        assert!(config.alphabet.bits_per_char() * config.length as f64 >= 48.0);
+    }
+
+    #[test]
+    fn malformed_shortcodes_are_rejected() {
+        for shortcode in [
+            "",
+            "has space",
+            "slash/",
+            "dot.dot",
+            "ünïcode",
+            &"a".repeat(33),
+        ] {
+            assert!(
+                ShortCode::from_str(shortcode).is_err(),
+                "accepted '{}'",
+                shortcode
+            );
+        }
+        for shortcode in ["1", "abcd1234", "Legacy_code-2", &"a".repeat(32)] {
+            assert!(
+                ShortCode::from_str(shortcode).is_ok(),
+                "rejected '{}'",
+                shortcode
+            );
+        }
+        assert!(serde_json::from_str::<ShortCode>("\"not valid\"").is_err());
+    }
+
+    #[test]
+    fn vanity_shortcodes_are_checked() {
+        assert!(ShortCode::vanity("my-job").is_ok());
+        assert!(ShortCode::vanity("abc").is_err());
+        assert!(ShortCode::vanity("Stats").is_err());
+        assert!(ShortCode::vanity("not valid").is_err());
     }
 }
@@ -0,0 +1,139 @@
+use crate::domain::job::JobError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The kind of work a [`Job`](crate::domain::job::Job) asks for, from the
+/// `request_type` of its HUMAN manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskType {
+    /// Answer yes or no about an image.
+    ImageLabelBinary,
+    /// Choose one or more labels for an image.
+    ImageLabelMultipleChoice,
+    /// Draw bounding boxes or polygons on an image.
+    ImageLabelAreaSelect,
+    /// Adjust existing bounding boxes or polygons on an image.
+    ImageLabelAreaAdjust,
+    /// Transcribe text from an image.
+    ImageLabelText,
+    /// Write free text.
+    TextFreeEntry,
+    /// Choose one option for a text.
+    TextMultipleChoiceOneOption,
+    /// Choose any number of options for a text.
+    TextMultipleChoiceMultipleOptions,
+    /// Select spans of a text.
+    TextLabelMultipleSpanSelect,
+    /// Several of the other task types.
+    MultiChallenge,
+}
+
+impl TaskType {
+    /// Every task type, in the order they are shown.
+    pub const ALL: [TaskType; 10] = [
+        Self::ImageLabelBinary,
+        Self::ImageLabelMultipleChoice,
+        Self::ImageLabelAreaSelect,
+        Self::ImageLabelAreaAdjust,
+        Self::ImageLabelText,
+        Self::TextFreeEntry,
+        Self::TextMultipleChoiceOneOption,
+        Self::TextMultipleChoiceMultipleOptions,
+        Self::TextLabelMultipleSpanSelect,
+        Self::MultiChallenge,
+    ];
+
+    /// Classify a manifest by its `request_type`.
+    ///
+    /// Returns `None` when the manifest is not JSON, or has no known `request_type`.
+    pub fn from_manifest(manifest: &[u8]) -> Option<Self> {
+        let manifest: serde_json::Value = serde_json::from_slice(manifest).ok()?;
+        manifest.get("request_type")?.as_str()?.parse().ok()
+    }
+
+    /// The name of the task type, as it is stored and used in filters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ImageLabelBinary => "image_label_binary",
+            Self::ImageLabelMultipleChoice => "image_label_multiple_choice",
+            Self::ImageLabelAreaSelect => "image_label_area_select",
+            Self::ImageLabelAreaAdjust => "image_label_area_adjust",
+            Self::ImageLabelText => "image_label_text",
+            Self::TextFreeEntry => "text_free_entry",
+            Self::TextMultipleChoiceOneOption => "text_multiple_choice_one_option",
+            Self::TextMultipleChoiceMultipleOptions => "text_multiple_choice_multiple_options",
+            Self::TextLabelMultipleSpanSelect => "text_label_multiple_span_select",
+            Self::MultiChallenge => "multi_challenge",
+        }
+    }
+
+    /// A short description of the task type, for the job board.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ImageLabelBinary => "Image: yes/no",
+            Self::ImageLabelMultipleChoice => "Image: multiple choice",
+            Self::ImageLabelAreaSelect => "Image: bounding boxes",
+            Self::ImageLabelAreaAdjust => "Image: adjust boxes",
+            Self::ImageLabelText => "Image: transcription",
+            Self::TextFreeEntry => "Text: free entry",
+            Self::TextMultipleChoiceOneOption => "Text: single choice",
+            Self::TextMultipleChoiceMultipleOptions => "Text: multiple choice",
+            Self::TextLabelMultipleSpanSelect => "Text: span selection",
+            Self::MultiChallenge => "Multiple challenges",
+        }
+    }
+}
+
+impl FromStr for TaskType {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|task_type| task_type.as_str() == s)
+            .copied()
+            .ok_or_else(|| JobError::InvalidTaskType(s.to_owned()))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for TaskType {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TaskType;
+    use std::str::FromStr;
+
+    #[test]
+    fn task_types_round_trip_through_their_names() {
+        for task_type in TaskType::ALL {
+            assert_eq!(TaskType::from_str(task_type.as_str()).unwrap(), task_type);
+            assert_eq!(
+                serde_json::to_string(&task_type).unwrap(),
+                format!("\"{}\"", task_type.as_str())
+            );
+        }
+        assert!(TaskType::from_str("unknown").is_err());
+    }
+
+    #[test]
+    fn manifests_are_classified_by_request_type() {
+        assert_eq!(
+            TaskType::from_manifest(
+                br#"{"job_mode": "batch", "request_type": "image_label_area_select"}"#
+            ),
+            Some(TaskType::ImageLabelAreaSelect)
+        );
+        assert_eq!(
+            TaskType::from_manifest(br#"{"request_type": "unknown"}"#),
+            None
+        );
+        assert_eq!(TaskType::from_manifest(br#"{"job_mode": "batch"}"#), None);
+        assert_eq!(TaskType::from_manifest(b"not json"), None);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::domain::job::JobError;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// An exact amount of a token, in its smallest unit.
+///
+/// Amounts are kept as decimal strings without leading zeros, so they are never
+/// rounded, and amounts with more digits are always larger.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenAmount(String);
+
+impl TokenAmount {
+    /// Create a new `TokenAmount` from a whole number of the token's smallest unit.
+    pub fn from_base_units(amount: &str) -> Result<Self, JobError> {
+        let amount = amount.trim();
+        if amount.is_empty() || !amount.chars().all(|c| c.is_ascii_digit()) {
+            return Err(JobError::InvalidTokenAmount(amount.to_owned()));
+        }
+        Ok(Self::canonical(amount))
+    }
+
+    /// Create a new `TokenAmount` from an amount of whole tokens, such as `0.05`,
+    /// for a token with `decimals` decimal places.
+    ///
+    /// Amounts with more decimal places than the token has are rejected, rather than rounded.
+    pub fn from_decimal(amount: &str, decimals: u8) -> Result<Self, JobError> {
+        let invalid = || JobError::InvalidTokenAmount(amount.to_owned());
+        let trimmed = amount.trim();
+        let (whole, fraction) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+        let fraction = fraction.trim_end_matches('0');
+        if (whole.is_empty() && fraction.is_empty())
+            || fraction.len() > usize::from(decimals)
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let padding = "0".repeat(usize::from(decimals) - fraction.len());
+        Ok(Self::canonical(&format!(
+            "{}{}{}",
+            whole, fraction, padding
+        )))
+    }
+
+    /// Strips leading zeros from a string of digits.
+    fn canonical(digits: &str) -> Self {
+        let digits = digits.trim_start_matches('0');
+        Self(if digits.is_empty() { "0" } else { digits }.to_owned())
+    }
+
+    /// Subtracts `other` from this amount, stopping at zero.
+    pub fn saturating_sub(&self, other: &Self) -> Self {
+        if self <= other {
+            return Self("0".to_owned());
+        }
+        let mut borrow = 0;
+        let mut digits: Vec<u8> = self
+            .0
+            .bytes()
+            .rev()
+            .zip(other.0.bytes().rev().chain(std::iter::repeat(b'0')))
+            .map(|(a, b)| {
+                let mut digit = i16::from(a - b'0') - i16::from(b - b'0') - borrow;
+                borrow = i16::from(digit < 0);
+                if digit < 0 {
+                    digit += 10;
+                }
+                b'0' + digit as u8
+            })
+            .collect();
+        digits.reverse();
+        Self::canonical(&String::from_utf8(digits).expect("digits are ascii"))
+    }
+
+    /// Formats the amount in whole tokens, for a token with `decimals` decimal places.
+    ///
+    /// Trailing zeros after the decimal point are left out.
+    pub fn format(&self, decimals: u8) -> String {
+        let decimals = usize::from(decimals);
+        let padded = format!("{:0>width$}", self.0, width = decimals + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - decimals);
+        match fraction.trim_end_matches('0') {
+            "" => whole.to_owned(),
+            fraction => format!("{}.{}", whole, fraction),
+        }
+    }
+
+    /// Return a reference to the underlying [`&str`].
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Return the underlying [`String`].
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+/// Amounts are compared by their number of digits, and then digit by digit.
+impl Ord for TokenAmount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for TokenAmount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_base_units(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let amount = String::deserialize(deserializer)?;
+        Self::from_base_units(&amount).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TokenAmount;
+
+    #[test]
+    fn decimal_amounts_are_converted_exactly() {
+        let amount = TokenAmount::from_decimal("0.05", 18).unwrap();
+        assert_eq!(amount.as_str(), "50000000000000000");
+        assert_eq!(amount.format(18), "0.05");
+        assert_eq!(
+            TokenAmount::from_decimal("123456789.123456789123456789", 18)
+                .unwrap()
+                .format(18),
+            "123456789.123456789123456789"
+        );
+        assert_eq!(
+            TokenAmount::from_decimal("12", 6).unwrap().as_str(),
+            "12000000"
+        );
+        assert_eq!(TokenAmount::from_decimal(".5", 1).unwrap().as_str(), "5");
+        assert_eq!(TokenAmount::from_decimal("1.50", 1).unwrap().as_str(), "15");
+        assert_eq!(TokenAmount::from_decimal("0", 18).unwrap().format(18), "0");
+        assert_eq!(TokenAmount::from_base_units("007").unwrap().format(0), "7");
+        for amount in ["", ".", "-1", "1e5", "0.001", "1.2.3", "abc"] {
+            assert!(
+                TokenAmount::from_decimal(amount, 2).is_err(),
+                "accepted '{}'",
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn amounts_compare_and_subtract_as_numbers() {
+        let amount = |s: &str| TokenAmount::from_base_units(s).unwrap();
+        assert!(amount("10") > amount("9"));
+        assert!(amount("0100") < amount("99999"));
+        assert_eq!(amount("1000").saturating_sub(&amount("1")), amount("999"));
+        assert_eq!(amount("5000").saturating_sub(&amount("4999")), amount("1"));
+        assert_eq!(amount("5").saturating_sub(&amount("6")), amount("0"));
+        assert_eq!(
+            amount("100000000000000000000000000000").saturating_sub(&amount("1")),
+            amount("99999999999999999999999999999")
+        );
+    }
+}
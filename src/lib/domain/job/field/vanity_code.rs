@@ -0,0 +1,89 @@
+use crate::domain::job::field::ShortCode;
+use crate::domain::job::JobError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::str::FromStr;
+
+/// A custom [`ShortCode`] chosen when posting a [`Job`](crate::domain::job::Job).
+///
+/// A shortcode is generated for the `Job` when this is empty.
+#[derive(Clone, Debug, Serialize)]
+pub struct VanityCode(Option<ShortCode>);
+
+impl VanityCode {
+    /// Create a new `VanityCode` field.
+    ///
+    /// A blank shortcode is no custom shortcode. Otherwise a [`JobError`] is
+    /// returned unless it is accepted by [`ShortCode::vanity`].
+    pub fn new<T: Into<Option<String>>>(shortcode: T) -> Result<Self, JobError> {
+        let shortcode: Option<String> = shortcode.into();
+        match shortcode {
+            Some(shortcode) if !shortcode.trim().is_empty() => {
+                Ok(Self(Some(ShortCode::vanity(shortcode.trim())?)))
+            }
+            _ => Ok(Self(None)),
+        }
+    }
+
+    /// Return the underlying [`Option<ShortCode>`](`ShortCode`).
+    pub fn into_inner(self) -> Option<ShortCode> {
+        self.0
+    }
+}
+
+/// The Default implementation is no custom shortcode.
+impl Default for VanityCode {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl FromStr for VanityCode {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.to_string())
+    }
+}
+
+/// Only accepts custom shortcodes allowed by [`VanityCode::new`].
+impl<'de> Deserialize<'de> for VanityCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let shortcode = Option::<String>::deserialize(deserializer)?;
+        Self::new(shortcode).map_err(serde::de::Error::custom)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for VanityCode {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value.to_owned())
+            .map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(<Self as Default>::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VanityCode;
+
+    #[test]
+    fn blank_vanity_code_is_none() {
+        assert!(VanityCode::new(" ".to_owned())
+            .unwrap()
+            .into_inner()
+            .is_none());
+        assert!(serde_json::from_str::<VanityCode>("null")
+            .unwrap()
+            .into_inner()
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_vanity_code() {
+        assert!(VanityCode::new("abc".to_owned()).is_err());
+        assert!(serde_json::from_str::<VanityCode>("\"stats\"").is_err());
+    }
+}
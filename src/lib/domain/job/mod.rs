@@ -15,10 +15,43 @@ pub enum JobError {
     #[error("invalid manifest_url: {0}")]
     InvalidManifestUrl(String),
 
+    /// Manifest hash is not 40 or 64 hex digits.
+    #[error("invalid manifest hash: {0}")]
+    InvalidManifestHash(String),
+
+    /// Manifest status is not one of the known statuses.
+    #[error("invalid manifest status: {0}")]
+    InvalidManifestStatus(String),
+
+    /// Task type is not one of the HUMAN request types.
+    #[error("invalid task type: {0}")]
+    InvalidTaskType(String),
+
+    /// Token amount is not a whole number of the token's smallest unit, or has
+    /// more decimal places than the token.
+    #[error("invalid token amount: {0}")]
+    InvalidTokenAmount(String),
+
+    /// Sort order is not one of the job board orders.
+    #[error("invalid sort: {0}")]
+    InvalidSort(String),
+
+    /// ShortCode is malformed, or can not be used as a custom shortcode.
+    #[error("invalid shortcode: {0}")]
+    InvalidShortCode(String),
+
     /// EscrowId was not provided.
     #[error("empty escrow_id")]
     EmptyEscrowId,
 
+    /// An escrow address is not `0x` followed by 40 hex digits.
+    #[error("invalid escrow address: {0}")]
+    InvalidEscrowAddress(String),
+
+    /// A mixed case escrow address does not have a valid EIP-55 checksum.
+    #[error("invalid escrow address checksum: {0}")]
+    InvalidEscrowChecksum(String),
+
     /// Date is invalid: invalid day of the month, too far in the past, etc.
     #[error("invalid date: {0}")]
     InvalidDate(String),
@@ -54,6 +87,18 @@ pub struct Job {
     pub escrow_id: field::EscrowId,
     /// The manifest_url of the Job.
     pub manifest_url: field::ManifestUrl,
+    /// Whether the manifest matches the hash recorded by the escrow.
+    #[serde(default)]
+    pub manifest_verification: field::ManifestVerification,
+    /// The task type of the Job, once its manifest has been classified.
+    #[serde(default)]
+    pub task_type: Option<field::TaskType>,
+    /// What the Job pays, and in which token.
+    #[serde(default)]
+    pub reward: field::Reward,
+    /// The network of the subgraph the Job was read from. Jobs posted to the service have none.
+    #[serde(default)]
+    pub network: Option<String>,
     /// The date that this Job was posted to the service.
     pub posted: field::Posted,
     /// The date that this Job will expire.
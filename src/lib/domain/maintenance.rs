@@ -1,31 +1,279 @@
-//! Background database maintenance task.
+//! Background database maintenance tasks.
 
+use crate::data::graph::GraphConfig;
 use crate::data::DatabasePool;
+use crate::domain::alert::WebhookPolicy;
+use crate::domain::health::SyncProgress;
+use crate::domain::job::field::{ManifestPolicy, ManifestStatus, ShortCodeConfig, TokenList};
+use crate::domain::supervisor::{Supervisor, TaskSpec, TaskStatus};
+use crate::metrics;
 use crate::service;
+use crate::Time;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
 
-/// Async background task that performs rountine database tasks.
+/// Settings for the [`Maintenance`] tasks.
+#[derive(Debug, Clone)]
+pub struct MaintenanceConfig {
+    /// How often new jobs are fetched from the Graph.
+    pub sync_interval: Duration,
+    /// How often expired jobs are deleted.
+    pub expiry_purge_interval: Duration,
+    /// How often old hourly response buckets are merged into daily buckets.
+    pub stats_rollup_interval: Duration,
+    /// How long hourly response buckets are kept before being merged.
+    pub hourly_stats_retention: Duration,
+    /// How long visitors are remembered for, to count unique visitors.
+    pub visitor_retention: Duration,
+    /// How often query planner statistics are updated and unused space is released.
+    pub optimize_interval: Duration,
+    /// How often pending manifests are checked against their escrow's hash.
+    pub manifest_verify_interval: Duration,
+    /// How often alerts are posted to the webhooks of saved searches.
+    pub alert_delivery_interval: Duration,
+    /// How often claims whose lease has run out are deleted.
+    pub claim_expiry_interval: Duration,
+    /// Where jobs are fetched from.
+    pub graph: GraphConfig,
+    /// How manifests are fetched.
+    pub manifests: ManifestPolicy,
+    /// Which URLs alerts may be posted to.
+    pub webhooks: WebhookPolicy,
+    /// How shortcodes are generated for synced jobs.
+    pub shortcodes: ShortCodeConfig,
+    /// The tokens which the rewards of synced jobs are recognised in.
+    pub tokens: TokenList,
+}
+
+/// The Default implementation syncs every 10 seconds, purges every minute,
+/// rolls up hourly buckets older than 7 days and forgets visitors older than
+/// 30 days every hour, optimizes the
+/// database every 6 hours, verifies manifests every minute, posts alerts to
+/// webhooks every 10 seconds, and deletes expired claims every minute.
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            sync_interval: Duration::from_secs(10),
+            expiry_purge_interval: Duration::from_secs(60),
+            stats_rollup_interval: Duration::from_secs(60 * 60),
+            hourly_stats_retention: Duration::from_secs(7 * 24 * 60 * 60),
+            visitor_retention: Duration::from_secs(30 * 24 * 60 * 60),
+            optimize_interval: Duration::from_secs(6 * 60 * 60),
+            manifest_verify_interval: Duration::from_secs(60),
+            alert_delivery_interval: Duration::from_secs(10),
+            claim_expiry_interval: Duration::from_secs(60),
+            graph: GraphConfig::default(),
+            manifests: ManifestPolicy::default(),
+            webhooks: WebhookPolicy::default(),
+            shortcodes: ShortCodeConfig::default(),
+            tokens: TokenList::default(),
+        }
+    }
+}
+
+/// Supervised background tasks that perform routine database work.
 ///
-/// This task deletes expired jobs periodically.
-pub struct Maintenance;
+/// * `sync` fetches new jobs from the Graph.
+/// * `expiry_purge` deletes expired jobs.
+/// * `stats_rollup` merges old hourly response buckets into daily buckets, and
+///   forgets old visitors.
+/// * `optimize` updates query planner statistics and releases unused space.
+/// * `manifest_verify` checks manifests against the hash recorded by their escrow.
+///   It is also the manifest refresh task: manifests are fetched again when their
+///   URL changes, and unavailable manifests are retried hourly.
+/// * `alert_delivery` posts job alerts to the webhooks of saved searches.
+/// * `claim_expiry` deletes claims whose lease has run out.
+pub struct Maintenance {
+    supervisor: Supervisor,
+    sync_progress: Arc<SyncProgress>,
+}
 
 impl Maintenance {
-    /// Spawn the database maintenance task.
+    /// Spawn the database maintenance tasks with the default [`MaintenanceConfig`].
     pub fn spawn(pool: DatabasePool, handle: Handle) -> Self {
-        handle.spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
-            let res = service::action::download_graph_jobs(&pool).await;
-            println!("Downloading All Jobs: {:?}", res);
-            loop {
-                interval.tick().await;
-                if let Err(e) = service::action::delete_expired(&pool).await {
-                    eprintln!("failed to delete expired jobs: {}", e);
+        Self::with_config(pool, handle, MaintenanceConfig::default())
+    }
+
+    /// Spawn the database maintenance tasks.
+    ///
+    /// Each run is delayed by a random jitter of up to a tenth of its interval.
+    pub fn with_config(pool: DatabasePool, handle: Handle, config: MaintenanceConfig) -> Self {
+        let supervisor = Supervisor::new(handle);
+
+        let sync_pool = pool.clone();
+        let graph = config.graph;
+        let shortcodes = config.shortcodes;
+        let tokens = config.tokens;
+        let sync_progress = Arc::new(SyncProgress::default());
+        let progress = Arc::clone(&sync_progress);
+        supervisor.register(
+            TaskSpec::new("sync", config.sync_interval)
+                .with_jitter(config.sync_interval / 10)
+                .with_max_backoff(Duration::from_secs(300).max(config.sync_interval)),
+            move || {
+                let pool = sync_pool.clone();
+                let graph = graph.clone();
+                let shortcodes = shortcodes.clone();
+                let tokens = tokens.clone();
+                let progress = Arc::clone(&progress);
+                async move {
+                    let inserted = service::action::sync_graph_jobs(
+                        &graph,
+                        &shortcodes,
+                        &tokens,
+                        &progress,
+                        &pool,
+                    )
+                    .await?;
+                    metrics::SYNC_JOBS_INSERTED.observe(inserted as f64);
+                    if inserted > 0 {
+                        tracing::info!(inserted, "synced new jobs from the Graph");
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let expiry_pool = pool.clone();
+        supervisor.register(
+            TaskSpec::new("expiry_purge", config.expiry_purge_interval)
+                .with_jitter(config.expiry_purge_interval / 10),
+            move || {
+                let pool = expiry_pool.clone();
+                async move {
+                    let deleted = service::action::delete_expired(&pool).await?;
+                    metrics::EXPIRED_JOBS_DELETED.inc_by(deleted);
+                    if deleted > 0 {
+                        tracing::info!(deleted, "deleted expired jobs");
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let retention = config.hourly_stats_retention.as_secs() as i64;
+        let visitor_retention = config.visitor_retention.as_secs() as i64;
+        let rollup_pool = pool.clone();
+        supervisor.register(
+            TaskSpec::new("stats_rollup", config.stats_rollup_interval)
+                .with_jitter(config.stats_rollup_interval / 10)
+                .with_max_backoff(config.stats_rollup_interval * 6),
+            move || {
+                let pool = rollup_pool.clone();
+                async move {
+                    let now = Time::now().timestamp();
+                    service::action::rollup_response_stats(now - retention, &pool).await?;
+                    service::action::delete_old_visitors(now - visitor_retention, &pool).await?;
+                    Ok(())
+                }
+            },
+        );
+
+        let verify_pool = pool.clone();
+        let manifests = config.manifests.clone();
+        supervisor.register(
+            TaskSpec::new("manifest_verify", config.manifest_verify_interval)
+                .with_jitter(config.manifest_verify_interval / 10)
+                .with_max_backoff(config.manifest_verify_interval * 10),
+            move || {
+                let pool = verify_pool.clone();
+                let manifests = manifests.clone();
+                async move {
+                    let statuses = service::action::verify_manifests(&manifests, &pool).await?;
+                    for status in &statuses {
+                        metrics::MANIFEST_VERIFICATIONS
+                            .with_label_values(&[status.as_str()])
+                            .inc();
+                    }
+                    let flagged = statuses
+                        .iter()
+                        .filter(|status| **status == ManifestStatus::Flagged)
+                        .count();
+                    if flagged > 0 {
+                        tracing::warn!(
+                            flagged,
+                            "flagged manifests which do not match their escrow"
+                        );
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let delivery_pool = pool.clone();
+        let webhooks = config.webhooks;
+        supervisor.register(
+            TaskSpec::new("alert_delivery", config.alert_delivery_interval)
+                .with_jitter(config.alert_delivery_interval / 10)
+                .with_max_backoff(config.alert_delivery_interval * 10),
+            move || {
+                let pool = delivery_pool.clone();
+                let webhooks = webhooks.clone();
+                async move {
+                    let results = service::action::deliver_webhooks(&webhooks, &pool).await?;
+                    for result in &results {
+                        metrics::WEBHOOK_DELIVERIES
+                            .with_label_values(&[result.as_str()])
+                            .inc();
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let claim_pool = pool.clone();
+        supervisor.register(
+            TaskSpec::new("claim_expiry", config.claim_expiry_interval)
+                .with_jitter(config.claim_expiry_interval / 10),
+            move || {
+                let pool = claim_pool.clone();
+                async move {
+                    let deleted = service::action::delete_expired_claims(&pool).await?;
+                    metrics::EXPIRED_CLAIMS_DELETED.inc_by(deleted);
+                    if deleted > 0 {
+                        tracing::debug!(deleted, "deleted expired claims");
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        supervisor.register(
+            TaskSpec::new("optimize", config.optimize_interval)
+                .with_jitter(config.optimize_interval / 10)
+                .with_max_backoff(config.optimize_interval * 4),
+            move || {
+                let pool = pool.clone();
+                async move {
+                    service::action::optimize_database(&pool).await?;
+                    tracing::debug!("optimized the database");
+                    Ok(())
                 }
-                let new_jobs = service::action::fetch_and_insert_new_jobs(&pool).await;
-                println!("Downloaded New Jobs:: {:?}", new_jobs);
-            }
-        });
-        Self
+            },
+        );
+
+        Self {
+            supervisor,
+            sync_progress,
+        }
+    }
+
+    /// The status of each maintenance task.
+    pub fn status(&self) -> Vec<TaskStatus> {
+        self.supervisor.status()
+    }
+
+    /// The status of the maintenance task called `name`.
+    pub fn task(&self, name: &str) -> Option<TaskStatus> {
+        self.supervisor
+            .status()
+            .into_iter()
+            .find(|task| task.name == name)
+    }
+
+    /// The blocks seen by the `sync` task.
+    pub fn sync_progress(&self) -> &SyncProgress {
+        &self.sync_progress
     }
 }
@@ -1,7 +1,13 @@
 //! Business-specific modules and functions
 
+pub mod alert;
+pub mod board;
+pub mod claim;
+pub mod health;
 pub mod job;
 pub mod maintenance;
+pub mod stats;
+pub mod supervisor;
 pub mod time;
 
 pub use job::Job;
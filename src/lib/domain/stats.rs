@@ -0,0 +1,152 @@
+//! Response analytics time series.
+
+use crate::domain::time::Time;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The possible errors that can occur when working with response statistics.
+#[derive(Debug, thiserror::Error)]
+pub enum StatsError {
+    /// The requested granularity is not supported.
+    #[error("invalid granularity: {0}")]
+    InvalidGranularity(String),
+
+    /// A bucket timestamp is out of range.
+    #[error("invalid bucket timestamp: {0}")]
+    InvalidBucket(i64),
+}
+
+/// The width of each bucket in a response time series.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// One bucket per hour.
+    Hourly,
+    /// One bucket per day.
+    Daily,
+}
+
+impl Granularity {
+    /// The width of a bucket, in seconds.
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Self::Hourly => 60 * 60,
+            Self::Daily => 24 * 60 * 60,
+        }
+    }
+
+    /// Returns the start of the bucket which contains `timestamp`.
+    pub fn bucket_start(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.seconds())
+    }
+
+    /// The default number of days covered by a time series of this granularity.
+    pub fn default_days(&self) -> u32 {
+        match self {
+            Self::Hourly => 1,
+            Self::Daily => 30,
+        }
+    }
+}
+
+/// The Default implementation is hourly buckets.
+impl Default for Granularity {
+    fn default() -> Self {
+        Self::Hourly
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = StatsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hourly" | "hour" => Ok(Self::Hourly),
+            "daily" | "day" => Ok(Self::Daily),
+            other => Err(StatsError::InvalidGranularity(other.to_owned())),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Granularity {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+}
+
+/// The way in which a [`Job`](crate::Job) was accessed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// The job page was viewed.
+    Html,
+    /// The raw escrow id was fetched.
+    Raw,
+    /// The job was fetched through the API.
+    Api,
+}
+
+impl Channel {
+    /// Return the name that is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Raw => "raw",
+            Self::Api => "api",
+        }
+    }
+}
+
+/// The responses received by a single [`Job`](crate::Job), split by [`Channel`].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ResponseBreakdown {
+    /// Job page views.
+    pub html: u64,
+    /// Raw escrow id fetches.
+    pub raw: u64,
+    /// API fetches.
+    pub api: u64,
+    /// The number of distinct API keys that fetched the job.
+    pub api_keys: u64,
+    /// Approximate number of distinct visitors across all channels, over the
+    /// days that visitors are remembered for.
+    pub unique_visitors: u64,
+}
+
+/// The number of responses received during a single bucket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResponseBucket {
+    /// The start of the bucket.
+    pub start: Time,
+    /// The number of responses received during the bucket.
+    pub responses: u64,
+}
+
+/// A series of response counts, ordered by bucket start.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResponseSeries {
+    /// The width of each bucket.
+    pub granularity: Granularity,
+    /// The buckets that received at least one response.
+    pub buckets: Vec<ResponseBucket>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Granularity;
+    use std::str::FromStr;
+
+    #[test]
+    fn bucket_start_truncates_to_granularity() {
+        let timestamp = 1_626_400_000;
+        assert_eq!(Granularity::Hourly.bucket_start(timestamp), 1_626_397_200);
+        assert_eq!(Granularity::Daily.bucket_start(timestamp), 1_626_393_600);
+    }
+
+    #[test]
+    fn parses_granularity() {
+        assert_eq!(Granularity::from_str("Daily").unwrap(), Granularity::Daily);
+        assert!(Granularity::from_str("weekly").is_err());
+    }
+}
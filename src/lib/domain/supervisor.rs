@@ -0,0 +1,278 @@
+//! Supervisor for named, periodic background tasks.
+
+use crate::Time;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tracing::Instrument;
+
+/// The error returned by a failed task run.
+pub type TaskError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How often, and how persistently, a supervised task is run.
+#[derive(Debug, Clone)]
+pub struct TaskSpec {
+    /// The name reported in the task status.
+    pub name: &'static str,
+    /// The delay between successful runs.
+    pub interval: Duration,
+    /// A random delay of up to this long is added to each interval, so that
+    /// tasks do not run in lockstep.
+    pub jitter: Duration,
+    /// The longest delay between runs while the task keeps failing.
+    ///
+    /// The delay starts at `interval` and doubles after each consecutive failure.
+    pub max_backoff: Duration,
+}
+
+impl TaskSpec {
+    /// Create a new [`TaskSpec`] with no jitter and a backoff of up to 10 intervals.
+    pub fn new(name: &'static str, interval: Duration) -> Self {
+        Self {
+            name,
+            interval,
+            jitter: Duration::from_secs(0),
+            max_backoff: interval * 10,
+        }
+    }
+
+    /// Set the maximum random delay that is added to each interval.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the longest delay between runs while the task keeps failing.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// The delay before the next run, after `consecutive_failures` failed runs.
+    fn delay(&self, consecutive_failures: u32) -> Duration {
+        use rand::Rng;
+
+        let delay = match consecutive_failures {
+            0 => self.interval,
+            failures => {
+                let factor = 2u32.saturating_pow(failures.min(16));
+                self.interval
+                    .checked_mul(factor)
+                    .unwrap_or(self.max_backoff)
+                    .min(self.max_backoff.max(self.interval))
+            }
+        };
+        let jitter = match self.jitter.as_millis() {
+            0 => 0,
+            max => rand::thread_rng().gen_range(0..=max as u64),
+        };
+        delay + Duration::from_millis(jitter)
+    }
+}
+
+/// The most recent state of a supervised task.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    /// The name of the task.
+    pub name: &'static str,
+    /// The delay between successful runs, in seconds.
+    pub interval_secs: u64,
+    /// Whether the task is running right now.
+    pub running: bool,
+    /// The number of times the task has run.
+    pub runs: u64,
+    /// The number of runs which returned an error or panicked.
+    pub failures: u64,
+    /// The number of runs which panicked.
+    pub panics: u64,
+    /// The number of failed runs since the last success.
+    pub consecutive_failures: u32,
+    /// When the most recent run started.
+    pub last_started: Option<Time>,
+    /// When the most recent run finished.
+    pub last_finished: Option<Time>,
+    /// When the most recent successful run finished.
+    pub last_success: Option<Time>,
+    /// The error from the most recent failed run.
+    pub last_error: Option<String>,
+    /// When the task will next run.
+    pub next_run: Option<Time>,
+}
+
+impl TaskStatus {
+    /// The status of a task which has not run yet.
+    fn new(spec: &TaskSpec) -> Self {
+        Self {
+            name: spec.name,
+            interval_secs: spec.interval.as_secs(),
+            running: false,
+            runs: 0,
+            failures: 0,
+            panics: 0,
+            consecutive_failures: 0,
+            last_started: None,
+            last_finished: None,
+            last_success: None,
+            last_error: None,
+            next_run: Some(Time::now()),
+        }
+    }
+}
+
+/// Shared status of all supervised tasks, keyed by name.
+type StatusStore = Arc<RwLock<BTreeMap<&'static str, TaskStatus>>>;
+
+/// Runs named tasks periodically and keeps track of how they are doing.
+///
+/// Each run of a task is spawned separately, so a run which panics is counted
+/// as a failure and the task is simply run again after its backoff delay.
+pub struct Supervisor {
+    handle: Handle,
+    status: StatusStore,
+}
+
+impl Supervisor {
+    /// Create a new [`Supervisor`] which spawns tasks on the provided runtime.
+    pub fn new(handle: Handle) -> Self {
+        Self {
+            handle,
+            status: StatusStore::default(),
+        }
+    }
+
+    /// Start running `task` according to the [`TaskSpec`].
+    ///
+    /// The first run starts immediately.
+    pub fn register<F, Fut>(&self, spec: TaskSpec, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), TaskError>> + Send + 'static,
+    {
+        self.status
+            .write()
+            .insert(spec.name, TaskStatus::new(&spec));
+        let status = Arc::clone(&self.status);
+        let handle = self.handle.clone();
+        self.handle.spawn(async move {
+            loop {
+                Self::update(&status, spec.name, |s| {
+                    s.running = true;
+                    s.last_started = Some(Time::now());
+                    s.next_run = None;
+                });
+
+                let span = tracing::info_span!("task", name = spec.name);
+                let result = handle.spawn(task().instrument(span)).await;
+
+                let mut consecutive_failures = 0;
+                Self::update(&status, spec.name, |s| {
+                    s.running = false;
+                    s.runs += 1;
+                    s.last_finished = Some(Time::now());
+                    let error = match result {
+                        Ok(Ok(())) => None,
+                        Ok(Err(e)) => Some(e.to_string()),
+                        Err(e) => {
+                            s.panics += 1;
+                            Some(format!("task panicked: {}", e))
+                        }
+                    };
+                    match error {
+                        None => {
+                            s.consecutive_failures = 0;
+                            s.last_success = s.last_finished.clone();
+                        }
+                        Some(e) => {
+                            tracing::warn!(task = spec.name, error = %e, "task failed");
+                            s.failures += 1;
+                            s.consecutive_failures += 1;
+                            s.last_error = Some(e);
+                        }
+                    }
+                    consecutive_failures = s.consecutive_failures;
+                });
+
+                let delay = spec.delay(consecutive_failures);
+                Self::update(&status, spec.name, |s| {
+                    s.next_run =
+                        Time::from_timestamp(Time::now().timestamp() + delay.as_secs() as i64);
+                });
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+
+    /// Apply `f` to the status of the task called `name`.
+    fn update<F: FnOnce(&mut TaskStatus)>(status: &StatusStore, name: &'static str, f: F) {
+        if let Some(status) = status.write().get_mut(name) {
+            f(status);
+        }
+    }
+
+    /// The status of every registered task, ordered by name.
+    pub fn status(&self) -> Vec<TaskStatus> {
+        self.status.read().values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Supervisor, TaskSpec};
+    use crate::test::async_runtime;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_up_to_limit() {
+        let spec = TaskSpec::new("test", Duration::from_secs(10))
+            .with_max_backoff(Duration::from_secs(60));
+        assert_eq!(spec.delay(0), Duration::from_secs(10));
+        assert_eq!(spec.delay(1), Duration::from_secs(20));
+        assert_eq!(spec.delay(2), Duration::from_secs(40));
+        assert_eq!(spec.delay(3), Duration::from_secs(60));
+        assert_eq!(spec.delay(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn panicking_task_is_restarted() {
+        let rt = async_runtime();
+        let supervisor = Supervisor::new(rt.handle().clone());
+        let calls = Arc::new(AtomicU32::new(0));
+
+        let counter = Arc::clone(&calls);
+        supervisor.register(
+            TaskSpec::new("flaky", Duration::from_millis(5)),
+            move || {
+                let call = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match call {
+                        0 => panic!("first run panics"),
+                        1 => Err("second run fails".into()),
+                        _ => Ok(()),
+                    }
+                }
+            },
+        );
+
+        rt.block_on(async {
+            for _ in 0..100 {
+                if supervisor.status()[0].last_success.is_some() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let status = &supervisor.status()[0];
+        assert_eq!(status.name, "flaky");
+        assert!(status.last_success.is_some());
+        assert_eq!(status.panics, 1);
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!(status.last_error.as_deref(), Some("second run fails"));
+    }
+}
@@ -1,6 +1,6 @@
 //! Time wrapper structure.
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use derive_more::From;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
@@ -20,6 +20,16 @@ impl Time {
         self.0.timestamp()
     }
 
+    /// The current time.
+    pub fn now() -> Self {
+        Time(Utc::now())
+    }
+
+    /// Convert a number of seconds since the Unix epoch into a [`Time`].
+    pub fn from_timestamp(secs: i64) -> Option<Self> {
+        Utc.timestamp_opt(secs, 0).single().map(Time)
+    }
+
     /// Convert a [`NaiveDateTime`] into a [`Time`]
     pub fn from_naive_utc(datetime: NaiveDateTime) -> Self {
         Time(DateTime::from_utc(datetime, Utc))
@@ -0,0 +1,52 @@
+//! Structured logging.
+//!
+//! Log levels are filtered using the `RUST_LOG` environment variable, with the
+//! same syntax as [`EnvFilter`]. Everything at `info` and above is logged by default.
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+/// The possible errors that can occur when configuring logging.
+#[derive(Debug, thiserror::Error)]
+pub enum LoggingError {
+    /// The requested log format is not supported.
+    #[error("invalid log format '{0}', expected 'text' or 'json'")]
+    InvalidFormat(String),
+}
+
+/// How log entries are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = LoggingError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(LoggingError::InvalidFormat(s.to_owned())),
+        }
+    }
+}
+
+/// Install the global logger, which writes to stdout in the provided [`LogFormat`].
+///
+/// Messages from crates which use the `log` crate, such as Rocket, are logged as well.
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let res = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    };
+    if let Err(e) = res {
+        tracing::warn!("logger already installed: {}", e);
+    }
+}
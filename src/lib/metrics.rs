@@ -0,0 +1,127 @@
+//! Prometheus metrics.
+//!
+//! All metrics are registered with the default [`prometheus`] registry, which is
+//! exported by the `/metrics` route.
+
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    TextEncoder,
+};
+
+lazy_static! {
+    /// HTTP requests handled, by route, method and response status.
+    pub static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests handled, by route, method and response status.",
+        &["route", "method", "status"]
+    )
+    .expect("failed to register metric");
+
+    /// Time taken to handle HTTP requests, by route and method.
+    pub static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to handle HTTP requests, by route and method.",
+        &["route", "method"]
+    )
+    .expect("failed to register metric");
+
+    /// API error responses, by error variant.
+    pub static ref API_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "api_errors_total",
+        "API error responses, by error variant.",
+        &["variant"]
+    )
+    .expect("failed to register metric");
+
+    /// Time taken by Graph queries, including failed queries.
+    pub static ref GRAPH_QUERY_DURATION: Histogram = register_histogram!(
+        "graph_query_duration_seconds",
+        "Time taken by Graph queries, including failed queries."
+    )
+    .expect("failed to register metric");
+
+    /// Graph queries which failed or returned no data.
+    pub static ref GRAPH_QUERY_FAILURES: IntCounter = register_int_counter!(
+        "graph_query_failures_total",
+        "Graph queries which failed or returned no data."
+    )
+    .expect("failed to register metric");
+
+    /// Jobs inserted by each successful sync.
+    pub static ref SYNC_JOBS_INSERTED: Histogram = register_histogram!(
+        "sync_jobs_inserted",
+        "Jobs inserted by each successful sync.",
+        vec![0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0]
+    )
+    .expect("failed to register metric");
+
+    /// Manifests checked against the hash recorded by their escrow, by resulting status.
+    pub static ref MANIFEST_VERIFICATIONS: IntCounterVec = register_int_counter_vec!(
+        "manifest_verifications_total",
+        "Manifests checked against the hash recorded by their escrow, by resulting status.",
+        &["status"]
+    )
+    .expect("failed to register metric");
+
+    /// Job alerts posted to the webhooks of saved searches, by result.
+    pub static ref WEBHOOK_DELIVERIES: IntCounterVec = register_int_counter_vec!(
+        "webhook_deliveries_total",
+        "Job alerts posted to the webhooks of saved searches, by result.",
+        &["result"]
+    )
+    .expect("failed to register metric");
+
+    /// Expired jobs removed from the database.
+    pub static ref EXPIRED_JOBS_DELETED: IntCounter = register_int_counter!(
+        "expired_jobs_deleted_total",
+        "Expired jobs removed from the database."
+    )
+    .expect("failed to register metric");
+
+    /// Claims removed from the database after their lease ran out.
+    pub static ref EXPIRED_CLAIMS_DELETED: IntCounter = register_int_counter!(
+        "expired_claims_deleted_total",
+        "Claims removed from the database after their lease ran out."
+    )
+    .expect("failed to register metric");
+
+    /// Response entries waiting to be committed by the response counter.
+    pub static ref RESPONSE_COUNTER_PENDING: IntGauge = register_int_gauge!(
+        "response_counter_pending",
+        "Response entries waiting to be committed by the response counter."
+    )
+    .expect("failed to register metric");
+
+    /// Response entries dropped because the response counter's buffer was full.
+    pub static ref RESPONSE_COUNTER_DROPPED: IntCounter = register_int_counter!(
+        "response_counter_dropped_total",
+        "Response entries dropped because the response counter's buffer was full."
+    )
+    .expect("failed to register metric");
+
+    /// Time taken by response counter commits, including failed commits.
+    pub static ref RESPONSE_COUNTER_COMMIT_DURATION: Histogram = register_histogram!(
+        "response_counter_commit_duration_seconds",
+        "Time taken by response counter commits, including failed commits."
+    )
+    .expect("failed to register metric");
+
+    /// Response counter commits which failed and were rolled back.
+    pub static ref RESPONSE_COUNTER_COMMIT_FAILURES: IntCounter = register_int_counter!(
+        "response_counter_commit_failures_total",
+        "Response counter commits which failed and were rolled back."
+    )
+    .expect("failed to register metric");
+}
+
+/// Encode every registered metric in the Prometheus text format.
+pub fn gather() -> String {
+    let mut buffer = vec![];
+    let encoder = TextEncoder::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!(error = %e, "failed to encode metrics");
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
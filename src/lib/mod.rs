@@ -1,7 +1,10 @@
 //! Main application library
 
+pub mod config;
 pub mod data;
 pub mod domain;
+pub mod logging;
+pub mod metrics;
 pub mod service;
 pub mod web;
 
@@ -12,24 +15,54 @@ pub use domain::time::Time;
 pub use service::ServiceError;
 
 use data::AppDatabase;
+use domain::alert::WebhookPolicy;
+use domain::job::field::{ManifestPolicy, ShortCodeConfig};
 use domain::maintenance::Maintenance;
+use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket::{Build, Rocket};
+use std::net::SocketAddr;
+use web::admin::{AdminToken, BackupDirectory};
 use web::renderer::Renderer;
 use web::responsecounter::ResponseCounter;
+use web::CookieConfig;
 
 /// Creates a new Rocket build that is configured for running JobStash.
 pub fn rocket(config: RocketConfig) -> Rocket<Build> {
-    rocket::build()
+    let figment = rocket::Config::figment()
+        .merge(("address", config.address.ip()))
+        .merge(("port", config.address.port()));
+    rocket::custom(figment)
         .manage::<AppDatabase>(config.database)
         .manage::<Renderer>(config.renderer)
         .manage::<ResponseCounter>(config.response_counter)
         .manage::<Maintenance>(config.maintenance)
+        .manage::<AdminToken>(config.admin_token)
+        .manage::<CookieConfig>(config.cookies)
+        .manage::<BackupDirectory>(config.backup_directory)
+        .manage::<ShortCodeConfig>(config.shortcodes)
+        .manage::<ManifestPolicy>(config.manifests)
+        .manage::<WebhookPolicy>(config.webhooks)
         .mount("/", web::http::routes())
+        .mount("/", web::status::probe_routes())
         .mount("/api/job", web::api::routes())
+        .mount("/api/admin", web::admin::routes())
+        .mount("/api/v1", web::status::routes())
+        .mount("/api/v1", web::api::v1_routes())
         .mount("/static", FileServer::from("static"))
         .register("/", web::http::catcher::catchers())
         .register("/api/job", web::api::catcher::catchers())
+        .register("/api/admin", web::api::catcher::catchers())
+        .register("/api/v1", web::api::catcher::catchers())
+        .attach(web::trace::RequestTracing)
+        .attach(web::status::RequestMetrics)
+        .attach(AdHoc::on_shutdown("Flush response counter", |rocket| {
+            Box::pin(async move {
+                if let Some(counter) = rocket.state::<ResponseCounter>() {
+                    counter.shutdown().await;
+                }
+            })
+        }))
 }
 
 /// Data needed to set up exchange with Rocket.
@@ -38,16 +71,54 @@ pub struct RocketConfig {
     pub database: AppDatabase,
     pub response_counter: ResponseCounter,
     pub maintenance: Maintenance,
+    pub admin_token: AdminToken,
+    pub cookies: CookieConfig,
+    pub backup_directory: BackupDirectory,
+    pub shortcodes: ShortCodeConfig,
+    pub manifests: ManifestPolicy,
+    pub webhooks: WebhookPolicy,
+    pub address: SocketAddr,
 }
 
 #[cfg(test)]
 pub mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
     pub fn async_runtime() -> tokio::runtime::Runtime {
         tokio::runtime::Builder::new_multi_thread()
             .worker_threads(1)
             .max_blocking_threads(1)
-            .enable_time()
+            .enable_all()
             .build()
             .expect("failed to spawn tokio runtime")
     }
+
+    /// Serve each response once, in order, returning the base URL of the
+    /// server and the request lines it receives.
+    pub fn serve(responses: Vec<String>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let len = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..len]);
+                let _ = tx.send(request.lines().next().unwrap_or_default().to_owned());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    pub fn ok(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
 }
@@ -1,42 +1,143 @@
 //! Actions that the service may perform.
 
-use crate::data::graph::{fetch_new_jobs_from_graph, get_escrows_from_graph};
-use crate::data::{query, DatabasePool, Transaction};
+use crate::data::graph::{fetch_new_jobs_from_graph, get_escrows_from_graph, GraphConfig};
+use crate::data::snapshot::{self, SnapshotHeader};
+use crate::data::{manifest, model, webhook};
+use crate::data::{migration, query, Conn, DataError, DatabasePool, DbId, Transaction};
+use crate::domain::alert::{self, JobAlert, SavedSearch, WebhookResult};
+use crate::domain::board::JobListing;
+use crate::domain::claim::{self, Claim, ClaimError};
+use crate::domain::health::{JobCounts, Readiness, SyncProgress};
+use crate::domain::job::field::{self, ShortCodeConfig};
+use crate::domain::stats::{Channel, ResponseBreakdown, ResponseSeries};
 use crate::service::ask;
 use crate::web::api::ApiKey;
-use crate::{Job, ServiceError, ShortCode};
+use crate::{Job, ServiceError, ShortCode, Time};
 use std::convert::TryInto;
+use std::path::{Path, PathBuf};
 
 /// Begins a new [`Transaction`].
-pub async fn begin_transaction(pool: &DatabasePool) -> Result<Transaction<'_>, ServiceError> {
+pub async fn begin_transaction(pool: &DatabasePool) -> Result<Transaction, ServiceError> {
     Ok(pool.begin().await?)
 }
 
 /// Commits a [`Transaction`].
-pub async fn end_transaction(transaction: Transaction<'_>) -> Result<(), ServiceError> {
+pub async fn end_transaction(transaction: Transaction) -> Result<(), ServiceError> {
     Ok(transaction.commit().await?)
 }
 
-/// Increases the number of responses for a [`Job`].
-pub async fn increase_hit_count(
+/// Increases the number of responses for each [`Job`].
+pub async fn increase_hit_counts<'c>(
+    hits: &[(ShortCode, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<(), ServiceError> {
+    Ok(query::increase_hit_counts(hits, conn).await?)
+}
+
+/// Adds responses to the hourly buckets of each [`Job`].
+pub async fn record_response_stats<'c>(
+    stats: &[(ShortCode, i64, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<(), ServiceError> {
+    Ok(query::record_response_stats(stats, conn).await?)
+}
+
+/// Merges hourly response buckets which start before `before` into daily buckets.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn rollup_response_stats(before: i64, pool: &DatabasePool) -> Result<u64, ServiceError> {
+    Ok(query::rollup_response_stats(before, pool).await?)
+}
+
+/// Deletes the visitors recorded for days which start before `before`.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn delete_old_visitors(before: i64, pool: &DatabasePool) -> Result<u64, ServiceError> {
+    Ok(query::delete_old_visitors(before, pool).await?)
+}
+
+/// Adds responses to the count for the [`Channel`] used to access each [`Job`].
+pub async fn record_channel_responses<'c>(
+    channels: &[(ShortCode, Channel, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<(), ServiceError> {
+    Ok(query::record_channel_responses(channels, conn).await?)
+}
+
+/// Adds responses to the count for the [`ApiKey`] that fetched each [`Job`].
+pub async fn record_api_key_responses<'c>(
+    api_keys: &[(ShortCode, ApiKey, u32)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<(), ServiceError> {
+    Ok(query::record_api_key_responses(api_keys, conn).await?)
+}
+
+/// Records that each visitor accessed a [`Job`] on a day.
+pub async fn record_visitors<'c>(
+    visitors: &[(ShortCode, i64, String)],
+    conn: impl Into<Conn<'c>>,
+) -> Result<(), ServiceError> {
+    Ok(query::record_visitors(visitors, conn).await?)
+}
+
+/// Gets the responses for a [`Job`], split by [`Channel`].
+#[tracing::instrument(level = "debug", skip_all, fields(shortcode = shortcode.as_str()))]
+pub async fn get_response_breakdown(
     shortcode: &ShortCode,
-    responses: u32,
     pool: &DatabasePool,
-) -> Result<(), ServiceError> {
-    Ok(query::increase_hit_count(shortcode, responses, pool).await?)
+) -> Result<ResponseBreakdown, ServiceError> {
+    Ok(query::get_response_breakdown(shortcode, pool)
+        .await?
+        .try_into()?)
 }
 
-/// Creates a new [`Job`].
-pub async fn new_job(req: ask::NewJob, pool: &DatabasePool) -> Result<Job, ServiceError> {
-    Ok(query::new_job(req, pool).await?.try_into()?)
+/// Gets the response time series for a [`Job`], or for all jobs.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_response_stats(
+    req: ask::GetResponseStats,
+    pool: &DatabasePool,
+) -> Result<ResponseSeries, ServiceError> {
+    let granularity = req.granularity;
+    let buckets = query::get_response_stats(req, pool)
+        .await?
+        .into_iter()
+        .map(|bucket| bucket.try_into())
+        .collect::<Result<_, _>>()?;
+    Ok(ResponseSeries {
+        granularity,
+        buckets,
+    })
+}
+
+/// Creates a new [`Job`], once its manifest URL is allowed by the `policy`.
+#[tracing::instrument(level = "debug", skip(shortcodes, policy, pool))]
+pub async fn new_job(
+    req: ask::NewJob,
+    shortcodes: &ShortCodeConfig,
+    policy: &field::ManifestPolicy,
+    pool: &DatabasePool,
+) -> Result<Job, ServiceError> {
+    let req = ask::NewJob {
+        manifest_url: req.manifest_url.check(policy)?,
+        ..req
+    };
+    Ok(query::new_job(req, shortcodes, pool).await?.try_into()?)
 }
 
-/// Updates an existing [`Job`].
-pub async fn update_job(req: ask::UpdateJob, pool: &DatabasePool) -> Result<Job, ServiceError> {
+/// Updates an existing [`Job`], once its manifest URL is allowed by the `policy`.
+#[tracing::instrument(level = "debug", skip(policy, pool))]
+pub async fn update_job(
+    req: ask::UpdateJob,
+    policy: &field::ManifestPolicy,
+    pool: &DatabasePool,
+) -> Result<Job, ServiceError> {
+    let req = ask::UpdateJob {
+        manifest_url: req.manifest_url.check(policy)?,
+        ..req
+    };
     Ok(query::update_job(req, pool).await?.try_into()?)
 }
 
 /// Gets a [`Job`].
+#[tracing::instrument(level = "debug", skip_all, fields(job = ?req.job))]
 pub async fn get_job(req: ask::GetJob, pool: &DatabasePool) -> Result<Job, ServiceError> {
     let user_password = req.password.clone();
     let job: Job = query::get_job(req, pool).await?.try_into()?;
@@ -51,33 +152,482 @@ pub async fn get_job(req: ask::GetJob, pool: &DatabasePool) -> Result<Job, Servi
     }
 }
 
-/// Downloads jobs from the Graph.
-pub async fn download_graph_jobs(pool: &DatabasePool) -> Result<(), ServiceError> {
-    let req = get_escrows_from_graph().await.unwrap();
-    for job in req {
-        let _ = query::new_job(job, pool).await?;
+/// Gets the [`ShortCode`] of the most recent [`Job`] with the escrow address.
+///
+/// The password is not checked, since the shortcode alone does not give access to the job.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn get_shortcode(
+    escrow_id: field::EscrowId,
+    pool: &DatabasePool,
+) -> Result<ShortCode, ServiceError> {
+    let req = ask::GetJob {
+        job: escrow_id.into(),
+        password: field::Password::default(),
+    };
+    let job: Job = query::get_job(req, pool).await?.try_into()?;
+    Ok(job.shortcode)
+}
+
+/// Downloads jobs from the Graph, returning the number of jobs added.
+///
+/// Saved searches are not alerted, since these jobs were posted before the
+/// database was seeded rather than just now.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn download_graph_jobs(
+    config: &GraphConfig,
+    shortcodes: &ShortCodeConfig,
+    tokens: &field::TokenList,
+    progress: &SyncProgress,
+    pool: &DatabasePool,
+) -> Result<usize, ServiceError> {
+    let page = get_escrows_from_graph(config).await?;
+    if let Some(block) = page.block {
+        progress.indexed(block);
+    }
+    let count = page.jobs.len();
+    for job in page.jobs {
+        let job = model::NewJob::from_graph(job, tokens);
+        query::new_job(job, shortcodes, pool).await?;
     }
-    Ok(())
+    if let Some(block) = page.block {
+        progress.synced(block);
+    }
+    Ok(count)
 }
 
-/// Fetches latest jobs from the Graph.
+/// Fetches latest jobs from the Graph, returning the number of jobs added.
+///
+/// Each job is added together with the alerts for the saved searches it matches.
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn fetch_and_insert_new_jobs(
+    config: &GraphConfig,
+    shortcodes: &ShortCodeConfig,
+    tokens: &field::TokenList,
+    progress: &SyncProgress,
+    pool: &DatabasePool,
+) -> Result<usize, ServiceError> {
+    let page = fetch_new_jobs_from_graph(config, pool).await?;
+    if let Some(block) = page.block {
+        progress.indexed(block);
+    }
+    let count = page.jobs.len();
+    for job in page.jobs {
+        let job = model::NewJob::from_graph(job, tokens);
+        let mut transaction = begin_transaction(pool).await?;
+        let job = query::new_job(job, shortcodes, &mut transaction).await?;
+        alert_saved_searches(&job.try_into()?, &mut transaction).await?;
+        end_transaction(transaction).await?;
+    }
+    if let Some(block) = page.block {
+        progress.synced(block);
+    }
+    Ok(count)
+}
+
+/// Brings the jobs up to date with the Graph, returning the number of jobs added.
+///
+/// An empty database is seeded with a full download, otherwise only new jobs are fetched.
+/// The blocks indexed by the Graph are recorded in `progress`.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn sync_graph_jobs(
+    config: &GraphConfig,
+    shortcodes: &ShortCodeConfig,
+    tokens: &field::TokenList,
+    progress: &SyncProgress,
+    pool: &DatabasePool,
+) -> Result<usize, ServiceError> {
+    match query::get_last_fetched_escrow_id_time(pool).await? {
+        None => download_graph_jobs(config, shortcodes, tokens, progress, pool).await,
+        Some(_) => fetch_and_insert_new_jobs(config, shortcodes, tokens, progress, pool).await,
+    }
+}
+
+/// The most manifests checked by each run of [`verify_manifests`].
+const MANIFEST_BATCH: i64 = 25;
+
+/// Seconds to wait before checking an unavailable manifest again.
+const MANIFEST_RETRY_SECS: i64 = 60 * 60;
+
+/// Checks pending manifests against the hashes recorded by their escrows,
+/// returning the status of each manifest checked.
+///
+/// Manifests which do not match are flagged, and manifests which can not be
+/// fetched are checked again after an hour. Every other manifest is classified
+/// by its task type, and its reward terms and searchable text are recorded,
+/// including manifests which have no hash to check. Saved searches are then
+/// matched again, since they may only match now that the manifest is known.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn verify_manifests(
+    policy: &field::ManifestPolicy,
+    pool: &DatabasePool,
+) -> Result<Vec<field::ManifestStatus>, ServiceError> {
+    let retry_before = Time::now().timestamp() - MANIFEST_RETRY_SECS;
+    let pending = query::get_pending_manifests(retry_before, MANIFEST_BATCH, pool).await?;
+    let mut statuses = Vec::with_capacity(pending.len());
+    for pending in pending {
+        let pending: field::PendingManifest = pending.try_into()?;
+        let (status, manifest) = match manifest::fetch(
+            &pending.manifest_url,
+            pending.hash.as_ref(),
+            policy,
+        )
+        .await
+        {
+            Ok(manifest) => {
+                let status = match pending.hash {
+                    Some(_) => field::ManifestStatus::Verified,
+                    None => field::ManifestStatus::Unverifiable,
+                };
+                (status, Some(manifest))
+            }
+            Err(e @ DataError::ManifestHashMismatch { .. }) => {
+                tracing::warn!(manifest_url = %pending.manifest_url, error = %e, "manifest does not match its escrow");
+                (field::ManifestStatus::Flagged, None)
+            }
+            Err(e) => {
+                tracing::debug!(manifest_url = %pending.manifest_url, error = %e, "manifest is unavailable");
+                // Without a hash there is nothing to verify, so the manifest is not retried.
+                let status = match pending.hash {
+                    Some(_) => field::ManifestStatus::Unavailable,
+                    None => field::ManifestStatus::Unverifiable,
+                };
+                (status, None)
+            }
+        };
+        let terms = manifest
+            .as_deref()
+            .map(field::ManifestTerms::from_manifest)
+            .unwrap_or_default();
+        let check = field::ManifestCheck {
+            job_id: pending.job_id.clone(),
+            status,
+            task_type: manifest.as_deref().and_then(field::TaskType::from_manifest),
+            task_bid: terms.task_bid(pending.decimals),
+            total_tasks: terms.total_tasks,
+            search_text: manifest.as_deref().map(field::SearchText::from_manifest),
+            checked: Time::now(),
+        };
+        query::record_manifest_check(check, pool).await?;
+        if manifest.is_some() && status != field::ManifestStatus::Flagged {
+            let job: Job = query::get_job(pending.job_id, pool).await?.try_into()?;
+            alert_saved_searches(&job, pool).await?;
+        }
+        statuses.push(status);
+    }
+    Ok(statuses)
+}
+
+/// Gets a page of the job board, with the number of jobs of each task type.
+///
+/// When the board is searched, only the jobs which match are counted.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn list_jobs(
+    req: ask::ListJobs,
+    pool: &DatabasePool,
+) -> Result<JobListing, ServiceError> {
+    let search = req.search.clone();
+    let jobs = query::list_jobs(req, pool)
+        .await?
+        .into_iter()
+        .map(|job| job.try_into())
+        .collect::<Result<_, _>>()?;
+    let facets = query::count_task_types(search.as_ref(), pool)
+        .await?
+        .into_iter()
+        .map(|count| count.try_into())
+        .collect::<Result<_, _>>()?;
+    Ok(JobListing { jobs, facets })
+}
+
+/// Saves a search for an [`ApiKey`], which is alerted about new jobs that match it.
+///
+/// Its webhook URL, if any, must be allowed by the `policy`.
+#[tracing::instrument(level = "debug", skip(api_key, policy, pool))]
+pub async fn save_search(
+    api_key: ApiKey,
+    req: ask::NewSavedSearch,
+    policy: &alert::WebhookPolicy,
+    pool: &DatabasePool,
+) -> Result<SavedSearch, ServiceError> {
+    let req = ask::NewSavedSearch {
+        network: req
+            .network
+            .as_deref()
+            .map(alert::check_network)
+            .transpose()?,
+        min_task_bid: req
+            .min_task_bid
+            .as_deref()
+            .map(alert::check_min_task_bid)
+            .transpose()?,
+        webhook_url: req
+            .webhook_url
+            .as_deref()
+            .map(|url| alert::WebhookUrl::new(url, policy).map(alert::WebhookUrl::into_inner))
+            .transpose()?,
+        ..req
+    };
+    let max = i64::from(alert::MAX_SAVED_SEARCHES);
+    match query::save_search(model::NewSavedSearch::new(api_key, req), max, pool).await? {
+        Some(search) => Ok(search.try_into()?),
+        None => Err(alert::AlertError::TooManySearches(alert::MAX_SAVED_SEARCHES).into()),
+    }
+}
+
+/// Gets the saved searches of an [`ApiKey`].
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn list_saved_searches(
+    api_key: ApiKey,
+    pool: &DatabasePool,
+) -> Result<Vec<SavedSearch>, ServiceError> {
+    Ok(query::get_saved_searches(api_key, pool)
+        .await?
+        .into_iter()
+        .map(|search| search.try_into())
+        .collect::<Result<_, _>>()?)
+}
+
+/// Deletes a saved search of an [`ApiKey`], along with its alerts.
+#[tracing::instrument(level = "debug", skip(api_key, pool))]
+pub async fn delete_saved_search(
+    api_key: ApiKey,
+    search_id: DbId,
     pool: &DatabasePool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let new_jobs = fetch_new_jobs_from_graph(pool).await?;
-    for job in new_jobs {
-        let _ = query::new_job(job, pool).await?;
+) -> Result<(), ServiceError> {
+    let search_id: String = search_id.into();
+    match query::delete_saved_search(&search_id, api_key, pool).await? {
+        true => Ok(()),
+        false => Err(ServiceError::NotFound),
+    }
+}
+
+/// Alerts each saved search which matches the [`Job`], returning the number of new alerts.
+///
+/// A search is only alerted about each job once, however often the job is matched.
+#[tracing::instrument(level = "debug", skip_all, fields(shortcode = job.shortcode.as_str()))]
+pub async fn alert_saved_searches<'c>(
+    job: &Job,
+    conn: impl Into<Conn<'c>>,
+) -> Result<usize, ServiceError> {
+    if job.password.has_password() {
+        return Ok(0);
+    }
+    let mut conn = conn.into();
+    let task_type = job.task_type.map(|task_type| task_type.as_str());
+    let mut alerted = 0;
+    for search in query::get_candidate_searches(task_type, conn.reborrow()).await? {
+        let search: SavedSearch = search.try_into()?;
+        if search.matches(job)
+            && query::new_job_alert(model::NewJobAlert::new(&search, job), conn.reborrow()).await?
+        {
+            alerted += 1;
+        }
     }
-    Ok(())
+    Ok(alerted)
+}
+
+/// Gets up to `limit` alerts for the saved searches of an [`ApiKey`] which
+/// come after the alert `after`, oldest first.
+#[tracing::instrument(level = "debug", skip(api_key, pool))]
+pub async fn get_job_alerts(
+    api_key: ApiKey,
+    after: i64,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<JobAlert>, ServiceError> {
+    let limit = limit.min(crate::domain::board::MAX_LIMIT);
+    Ok(
+        query::get_job_alerts(api_key, after, i64::from(limit), pool)
+            .await?
+            .into_iter()
+            .map(|alert| alert.try_into())
+            .collect::<Result<_, _>>()?,
+    )
+}
+
+/// Gets the id of the newest alert for the saved searches of an [`ApiKey`].
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn get_latest_alert_id(
+    api_key: ApiKey,
+    pool: &DatabasePool,
+) -> Result<i64, ServiceError> {
+    Ok(query::get_latest_alert_id(api_key, pool).await?)
+}
+
+/// The most webhooks sent by each run of [`deliver_webhooks`].
+const WEBHOOK_BATCH: i64 = 25;
+
+/// Posts each alert which is due to its search's webhook, returning the result of each.
+///
+/// Webhooks which fail are sent again with a growing delay, until they have
+/// been sent [`MAX_WEBHOOK_ATTEMPTS`](alert::MAX_WEBHOOK_ATTEMPTS) times.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn deliver_webhooks(
+    policy: &alert::WebhookPolicy,
+    pool: &DatabasePool,
+) -> Result<Vec<WebhookResult>, ServiceError> {
+    let now = Time::now().timestamp();
+    let pending = query::get_pending_webhooks(now, WEBHOOK_BATCH, pool).await?;
+    let mut results = Vec::with_capacity(pending.len());
+    for pending in pending {
+        let pending: alert::PendingWebhook = pending.try_into()?;
+        let job: Job = query::get_job(pending.job_id.clone(), pool)
+            .await?
+            .try_into()?;
+        let body = JobAlert {
+            alert_id: pending.alert_id,
+            search_id: pending.search_id.clone(),
+            job,
+            created: pending.created.clone(),
+            delivered: None,
+        };
+        let (result, delivered, next_attempt) =
+            match webhook::post(&pending.webhook_url, &body, policy).await {
+                Ok(()) => (
+                    WebhookResult::Delivered,
+                    Some(Time::now().timestamp()),
+                    None,
+                ),
+                Err(e) => {
+                    tracing::debug!(alert_id = pending.alert_id, error = %e, "webhook failed");
+                    match pending.retry_delay() {
+                        Some(delay) => (
+                            WebhookResult::Retried,
+                            None,
+                            Some(now + delay.as_secs() as i64),
+                        ),
+                        None => (WebhookResult::Abandoned, None, None),
+                    }
+                }
+            };
+        query::record_webhook_attempt(pending.alert_id, delivered, next_attempt, pool).await?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Claims a task of a [`Job`] for an [`ApiKey`], reserving it until the lease runs out.
+///
+/// Password protected jobs require their password. A job can have as many
+/// claims as the `total_tasks` of its manifest, so jobs whose manifest has not
+/// been read can not be claimed.
+#[tracing::instrument(level = "debug", skip(api_key, pool), fields(job = ?req.job.job))]
+pub async fn claim_task(
+    api_key: ApiKey,
+    req: ask::ClaimTask,
+    pool: &DatabasePool,
+) -> Result<Claim, ServiceError> {
+    let lease = claim::lease(req.lease_secs)?;
+    let job = get_job(req.job, pool).await?;
+    let now = Time::now().timestamp();
+    if let Some(expires) = job.expires.clone().into_inner() {
+        if expires.timestamp() <= now {
+            return Err(ClaimError::JobExpired.into());
+        }
+    }
+    let total_tasks = job
+        .reward
+        .total_tasks
+        .ok_or(ClaimError::UnknownTotalTasks)?;
+    match query::new_claim(model::NewClaim::new(api_key, &job, lease), now, pool).await? {
+        Some(claim) => Ok(claim.try_into()?),
+        None => Err(ClaimError::AllTasksClaimed(total_tasks).into()),
+    }
+}
+
+/// Renews a claim of an [`ApiKey`], so its lease runs out `lease_secs` from now.
+///
+/// Claims whose lease has already run out can not be renewed, since their task may have been claimed again.
+#[tracing::instrument(level = "debug", skip(api_key, pool))]
+pub async fn renew_claim(
+    api_key: ApiKey,
+    req: ask::RenewClaim,
+    pool: &DatabasePool,
+) -> Result<Claim, ServiceError> {
+    let lease = claim::lease(req.lease_secs)?;
+    let now = Time::now().timestamp();
+    let claim_id: String = req.claim_id.into();
+    let expires = now + lease.as_secs() as i64;
+    if !query::renew_claim(
+        &claim_id,
+        req.shortcode.as_str(),
+        api_key.clone(),
+        now,
+        expires,
+        pool,
+    )
+    .await?
+    {
+        return Err(ServiceError::NotFound);
+    }
+    match query::get_claim(&claim_id, api_key, now, pool).await? {
+        Some(claim) => Ok(claim.try_into()?),
+        None => Err(ServiceError::NotFound),
+    }
+}
+
+/// Releases a claim of an [`ApiKey`] before its lease runs out, freeing its task.
+#[tracing::instrument(level = "debug", skip(api_key, pool))]
+pub async fn release_claim(
+    api_key: ApiKey,
+    shortcode: ShortCode,
+    claim_id: DbId,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let claim_id: String = claim_id.into();
+    let now = Time::now().timestamp();
+    match query::release_claim(&claim_id, shortcode.as_str(), api_key, now, pool).await? {
+        true => Ok(()),
+        false => Err(ServiceError::NotFound),
+    }
+}
+
+/// Deletes the claims whose lease has run out, returning the number deleted.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn delete_expired_claims(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    Ok(query::delete_expired_claims(Time::now().timestamp(), pool).await?)
+}
+
+/// Checks whether the database is reachable and has every migration applied.
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn check_readiness(pool: &DatabasePool) -> Readiness {
+    let database = query::ping(pool).await.is_ok();
+    let pending_migrations = if database {
+        migration::pending(pool).await.unwrap_or_default()
+    } else {
+        pool.migrator()
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect()
+    };
+    Readiness {
+        database,
+        pending_migrations,
+    }
+}
+
+/// Counts the stored [`Jobs`](`Job`).
+pub async fn get_job_counts(pool: &DatabasePool) -> Result<JobCounts, ServiceError> {
+    Ok(query::count_jobs(pool).await?.try_into()?)
+}
+
+/// Gets when the most recent [`Job`] was posted.
+pub async fn get_latest_posted(pool: &DatabasePool) -> Result<Option<Time>, ServiceError> {
+    Ok(query::get_last_fetched_escrow_id_time(pool)
+        .await?
+        .and_then(Time::from_timestamp))
 }
 
 /// Creates a new [`ApiKey`].
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
     let api_key = ApiKey::default();
     Ok(query::save_api_key(api_key, pool).await?)
 }
 
 /// Revokes an existing [`ApiKey`].
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn revoke_api_key(
     api_key: ApiKey,
     pool: &DatabasePool,
@@ -86,11 +636,47 @@ pub async fn revoke_api_key(
 }
 
 /// Determines if an [`ApiKey`] is valid.
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool, ServiceError> {
     Ok(query::api_key_is_valid(api_key, pool).await?)
 }
 
 /// Deletes all expired [`Jobs`](`Job`).
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn delete_expired(pool: &DatabasePool) -> Result<u64, ServiceError> {
     Ok(query::delete_expired(pool).await?)
 }
+
+/// Updates the query planner statistics and releases unused space.
+pub async fn optimize_database(pool: &DatabasePool) -> Result<(), ServiceError> {
+    Ok(query::optimize(pool).await?)
+}
+
+/// Writes a [`snapshot`] of the database to a new file at `path`.
+pub async fn create_snapshot(
+    path: &Path,
+    pool: &DatabasePool,
+) -> Result<SnapshotHeader, ServiceError> {
+    Ok(snapshot::create(pool, path).await?)
+}
+
+/// Writes a [`snapshot`] of the database to a new, timestamped file in `directory`,
+/// returning the path of the file.
+pub async fn create_snapshot_in(
+    directory: &Path,
+    pool: &DatabasePool,
+) -> Result<(PathBuf, SnapshotHeader), ServiceError> {
+    std::fs::create_dir_all(directory).map_err(DataError::from)?;
+    let name = format!("gptx-{}.db", chrono::Utc::now().format("%Y%m%dT%H%M%S%3fZ"));
+    let path = directory.join(name);
+    let header = create_snapshot(&path, pool).await?;
+    Ok((path, header))
+}
+
+/// Replaces the contents of the database with the [`snapshot`] at `path`.
+pub async fn restore_snapshot(
+    path: &Path,
+    pool: &DatabasePool,
+) -> Result<SnapshotHeader, ServiceError> {
+    Ok(snapshot::restore(pool, path).await?)
+}
@@ -1,34 +1,76 @@
 //! Data structures to make a service request.
 
+use crate::domain::board::{JobSort, SearchQuery};
 use crate::domain::job::field;
-use crate::ShortCode;
+use crate::domain::stats::Granularity;
+use crate::{JobError, ShortCode};
 
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 /// Data required to run the [`new_job`](crate::service::action::new_job()) action to add a new [`crate::domain::Job`].
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NewJob {
     pub escrow_id: field::EscrowId,
+    /// Checked against the [`ManifestPolicy`](field::ManifestPolicy) by the action.
     pub manifest_url: field::ManifestUrl,
     pub posted: field::Posted,
     pub expires: field::Expires,
     pub password: field::Password,
+    /// A custom shortcode. One is generated when this is empty.
+    #[serde(default)]
+    pub shortcode: field::VanityCode,
 }
 
 /// Data required to run the [`update_job`](crate::service::action::update_job()) action to update [`crate::domain::Job`] data.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct UpdateJob {
     pub escrow_id: field::EscrowId,
+    /// Checked against the [`ManifestPolicy`](field::ManifestPolicy) by the action.
     pub manifest_url: field::ManifestUrl,
     pub expires: field::Expires,
     pub password: field::Password,
     pub shortcode: ShortCode,
 }
 
+/// Identifies a [`crate::domain::Job`] by its shortcode, or by its escrow address.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobRef {
+    ShortCode(ShortCode),
+    EscrowId(field::EscrowId),
+}
+
+/// Escrow addresses are [`JobRef::EscrowId`], and anything else is a [`JobRef::ShortCode`].
+///
+/// Shortcodes are never long enough to be mistaken for an address.
+impl FromStr for JobRef {
+    type Err = JobError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match field::EscrowId::new(s) {
+            Ok(escrow_id) => Ok(Self::EscrowId(escrow_id)),
+            Err(e @ JobError::InvalidEscrowChecksum(_)) => Err(e),
+            Err(_) => Ok(Self::ShortCode(ShortCode::from_str(s)?)),
+        }
+    }
+}
+
+impl From<ShortCode> for JobRef {
+    fn from(shortcode: ShortCode) -> Self {
+        Self::ShortCode(shortcode)
+    }
+}
+
+impl From<field::EscrowId> for JobRef {
+    fn from(escrow_id: field::EscrowId) -> Self {
+        Self::EscrowId(escrow_id)
+    }
+}
+
 /// Data required to run the [`get_job`](crate::service::action::get_job()) action to get a [`crate::domain::Job`].
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GetJob {
-    pub shortcode: ShortCode,
+    pub job: JobRef,
     pub password: field::Password,
 }
 
@@ -36,7 +78,7 @@ impl GetJob {
     /// Convert a [`&str`] into a [`GetJob`] action request.
     pub fn from_raw(shortcode: &str) -> Self {
         Self {
-            shortcode: ShortCode::from(shortcode),
+            job: ShortCode::from(shortcode).into(),
             password: field::Password::default(),
         }
     }
@@ -45,7 +87,7 @@ impl GetJob {
 impl From<ShortCode> for GetJob {
     fn from(shortcode: ShortCode) -> Self {
         Self {
-            shortcode,
+            job: shortcode.into(),
             password: field::Password::default(),
         }
     }
@@ -56,3 +98,79 @@ impl From<&str> for GetJob {
         Self::from_raw(raw)
     }
 }
+
+/// Data required to run the [`get_response_stats`](crate::service::action::get_response_stats()) action to get a response time series.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetResponseStats {
+    /// The job to report on. All jobs are combined when this is `None`.
+    pub shortcode: Option<ShortCode>,
+    pub granularity: Granularity,
+    /// The number of days of history to include.
+    pub days: u32,
+}
+
+/// Data required to run the [`list_jobs`](crate::service::action::list_jobs()) action to get a page of the job board.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListJobs {
+    /// Only list jobs with this task type.
+    pub task_type: Option<field::TaskType>,
+    /// Only list jobs which match this search.
+    pub search: Option<SearchQuery>,
+    /// The order to list jobs in. By default, searches list the best matches
+    /// first and other listings list the most recently posted jobs first.
+    pub sort: Option<JobSort>,
+    /// The most jobs to list, up to [`MAX_LIMIT`](crate::domain::board::MAX_LIMIT).
+    pub limit: u32,
+    /// The number of jobs to skip.
+    pub offset: u32,
+}
+
+/// The Default implementation is the first page of the whole board.
+impl Default for ListJobs {
+    fn default() -> Self {
+        Self {
+            task_type: None,
+            search: None,
+            sort: None,
+            limit: crate::domain::board::DEFAULT_LIMIT,
+            offset: 0,
+        }
+    }
+}
+
+/// Data required to run the [`save_search`](crate::service::action::save_search()) action to add a new [`SavedSearch`](crate::domain::alert::SavedSearch).
+///
+/// Every criterion is optional, and a search without any matches every public job.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewSavedSearch {
+    /// Only match jobs read from the subgraph of this network.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Only match jobs with this task type.
+    #[serde(default)]
+    pub task_type: Option<field::TaskType>,
+    /// Only match jobs paying at least this much per task, in whole tokens.
+    #[serde(default)]
+    pub min_task_bid: Option<String>,
+    /// Post each alert to this URL, as well as listing it.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+/// Data required to run the [`claim_task`](crate::service::action::claim_task()) action to reserve a task slot of a [`crate::domain::Job`].
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClaimTask {
+    /// The job to claim, with its password when it is protected.
+    pub job: GetJob,
+    /// How long the claim lasts, defaulting to [`DEFAULT_LEASE_SECS`](crate::domain::claim::DEFAULT_LEASE_SECS).
+    pub lease_secs: Option<u32>,
+}
+
+/// Data required to run the [`renew_claim`](crate::service::action::renew_claim()) action to extend a [`Claim`](crate::domain::claim::Claim).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenewClaim {
+    pub shortcode: ShortCode,
+    pub claim_id: crate::data::DbId,
+    /// How long the claim lasts from now, defaulting to [`DEFAULT_LEASE_SECS`](crate::domain::claim::DEFAULT_LEASE_SECS).
+    pub lease_secs: Option<u32>,
+}
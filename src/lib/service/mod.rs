@@ -3,6 +3,9 @@
 pub mod action;
 pub mod ask;
 
+use crate::domain::alert::AlertError;
+use crate::domain::claim::ClaimError;
+use crate::domain::stats::StatsError;
 use crate::{DataError, JobError};
 
 /// The possible errors that can occur when working with the [`service layer`](crate::service).
@@ -11,12 +14,24 @@ pub enum ServiceError {
     /// A job error.
     #[error("job error: {0}")]
     Job(#[from] JobError),
+    /// A saved search error.
+    #[error("alert error: {0}")]
+    Alert(#[from] AlertError),
+    /// A task claim error.
+    #[error("claim error: {0}")]
+    Claim(#[from] ClaimError),
+    /// A response statistics error.
+    #[error("stats error: {0}")]
+    Stats(#[from] StatsError),
     /// A database error.
     #[error("database error: {0}")]
     Data(DataError),
     /// Data not found.
     #[error("not found")]
     NotFound,
+    /// The request conflicts with existing data.
+    #[error("conflict: {0}")]
+    Conflict(String),
     /// Password does not match for password protected [`Job`](crate::domain::Job).
     #[error("permissions not met: {0}")]
     PermissionError(String),
@@ -29,6 +44,8 @@ impl From<DataError> for ServiceError {
                 sqlx::Error::RowNotFound => Self::NotFound,
                 other => Self::Data(DataError::Database(other)),
             },
+            DataError::ShortCodeTaken(_) => Self::Conflict(err.to_string()),
+            other => Self::Data(other),
         }
     }
 }
@@ -0,0 +1,215 @@
+//! Administrative API routes.
+
+use crate::data::snapshot::SnapshotHeader;
+use crate::data::AppDatabase;
+use crate::domain::maintenance::Maintenance;
+use crate::domain::supervisor::TaskStatus;
+use crate::service::action;
+use crate::web::api::ApiError;
+use crate::web::trace::RequestId;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// HTTP request header name to include the admin token.
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// The token required to access the admin routes.
+///
+/// When no token is configured, every admin request is forbidden.
+#[derive(Clone, Default)]
+pub struct AdminToken(Option<String>);
+
+/// The token itself is never included in debug output, so that it cannot end up in logs.
+impl std::fmt::Debug for AdminToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("AdminToken(<redacted>)"),
+            None => f.write_str("AdminToken(None)"),
+        }
+    }
+}
+
+impl AdminToken {
+    /// Create a new [`AdminToken`]. Empty tokens are treated as unset.
+    pub fn new(token: Option<String>) -> Self {
+        Self(token.filter(|token| !token.is_empty()))
+    }
+
+    /// Determines if `candidate` matches the configured token.
+    ///
+    /// The comparison takes the same time wherever the first mismatch is.
+    fn matches(&self, candidate: &str) -> bool {
+        match &self.0 {
+            None => false,
+            Some(token) => {
+                token.len() == candidate.len()
+                    && token
+                        .bytes()
+                        .zip(candidate.bytes())
+                        .fold(0, |acc, (a, b)| acc | (a ^ b))
+                        == 0
+            }
+        }
+    }
+}
+
+/// The directory where snapshots requested through [`create_backup`] are written.
+#[derive(Debug, Clone)]
+pub struct BackupDirectory(pub PathBuf);
+
+/// Uses `backups/` in the working directory.
+impl Default for BackupDirectory {
+    fn default() -> Self {
+        Self("backups/".into())
+    }
+}
+
+/// Proof that a request carries the admin token.
+pub struct Admin;
+
+/// Allows [`Admin`] to be used as a [request guard](https://rocket.rs/v0.5-rc/guide/requests/#request-guards) in a route.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        fn forbidden() -> Outcome<Admin, ApiError> {
+            ApiError::Forbidden(Json("admin token missing or invalid".to_string())).into_outcome()
+        }
+        let token = match req.guard::<&State<AdminToken>>().await {
+            Outcome::Success(token) => token,
+            _ => return forbidden(),
+        };
+        match req.headers().get_one(ADMIN_TOKEN_HEADER) {
+            Some(candidate) if token.matches(candidate) => Outcome::Success(Admin),
+            _ => forbidden(),
+        }
+    }
+}
+
+/// Route to retrieve the status of the background maintenance tasks.
+#[rocket::get("/tasks")]
+pub fn get_tasks(maintenance: &State<Maintenance>, _admin: Admin) -> Json<Vec<TaskStatus>> {
+    Json(maintenance.status())
+}
+
+/// A snapshot written by [`create_backup`].
+#[derive(Debug, Serialize)]
+pub struct Backup {
+    /// Where the snapshot was written.
+    pub path: PathBuf,
+    /// The schema version and creation time of the snapshot.
+    #[serde(flatten)]
+    pub header: SnapshotHeader,
+}
+
+/// Route to write a snapshot of the database to the [`BackupDirectory`].
+#[rocket::post("/backup")]
+#[tracing::instrument(name = "create_backup", skip_all, fields(request_id = %request_id))]
+pub async fn create_backup(
+    database: &State<AppDatabase>,
+    directory: &State<BackupDirectory>,
+    request_id: RequestId,
+    _admin: Admin,
+) -> Result<Json<Backup>, ApiError> {
+    let (path, header) = action::create_snapshot_in(&directory.0, database.get_pool()).await?;
+    tracing::info!(path = %path.display(), schema_version = header.schema_version, "created backup");
+    Ok(Json(Backup { path, header }))
+}
+
+/// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes!(get_tasks, create_backup)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AdminToken, ADMIN_TOKEN_HEADER};
+    use crate::web::test::{client, config};
+    use rocket::http::{Header, Status};
+
+    #[test]
+    fn token_must_match_exactly() {
+        let token = AdminToken::new(Some("secret".to_owned()));
+        assert!(token.matches("secret"));
+        assert!(!token.matches("secreT"));
+        assert!(!token.matches("secret2"));
+        assert!(!token.matches(""));
+        assert!(!AdminToken::new(Some(String::new())).matches(""));
+        assert!(!AdminToken::default().matches("secret"));
+    }
+
+    #[test]
+    fn tasks_require_admin_token() {
+        let rt = crate::test::async_runtime();
+        let mut config = config(rt.handle());
+        config.admin_token = AdminToken::new(Some("secret".to_owned()));
+        let client = client(config);
+
+        let res = client.get("/api/admin/tasks").dispatch();
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .get("/api/admin/tasks")
+            .header(Header::new(ADMIN_TOKEN_HEADER, "wrong"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .get("/api/admin/tasks")
+            .header(Header::new(ADMIN_TOKEN_HEADER, "secret"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let tasks: serde_json::Value = res.into_json().expect("task status is json");
+        let names: Vec<_> = tasks
+            .as_array()
+            .expect("task status is a list")
+            .iter()
+            .map(|task| task["name"].as_str().unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "alert_delivery",
+                "claim_expiry",
+                "expiry_purge",
+                "manifest_verify",
+                "optimize",
+                "stats_rollup",
+                "sync"
+            ]
+        );
+    }
+
+    #[test]
+    fn backups_require_admin_token() {
+        let (_rt, client) = crate::web::test::init_test_client();
+        let res = client.post("/api/admin/backup").dispatch();
+        assert_eq!(res.status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn api_keys_are_only_issued_to_admins() {
+        let rt = crate::test::async_runtime();
+        let mut config = config(rt.handle());
+        config.admin_token = AdminToken::new(Some("secret".to_owned()));
+        let client = client(config);
+
+        let res = client.get("/api/job/key").dispatch();
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .get("/api/job/key")
+            .header(Header::new(ADMIN_TOKEN_HEADER, "secret"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let body: serde_json::Value = res.into_json().expect("new key is json");
+        let api_key = body["api_key"].as_str().expect("key is returned");
+        assert!(
+            !format!("{:?}", api_key.parse::<crate::web::api::ApiKey>().unwrap()).contains(api_key)
+        );
+    }
+}
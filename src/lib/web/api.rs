@@ -1,17 +1,28 @@
 //! API routing, errors, and data structures.
 
-use crate::data::AppDatabase;
+use crate::data::{AppDatabase, DataError, DbId};
+use crate::domain::alert::{JobAlert, SavedSearch, WebhookPolicy};
+use crate::domain::board::{JobListing, JobSort, SearchQuery, DEFAULT_LIMIT};
+use crate::domain::claim::{Claim, ClaimError};
+use crate::domain::job::field::{EscrowId, ManifestPolicy, Password, ShortCodeConfig, TaskType};
+use crate::domain::stats::{Channel, Granularity, ResponseBreakdown, ResponseSeries};
+use crate::metrics;
 use crate::service;
 use crate::service::action;
+use crate::web::admin::Admin;
+use crate::web::responsecounter::{Hit, Visitor};
+use crate::web::trace::RequestId;
 use crate::web::{ResponseCounter, PASSWORD_COOKIE};
-use crate::ServiceError;
+use crate::{JobError, ServiceError, ShortCode};
 use rocket::http::{CookieJar, Status};
 use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::response::{self, Responder, Response};
 use rocket::serde::json::Json;
-use rocket::Responder;
-use rocket::State;
+use rocket::{Shutdown, State};
 use serde::Serialize;
 use std::str::FromStr;
+use std::time::Duration;
 
 /// HTTP request header name to include an API key.
 pub const API_KEY_HEADER: &str = "x-api-key";
@@ -30,9 +41,16 @@ pub enum ApiKeyError {
 }
 
 /// An API key that is used to access the API endpoints.
-#[derive(Debug, Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ApiKey(Vec<u8>);
 
+/// The key itself is never included in debug output, so that it cannot end up in logs.
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKey(<redacted>)")
+    }
+}
+
 impl ApiKey {
     /// Convert raw bytes of the [`ApiKey`]into a [`String`]
     pub fn to_base64(&self) -> String {
@@ -62,40 +80,125 @@ impl FromStr for ApiKey {
 }
 
 /// The possible errors that can occur when attempting to respond to a request.
-#[derive(Responder, Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error)]
 pub enum ApiError {
     /// Data not found.
     #[error("not found")]
-    #[response(status = 404, content_type = "json")]
     NotFound(Json<String>),
 
     /// Server error.
     #[error("server error")]
-    #[response(status = 500, content_type = "json")]
     Server(Json<String>),
 
     /// Invalid submission by client.
     #[error("client error")]
-    #[response(status = 401, content_type = "json")]
     User(Json<String>),
 
+    /// Malformed request.
+    #[error("bad request")]
+    BadRequest(Json<String>),
+
+    /// The request conflicts with existing data.
+    #[error("conflict")]
+    Conflict(Json<String>),
+
+    /// Missing or invalid admin token.
+    #[error("forbidden")]
+    Forbidden(Json<String>),
+
     /// Problem with the [`ApiKey`]
     #[error("key error")]
-    #[response(status = 400, content_type = "json")]
     KeyError(Json<ApiKeyError>),
 }
 
+impl ApiError {
+    /// The name of the variant, as used in metrics.
+    pub fn variant(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "not_found",
+            Self::Server(_) => "server",
+            Self::User(_) => "user",
+            Self::BadRequest(_) => "bad_request",
+            Self::Conflict(_) => "conflict",
+            Self::Forbidden(_) => "forbidden",
+            Self::KeyError(_) => "key_error",
+        }
+    }
+
+    /// The HTTP status of the response for this error.
+    pub fn status(&self) -> Status {
+        match self {
+            Self::NotFound(_) => Status::NotFound,
+            Self::Server(_) => Status::InternalServerError,
+            Self::User(_) => Status::Unauthorized,
+            Self::BadRequest(_) => Status::BadRequest,
+            Self::Conflict(_) => Status::Conflict,
+            Self::Forbidden(_) => Status::Forbidden,
+            Self::KeyError(_) => Status::BadRequest,
+        }
+    }
+
+    /// Fail a request guard with this error.
+    ///
+    /// The error is counted in the metrics, since Rocket hands failed guards to a
+    /// catcher rather than responding with the error itself.
+    pub fn into_outcome<T>(self) -> Outcome<T, Self> {
+        metrics::API_ERRORS
+            .with_label_values(&[self.variant()])
+            .inc();
+        Outcome::Failure((self.status(), self))
+    }
+}
+
+/// Responds with the JSON body and status of the error, counting it in the metrics.
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        metrics::API_ERRORS
+            .with_label_values(&[self.variant()])
+            .inc();
+        let status = self.status();
+        let body = match self {
+            Self::NotFound(msg)
+            | Self::Server(msg)
+            | Self::User(msg)
+            | Self::BadRequest(msg)
+            | Self::Conflict(msg)
+            | Self::Forbidden(msg) => msg.respond_to(req)?,
+            Self::KeyError(e) => e.respond_to(req)?,
+        };
+        Response::build_from(body).status(status).ok()
+    }
+}
+
 impl From<ServiceError> for ApiError {
     fn from(err: ServiceError) -> Self {
         match err {
             ServiceError::Job(c) => Self::User(Json(format!("job parsing error: {}", c))),
+            ServiceError::Alert(e) => Self::BadRequest(Json(e.to_string())),
+            ServiceError::Claim(e @ ClaimError::InvalidLease(_)) => {
+                Self::BadRequest(Json(e.to_string()))
+            }
+            ServiceError::Claim(e) => Self::Conflict(Json(e.to_string())),
             ServiceError::NotFound => Self::NotFound(Json("entity not found".to_owned())),
-            ServiceError::Data(_) => Self::Server(Json("a server error occurred".to_owned())),
+            ServiceError::Data(e @ DataError::UnsupportedBackend { .. }) => {
+                Self::BadRequest(Json(e.to_string()))
+            }
+            ServiceError::Data(_) | ServiceError::Stats(_) => {
+                Self::Server(Json("a server error occurred".to_owned()))
+            }
+            ServiceError::Conflict(msg) => Self::Conflict(Json(msg)),
             ServiceError::PermissionError(msg) => Self::User(Json(msg)),
         }
     }
 }
 
+/// A malformed path parameter, such as a [`ShortCode`](crate::ShortCode).
+impl From<JobError> for ApiError {
+    fn from(err: JobError) -> Self {
+        Self::BadRequest(Json(err.to_string()))
+    }
+}
+
 /// Allows an [`ApiKey`] to be used as a [request guard](https://rocket.rs/v0.5-rc/guide/requests/#request-guards) in a route.
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for ApiKey {
@@ -103,13 +206,10 @@ impl<'r> FromRequest<'r> for ApiKey {
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         fn server_error() -> Outcome<ApiKey, ApiError> {
-            Outcome::Failure((
-                Status::InternalServerError,
-                ApiError::Server(Json("server error".to_string())),
-            ))
+            ApiError::Server(Json("server error".to_string())).into_outcome()
         }
         fn key_error(e: ApiKeyError) -> Outcome<ApiKey, ApiError> {
-            Outcome::Failure((Status::BadRequest, ApiError::KeyError(Json(e))))
+            ApiError::KeyError(Json(e)).into_outcome()
         }
         match req.headers().get_one(API_KEY_HEADER) {
             None => key_error(ApiKeyError::NotFound("API key not found".to_string())),
@@ -134,85 +234,486 @@ impl<'r> FromRequest<'r> for ApiKey {
     }
 }
 
+/// Body of the response to [`new_api_key`].
+#[derive(Debug, Serialize)]
+pub struct NewApiKey {
+    /// The base64 encoded key, to be sent in the `x-api-key` header.
+    api_key: String,
+}
+
 /// Route to generate a new [`ApiKey`].
 ///
-/// The key will be logged to the terminal for this demo application.
+/// Requires the admin token. The key is only returned in the response, and is never logged.
 #[rocket::get("/key")]
-pub async fn new_api_key(database: &State<AppDatabase>) -> Result<Json<&str>, ApiError> {
+#[tracing::instrument(name = "new_api_key", skip_all, fields(request_id = %request_id))]
+pub async fn new_api_key(
+    database: &State<AppDatabase>,
+    request_id: RequestId,
+    _admin: Admin,
+) -> Result<Json<NewApiKey>, ApiError> {
     let api_key = action::generate_api_key(database.get_pool()).await?;
-    println!("Api Key: {}", api_key.to_base64());
-    Ok(Json("Api key generated. See logs for details."))
+    tracing::info!("generated new API key");
+    Ok(Json(NewApiKey {
+        api_key: api_key.to_base64(),
+    }))
+}
+
+/// Reads the [`Password`] for a protected [`Job`](crate::Job) from the request cookies.
+fn password_from_cookies(cookies: &CookieJar<'_>) -> Password {
+    cookies
+        .get(PASSWORD_COOKIE)
+        .and_then(|cookie| Password::new(cookie.value().to_string()).ok())
+        .unwrap_or_default()
 }
 
 /// Route to retrieve an existing [`Job`](crate::domain::Job), based on it's [`ShortCode`](crate::ShortCode).
 #[rocket::get("/<shortcode>")]
+#[tracing::instrument(name = "get_job", skip_all, fields(request_id = %request_id, shortcode))]
 pub async fn get_job(
-    shortcode: &str,
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
     database: &State<AppDatabase>,
     cookies: &CookieJar<'_>,
     hit_counter: &State<ResponseCounter>,
-    _api_key: ApiKey,
+    visitor: Visitor,
+    api_key: ApiKey,
 ) -> Result<Json<crate::Job>, ApiError> {
-    use crate::domain::job::field::Password;
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    let req = service::ask::GetJob {
+        job: shortcode.clone().into(),
+        password: password_from_cookies(cookies),
+    };
+    let job = action::get_job(req, database.get_pool()).await?;
+    hit_counter.hit(
+        Hit::new(shortcode, Channel::Api)
+            .with_api_key(api_key)
+            .with_visitor(visitor),
+        1,
+    );
+    Ok(Json(job))
+}
+
+/// Route to retrieve the responses for a [`Job`](crate::Job), split by access channel.
+#[rocket::get("/<shortcode>/responses")]
+#[tracing::instrument(name = "get_job_responses", skip_all, fields(request_id = %request_id, shortcode))]
+pub async fn get_job_responses(
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    database: &State<AppDatabase>,
+    cookies: &CookieJar<'_>,
+    _api_key: ApiKey,
+) -> Result<Json<ResponseBreakdown>, ApiError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    let req = service::ask::GetJob {
+        job: shortcode.clone().into(),
+        password: password_from_cookies(cookies),
+    };
+    let job = action::get_job(req, database.get_pool()).await?;
+    Ok(Json(
+        action::get_response_breakdown(&job.shortcode, database.get_pool()).await?,
+    ))
+}
+
+/// Route to retrieve the response time series across all [`Jobs`](crate::Job).
+///
+/// Defaults to hourly buckets. `days` limits how much history is returned.
+#[rocket::get("/stats?<granularity>&<days>")]
+#[tracing::instrument(name = "get_global_stats", skip_all, fields(request_id = %request_id))]
+pub async fn get_global_stats(
+    request_id: RequestId,
+    granularity: Option<Granularity>,
+    days: Option<u32>,
+    database: &State<AppDatabase>,
+    _api_key: ApiKey,
+) -> Result<Json<ResponseSeries>, ApiError> {
+    let granularity = granularity.unwrap_or_default();
+    let req = service::ask::GetResponseStats {
+        shortcode: None,
+        granularity,
+        days: days.unwrap_or_else(|| granularity.default_days()),
+    };
+    Ok(Json(
+        action::get_response_stats(req, database.get_pool()).await?,
+    ))
+}
+
+/// Route to retrieve the response time series for a single [`Job`](crate::Job).
+///
+/// Password-protected jobs require the password cookie, as with [`get_job`].
+#[rocket::get("/<shortcode>/stats?<granularity>&<days>")]
+#[tracing::instrument(name = "get_job_stats", skip_all, fields(request_id = %request_id, shortcode))]
+pub async fn get_job_stats(
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    granularity: Option<Granularity>,
+    days: Option<u32>,
+    database: &State<AppDatabase>,
+    cookies: &CookieJar<'_>,
+    _api_key: ApiKey,
+) -> Result<Json<ResponseSeries>, ApiError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    let req = service::ask::GetJob {
+        job: shortcode.clone().into(),
+        password: password_from_cookies(cookies),
+    };
+    let job = action::get_job(req, database.get_pool()).await?;
+    let granularity = granularity.unwrap_or_default();
+    let req = service::ask::GetResponseStats {
+        shortcode: Some(job.shortcode),
+        granularity,
+        days: days.unwrap_or_else(|| granularity.default_days()),
+    };
+    Ok(Json(
+        action::get_response_stats(req, database.get_pool()).await?,
+    ))
+}
+
+/// Route to list the public [`Jobs`](crate::Job), with the number of jobs of each task type.
+///
+/// Password protected and expired jobs are not listed. `q` searches the escrow
+/// id, task type, requester description and manifest of each job, and adds a
+/// `snippet` of the matched text to each job. `sort` is `posted`, `task_bid` or
+/// `balance`, and defaults to the best matches first when searching and `posted`
+/// otherwise. `limit` defaults to 20, and is at most 100.
+#[rocket::get("/jobs?<q>&<task_type>&<sort>&<limit>&<offset>")]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "list_jobs", skip_all, fields(request_id = %request_id))]
+pub async fn list_jobs(
+    request_id: RequestId,
+    q: Option<&str>,
+    task_type: Option<&str>,
+    sort: Option<&str>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    database: &State<AppDatabase>,
+    _api_key: ApiKey,
+) -> Result<Json<JobListing>, ApiError> {
+    let defaults = service::ask::ListJobs::default();
+    let req = service::ask::ListJobs {
+        task_type: task_type.map(TaskType::from_str).transpose()?,
+        search: q.and_then(SearchQuery::new),
+        sort: sort.map(JobSort::from_str).transpose()?,
+        limit: limit.unwrap_or(defaults.limit),
+        offset: offset.unwrap_or(defaults.offset),
+    };
+    Ok(Json(action::list_jobs(req, database.get_pool()).await?))
+}
 
+/// Route to retrieve the most recent [`Job`](crate::domain::Job) for an escrow address.
+///
+/// Addresses are matched regardless of case.
+#[rocket::get("/escrow/<escrow_id>")]
+#[tracing::instrument(name = "get_job_by_escrow", skip_all, fields(request_id = %request_id, escrow_id))]
+pub async fn get_job_by_escrow(
+    request_id: RequestId,
+    escrow_id: Result<EscrowId, JobError>,
+    database: &State<AppDatabase>,
+    cookies: &CookieJar<'_>,
+    hit_counter: &State<ResponseCounter>,
+    visitor: Visitor,
+    api_key: ApiKey,
+) -> Result<Json<crate::Job>, ApiError> {
+    let escrow_id = escrow_id?;
+    tracing::Span::current().record("escrow_id", escrow_id.as_str());
     let req = service::ask::GetJob {
-        shortcode: shortcode.into(),
-        password: cookies
-            .get(PASSWORD_COOKIE)
-            .map(|cookie| cookie.value())
-            .map(|raw_password| Password::new(raw_password.to_string()).ok())
-            .flatten()
-            .unwrap_or_else(Password::default),
+        job: escrow_id.into(),
+        password: password_from_cookies(cookies),
     };
     let job = action::get_job(req, database.get_pool()).await?;
-    hit_counter.hit(shortcode.into(), 1);
+    hit_counter.hit(
+        Hit::new(job.shortcode.clone(), Channel::Api)
+            .with_api_key(api_key)
+            .with_visitor(visitor),
+        1,
+    );
     Ok(Json(job))
 }
 
 /// Route to add a new [`Job`](crate::Job).
 #[rocket::post("/", data = "<req>")]
+#[tracing::instrument(name = "new_job", skip_all, fields(request_id = %request_id))]
 pub async fn new_job(
+    request_id: RequestId,
     req: Json<service::ask::NewJob>,
     database: &State<AppDatabase>,
+    shortcodes: &State<ShortCodeConfig>,
+    manifests: &State<ManifestPolicy>,
     _api_key: ApiKey,
 ) -> Result<Json<crate::Job>, ApiError> {
-    let job = action::new_job(req.into_inner(), database.get_pool()).await?;
+    let job = action::new_job(req.into_inner(), shortcodes, manifests, database.get_pool()).await?;
     Ok(Json(job))
 }
 
 /// Route to update an existing [`Job`](crate::Job).
 #[rocket::put("/", data = "<req>")]
+#[tracing::instrument(name = "update_job", skip_all, fields(request_id = %request_id))]
 pub async fn update_job(
+    request_id: RequestId,
     req: Json<service::ask::UpdateJob>,
     database: &State<AppDatabase>,
+    manifests: &State<ManifestPolicy>,
     _api_key: ApiKey,
 ) -> Result<Json<crate::Job>, ApiError> {
-    let job = action::update_job(req.into_inner(), database.get_pool()).await?;
+    let job = action::update_job(req.into_inner(), manifests, database.get_pool()).await?;
     Ok(Json(job))
 }
 
+/// Route to save a search, so the calling [`ApiKey`] is alerted about new jobs which match it.
+///
+/// Each alert is listed by [`list_job_alerts`], sent by [`stream_job_alerts`],
+/// and posted to the search's `webhook_url` when it has one.
+#[rocket::post("/searches", data = "<req>")]
+#[tracing::instrument(name = "save_search", skip_all, fields(request_id = %request_id))]
+pub async fn save_search(
+    request_id: RequestId,
+    req: Json<service::ask::NewSavedSearch>,
+    webhooks: &State<WebhookPolicy>,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<SavedSearch>, ApiError> {
+    let search =
+        action::save_search(api_key, req.into_inner(), webhooks, database.get_pool()).await?;
+    Ok(Json(search))
+}
+
+/// Route to list the saved searches of the calling [`ApiKey`].
+#[rocket::get("/searches")]
+#[tracing::instrument(name = "list_saved_searches", skip_all, fields(request_id = %request_id))]
+pub async fn list_saved_searches(
+    request_id: RequestId,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<Vec<SavedSearch>>, ApiError> {
+    Ok(Json(
+        action::list_saved_searches(api_key, database.get_pool()).await?,
+    ))
+}
+
+/// Route to delete a saved search of the calling [`ApiKey`], along with its alerts.
+#[rocket::delete("/searches/<search_id>")]
+#[tracing::instrument(name = "delete_saved_search", skip_all, fields(request_id = %request_id, search_id))]
+pub async fn delete_saved_search(
+    request_id: RequestId,
+    search_id: &str,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Status, ApiError> {
+    tracing::Span::current().record("search_id", search_id);
+    let search_id = DbId::from_str(search_id).map_err(JobError::from)?;
+    action::delete_saved_search(api_key, search_id, database.get_pool()).await?;
+    Ok(Status::NoContent)
+}
+
+/// Route to list the alerts for the saved searches of the calling [`ApiKey`], oldest first.
+///
+/// Only alerts after the `alert_id` in `after` are listed, so the last
+/// `alert_id` of each page is the `after` of the next. `limit` defaults to 20,
+/// and is at most 100.
+#[rocket::get("/alerts?<after>&<limit>")]
+#[tracing::instrument(name = "list_job_alerts", skip_all, fields(request_id = %request_id))]
+pub async fn list_job_alerts(
+    request_id: RequestId,
+    after: Option<i64>,
+    limit: Option<u32>,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<Vec<JobAlert>>, ApiError> {
+    let alerts = action::get_job_alerts(
+        api_key,
+        after.unwrap_or(0),
+        limit.unwrap_or(DEFAULT_LIMIT),
+        database.get_pool(),
+    )
+    .await?;
+    Ok(Json(alerts))
+}
+
+/// How often [`stream_job_alerts`] checks for new alerts.
+const ALERT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The `Last-Event-ID` header, which clients send when they reconnect to an event stream.
+#[derive(Debug, Clone, Copy)]
+pub struct LastEventId(Option<i64>);
+
+/// Never fails, since the header is only present on reconnection.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("last-event-id")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(Self(id))
+    }
+}
+
+/// Route to receive the alerts for the saved searches of the calling
+/// [`ApiKey`] as server-sent events.
+///
+/// Each `job_alert` event has the alert as its data and the `alert_id` as its
+/// id, so reconnecting clients receive the alerts they missed. New connections
+/// start after the alert in `after`, or with the next new alert.
+///
+/// This route is not instrumented, since `tracing::instrument` can not wrap a
+/// function which returns an `impl Stream`.
+#[rocket::get("/alerts/stream?<after>")]
+pub async fn stream_job_alerts(
+    after: Option<i64>,
+    last_event_id: LastEventId,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], ApiError> {
+    let pool = database.get_pool().clone();
+    let mut after = match last_event_id.0.or(after) {
+        Some(after) => after,
+        None => action::get_latest_alert_id(api_key.clone(), &pool).await?,
+    };
+    Ok(EventStream! {
+        loop {
+            match action::get_job_alerts(api_key.clone(), after, DEFAULT_LIMIT, &pool).await {
+                Ok(alerts) => {
+                    for alert in alerts {
+                        after = alert.alert_id;
+                        yield Event::json(&alert).id(alert.alert_id.to_string()).event("job_alert");
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to get job alerts"),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(ALERT_POLL_INTERVAL) => {}
+                _ = &mut shutdown => break,
+            }
+        }
+    })
+}
+
+/// Route to claim a task of a [`Job`](crate::Job) for the calling [`ApiKey`].
+///
+/// The task is reserved for `lease_secs`, which defaults to 15 minutes and is
+/// at most a day, and is freed when the lease runs out unless the claim is
+/// renewed. A job has as many tasks as the `total_tasks` of its manifest, and
+/// jobs whose tasks are all claimed respond with a conflict. Password-protected
+/// jobs require the password cookie, as with [`get_job`].
+#[rocket::post("/job/<shortcode>/claim?<lease_secs>")]
+#[tracing::instrument(name = "claim_task", skip_all, fields(request_id = %request_id, shortcode))]
+pub async fn claim_task(
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    lease_secs: Option<u32>,
+    database: &State<AppDatabase>,
+    cookies: &CookieJar<'_>,
+    api_key: ApiKey,
+) -> Result<Json<Claim>, ApiError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    let req = service::ask::ClaimTask {
+        job: service::ask::GetJob {
+            job: shortcode.into(),
+            password: password_from_cookies(cookies),
+        },
+        lease_secs,
+    };
+    let claim = action::claim_task(api_key, req, database.get_pool()).await?;
+    Ok(Json(claim))
+}
+
+/// Route to renew a claim of the calling [`ApiKey`], so its lease runs out
+/// `lease_secs` from now.
+///
+/// Claims whose lease has run out are not found, since their task may have been claimed again.
+#[rocket::post("/job/<shortcode>/claim/<claim_id>/renew?<lease_secs>")]
+#[tracing::instrument(name = "renew_claim", skip_all, fields(request_id = %request_id, shortcode, claim_id))]
+pub async fn renew_claim(
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    claim_id: &str,
+    lease_secs: Option<u32>,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<Claim>, ApiError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    tracing::Span::current().record("claim_id", claim_id);
+    let req = service::ask::RenewClaim {
+        shortcode,
+        claim_id: DbId::from_str(claim_id).map_err(JobError::from)?,
+        lease_secs,
+    };
+    let claim = action::renew_claim(api_key, req, database.get_pool()).await?;
+    Ok(Json(claim))
+}
+
+/// Route to release a claim of the calling [`ApiKey`], freeing its task.
+#[rocket::delete("/job/<shortcode>/claim/<claim_id>")]
+#[tracing::instrument(name = "release_claim", skip_all, fields(request_id = %request_id, shortcode, claim_id))]
+pub async fn release_claim(
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    claim_id: &str,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Status, ApiError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
+    tracing::Span::current().record("claim_id", claim_id);
+    let claim_id = DbId::from_str(claim_id).map_err(JobError::from)?;
+    action::release_claim(api_key, shortcode, claim_id, database.get_pool()).await?;
+    Ok(Status::NoContent)
+}
+
 /// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes!(get_job, new_job, update_job, new_api_key)
+    rocket::routes!(
+        get_job,
+        get_job_responses,
+        get_job_stats,
+        get_global_stats,
+        new_job,
+        update_job,
+        new_api_key
+    )
+}
+
+/// The versioned URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn v1_routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        get_job_by_escrow,
+        list_jobs,
+        claim_task,
+        renew_claim,
+        release_claim,
+        save_search,
+        list_saved_searches,
+        delete_saved_search,
+        list_job_alerts,
+        stream_job_alerts
+    ]
 }
 
 pub mod catcher {
     //! Contains all the API catchers.
+    use rocket::http::Status;
     use rocket::serde::json::Json;
     use rocket::Request;
     use rocket::{catch, catchers, Catcher};
 
     /// Catch unhandled errors.
     #[catch(default)]
-    fn default(req: &Request) -> Json<&'static str> {
-        eprintln!("General error: {:?}", req);
+    fn default(status: Status, req: &Request) -> Json<&'static str> {
+        tracing::warn!(status = status.code, method = %req.method(), path = %req.uri().path(), "unhandled error");
         Json("something went wrong...")
     }
 
     /// Catch server errors.
     #[catch(500)]
     fn internal_error(req: &Request) -> Json<&'static str> {
-        eprintln!("Internal error: {:?}", req);
+        tracing::error!(method = %req.method(), path = %req.uri().path(), "internal error");
         Json("internal server error")
     }
 
@@ -228,6 +729,12 @@ pub mod catcher {
         Json("request error")
     }
 
+    /// Catch admin token errors.
+    #[catch(403)]
+    fn forbidden() -> Json<&'static str> {
+        Json("forbidden")
+    }
+
     /// Catch API key errors.
     #[catch(400)]
     fn missing_api_key() -> Json<&'static str> {
@@ -241,7 +748,444 @@ pub mod catcher {
             default,
             internal_error,
             missing_api_key,
-            request_error
+            request_error,
+            forbidden
         ]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::API_KEY_HEADER;
+    use crate::data::graph::GraphJob;
+    use crate::data::{model, query};
+    use crate::domain::job::field;
+    use crate::service::action;
+    use crate::web::test::{client, config};
+    use rocket::http::{ContentType, Header, Status};
+    use std::io::Read;
+
+    #[test]
+    fn new_jobs_use_the_configured_shortcodes() {
+        use field::{Alphabet, ShortCodeConfig};
+        let rt = crate::test::async_runtime();
+        let mut config = config(rt.handle());
+        config.shortcodes = ShortCodeConfig {
+            alphabet: Alphabet::Base58,
+            length: 20,
+        };
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+
+        let req = crate::service::ask::NewJob {
+            escrow_id: field::EscrowId::new("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap(),
+            manifest_url: field::ManifestUrl::default(),
+            posted: field::Posted::new(0),
+            expires: field::Expires::default(),
+            password: field::Password::default(),
+            shortcode: field::VanityCode::default(),
+        };
+        let res = client
+            .post("/api/job")
+            .header(Header::new(API_KEY_HEADER, api_key))
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(&req).unwrap())
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let job: serde_json::Value = res.into_json().unwrap();
+        let shortcode = job["shortcode"].as_str().unwrap();
+        assert_eq!(shortcode.len(), 20);
+        assert!(shortcode
+            .bytes()
+            .all(|c| Alphabet::Base58.chars().contains(&c)));
+    }
+
+    #[test]
+    fn mismatched_manifests_are_flagged() {
+        use crate::test::{ok, serve};
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+
+        // The gateway serves a different manifest than the escrow recorded.
+        let (base, requests) = serve(vec![ok(r#"{"job":2}"#)]);
+        let policy = field::ManifestPolicy {
+            ipfs_gateway: format!("{}/ipfs/", base),
+            ..field::ManifestPolicy::default()
+        };
+        let job = GraphJob {
+            id: "0xflagged".to_owned(),
+            manifestUrl: Some("ipfs://bafybeigdyrzt/manifest.json".to_owned()),
+            manifestHash: Some(field::HashAlgorithm::Sha1.digest(br#"{"job":1}"#)),
+            eip20: None,
+            amountAllocated: None,
+            amountPayout: None,
+            timestamp: "1".to_owned(),
+            network: None,
+        };
+        let job: crate::Job = rt.block_on(async {
+            let job = model::NewJob::from_graph(job, &Default::default());
+            query::new_job(job, &Default::default(), &pool)
+                .await
+                .unwrap()
+                .try_into()
+                .unwrap()
+        });
+        assert_eq!(
+            job.manifest_verification.status,
+            field::ManifestStatus::Pending
+        );
+
+        let statuses = rt
+            .block_on(action::verify_manifests(&policy, &pool))
+            .unwrap();
+        assert_eq!(statuses, vec![field::ManifestStatus::Flagged]);
+        assert_eq!(
+            requests.recv().unwrap(),
+            "GET /ipfs/bafybeigdyrzt/manifest.json HTTP/1.1"
+        );
+        let stored: crate::Job = rt
+            .block_on(query::get_job(job.job_id, &pool))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            stored.manifest_verification.status,
+            field::ManifestStatus::Flagged
+        );
+        assert!(stored.manifest_verification.checked.is_some());
+        assert!(stored.task_type.is_none());
+
+        let res = client
+            .get(format!("/api/job/{}", stored.shortcode.as_str()))
+            .header(Header::new(API_KEY_HEADER, api_key))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let job: serde_json::Value = res.into_json().unwrap();
+        assert_eq!(job["manifest_verification"]["status"], "flagged");
+    }
+
+    #[test]
+    fn graph_jobs_alert_saved_searches_once_seeded() {
+        use crate::data::graph::GraphConfig;
+        use crate::domain::health::SyncProgress;
+        use crate::test::{ok, serve};
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+        let key = || Header::new(API_KEY_HEADER, api_key.clone());
+        let res = client
+            .post("/api/v1/searches")
+            .header(key())
+            .header(ContentType::JSON)
+            .body(r#"{"network": "polygon-mumbai"}"#)
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        // The download which seeds the database alerts nothing, but later jobs do.
+        let (url, _) = serve(vec![
+            ok(r#"{"data": {"launchedEscrows": [{"id": "0xseeded", "timestamp": "1"}]}}"#),
+            ok(r#"{"data": {"launchedEscrows": [{"id": "0xfetched", "timestamp": "2"}]}}"#),
+        ]);
+        let graph = GraphConfig {
+            url,
+            ..GraphConfig::default()
+        };
+        let progress = SyncProgress::default();
+        let sync = || {
+            rt.block_on(action::sync_graph_jobs(
+                &graph,
+                &Default::default(),
+                &Default::default(),
+                &progress,
+                &pool,
+            ))
+            .unwrap()
+        };
+        assert_eq!(sync(), 1);
+        assert_eq!(sync(), 1);
+
+        let alerts: Vec<serde_json::Value> = client
+            .get("/api/v1/alerts")
+            .header(key())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0]["job"]["escrow_id"], "0xfetched");
+    }
+
+    #[test]
+    fn webhooks_are_checked_against_their_own_policy() {
+        use crate::domain::alert::WebhookPolicy;
+        let rt = crate::test::async_runtime();
+        let mut config = config(rt.handle());
+        config.manifests = field::ManifestPolicy {
+            allow_private_addresses: true,
+            ..field::ManifestPolicy::default()
+        };
+        config.webhooks = WebhookPolicy {
+            denied_hosts: vec!["internal.example.com".to_owned()],
+            ..WebhookPolicy::default()
+        };
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+        let save = |webhook_url: &str| {
+            client
+                .post("/api/v1/searches")
+                .header(Header::new(API_KEY_HEADER, api_key.clone()))
+                .header(ContentType::JSON)
+                .body(serde_json::json!({ "webhook_url": webhook_url }).to_string())
+                .dispatch()
+        };
+
+        // Private addresses are refused even though manifests may use them.
+        let res = save("https://10.0.0.1/hook");
+        assert_eq!(res.status(), Status::BadRequest);
+        assert_eq!(
+            res.into_string().unwrap(),
+            r#""invalid webhook_url: 10.0.0.1 is not a public address""#
+        );
+        let res = save("https://hooks.internal.example.com/hook");
+        assert_eq!(res.status(), Status::BadRequest);
+        assert_eq!(
+            res.into_string().unwrap(),
+            r#""invalid webhook_url: 'hooks.internal.example.com' is a denied host""#
+        );
+        let res = save("https://hooks.example.com/hook");
+        assert_eq!(res.status(), Status::Ok);
+    }
+
+    #[test]
+    fn saved_searches_alert_matching_jobs() {
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+        let key = || Header::new(API_KEY_HEADER, api_key.clone());
+
+        let res = client
+            .post("/api/v1/searches")
+            .header(key())
+            .header(ContentType::JSON)
+            .body(r#"{"network": " "}"#)
+            .dispatch();
+        assert_eq!(res.status(), Status::BadRequest);
+        let res = client
+            .post("/api/v1/searches")
+            .header(key())
+            .header(ContentType::JSON)
+            .body(r#"{"network": "Polygon", "min_task_bid": "0"}"#)
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let search: serde_json::Value = res.into_json().unwrap();
+        let search_id = search["search_id"].as_str().unwrap().to_owned();
+        let searches: serde_json::Value = client
+            .get("/api/v1/searches")
+            .header(key())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(searches.as_array().unwrap().len(), 1);
+
+        // Jobs on other networks, and jobs without a task bid, do not match.
+        let ingest = |id: &str, network: &str| {
+            let job = GraphJob {
+                id: id.to_owned(),
+                manifestUrl: None,
+                manifestHash: None,
+                eip20: None,
+                amountAllocated: None,
+                amountPayout: None,
+                timestamp: "1".to_owned(),
+                network: Some(network.to_owned()),
+            };
+            rt.block_on(async {
+                let job = model::NewJob::from_graph(job, &Default::default());
+                let job = query::new_job(job, &Default::default(), &pool)
+                    .await
+                    .unwrap()
+                    .try_into()
+                    .unwrap();
+                let unpriced = action::alert_saved_searches(&job, &pool).await.unwrap();
+                let check = field::ManifestCheck {
+                    job_id: job.job_id.clone(),
+                    status: field::ManifestStatus::Unverifiable,
+                    task_type: None,
+                    task_bid: Some(field::TokenAmount::from_decimal("1", 18).unwrap()),
+                    total_tasks: None,
+                    search_text: None,
+                    checked: crate::Time::now(),
+                };
+                query::record_manifest_check(check, &pool).await.unwrap();
+                let job = query::get_job(job.job_id, &pool)
+                    .await
+                    .unwrap()
+                    .try_into()
+                    .unwrap();
+                let priced = action::alert_saved_searches(&job, &pool).await.unwrap();
+                let again = action::alert_saved_searches(&job, &pool).await.unwrap();
+                (unpriced, priced, again)
+            })
+        };
+        assert_eq!(ingest("0xmainnet", "ethereum"), (0, 0, 0));
+        assert_eq!(ingest("0xpolygon", "polygon"), (0, 1, 0));
+
+        let alerts: serde_json::Value = client
+            .get("/api/v1/alerts")
+            .header(key())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let alerts = alerts.as_array().unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0]["search_id"], search_id.as_str());
+        assert_eq!(alerts[0]["job"]["escrow_id"], "0xpolygon");
+        let alert_id = alerts[0]["alert_id"].as_i64().unwrap();
+        let res = client
+            .get(format!("/api/v1/alerts?after={}", alert_id))
+            .header(key())
+            .dispatch();
+        assert_eq!(res.into_json::<Vec<serde_json::Value>>().unwrap().len(), 0);
+
+        let mut res = client
+            .get("/api/v1/alerts/stream")
+            .header(key())
+            .header(Header::new("Last-Event-ID", "0"))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        // The stream starts with a comment, and is read until the first event has arrived.
+        let mut event = String::new();
+        let mut buf = [0; 4096];
+        while !event.contains("\n\n") || !event.contains("data:") {
+            let len = res.read(&mut buf).unwrap();
+            assert!(len > 0, "stream ended before an event: {}", event);
+            event.push_str(&String::from_utf8_lossy(&buf[..len]));
+        }
+        assert!(event.contains("event:job_alert"), "{}", event);
+        assert!(event.contains(&format!("id:{}", alert_id)), "{}", event);
+
+        let delete = |search_id: &str| {
+            client
+                .delete(format!("/api/v1/searches/{}", search_id))
+                .header(key())
+                .dispatch()
+                .status()
+        };
+        assert_eq!(delete("not-an-id"), Status::BadRequest);
+        assert_eq!(delete(&search_id), Status::NoContent);
+        assert_eq!(delete(&search_id), Status::NotFound);
+        let res = client.get("/api/v1/alerts").header(key()).dispatch();
+        assert_eq!(res.into_json::<Vec<serde_json::Value>>().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn tasks_are_claimed_renewed_and_released() {
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let pool = config.database.get_pool().clone();
+        let api_key = rt
+            .block_on(action::generate_api_key(&pool))
+            .unwrap()
+            .to_base64();
+        let client = client(config);
+        let key = || Header::new(API_KEY_HEADER, api_key.clone());
+
+        let job = GraphJob {
+            id: "0xclaimed".to_owned(),
+            manifestUrl: None,
+            manifestHash: None,
+            eip20: None,
+            amountAllocated: None,
+            amountPayout: None,
+            timestamp: "1".to_owned(),
+            network: None,
+        };
+        let job: crate::Job = rt
+            .block_on(query::new_job(
+                model::NewJob::from_graph(job, &Default::default()),
+                &Default::default(),
+                &pool,
+            ))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let claim_url = format!("/api/v1/job/{}/claim", job.shortcode.as_str());
+        let claim = || client.post(claim_url.as_str()).header(key()).dispatch();
+
+        // The number of tasks is only known once the manifest has been read.
+        assert_eq!(claim().status(), Status::Conflict);
+        let check = field::ManifestCheck {
+            job_id: job.job_id.clone(),
+            status: field::ManifestStatus::Unverifiable,
+            task_type: None,
+            task_bid: None,
+            total_tasks: Some(1),
+            search_text: None,
+            checked: crate::Time::now(),
+        };
+        rt.block_on(query::record_manifest_check(check, &pool))
+            .unwrap();
+
+        let res = client
+            .post(format!("{}?lease_secs=1", claim_url))
+            .header(key())
+            .dispatch();
+        assert_eq!(res.status(), Status::BadRequest);
+        let res = claim();
+        assert_eq!(res.status(), Status::Ok);
+        let claimed: serde_json::Value = res.into_json().unwrap();
+        let claim_id = claimed["claim_id"].as_str().unwrap().to_owned();
+        assert_eq!(claimed["shortcode"], job.shortcode.as_str());
+        assert_eq!(claim().status(), Status::Conflict);
+
+        let res = client
+            .post(format!("{}/{}/renew?lease_secs=3600", claim_url, claim_id))
+            .header(key())
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let renewed: serde_json::Value = res.into_json().unwrap();
+        assert_eq!(renewed["claim_id"], claim_id.as_str());
+        assert_ne!(renewed["expires"], claimed["expires"]);
+
+        let release = |claim_id: &str| {
+            client
+                .delete(format!("{}/{}", claim_url, claim_id))
+                .header(key())
+                .dispatch()
+                .status()
+        };
+        assert_eq!(release("not-an-id"), Status::BadRequest);
+        assert_eq!(release(&claim_id), Status::NoContent);
+        assert_eq!(release(&claim_id), Status::NotFound);
+        let res = client
+            .post(format!("{}/{}/renew", claim_url, claim_id))
+            .header(key())
+            .dispatch();
+        assert_eq!(res.status(), Status::NotFound);
+        // Releasing the claim frees its task.
+        assert_eq!(claim().status(), Status::Ok);
+    }
+}
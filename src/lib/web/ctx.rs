@@ -1,6 +1,7 @@
 //! Page contexts for rendering.
 
 use derive_more::Constructor;
+use rocket::http::RawStr;
 use serde::Serialize;
 
 /// Must be implemented by all structures which are used as a [`Context`](handlebars::Context) for template rendering.
@@ -15,13 +16,104 @@ pub trait PageContext {
     fn parent(&self) -> &str;
 }
 
+/// A job listed on the job board.
+#[derive(Debug, Serialize)]
+pub struct BoardJob {
+    pub shortcode: crate::ShortCode,
+    pub escrow_id: String,
+    /// The label of the job's task type, if it has been classified.
+    pub task_type: Option<&'static str>,
+    /// The task bid, formatted with the token's decimals and symbol.
+    pub task_bid: Option<String>,
+    /// Where the job matched the search, when the board is searched.
+    pub snippet: Option<crate::domain::board::Snippet>,
+}
+
+/// A chip which filters the job board by task type.
+#[derive(Debug, Serialize)]
+pub struct TaskTypeChip {
+    /// The `task_type` query value, or `None` for the chip which shows every job.
+    pub value: Option<&'static str>,
+    pub label: &'static str,
+    pub count: u64,
+    pub selected: bool,
+}
+
 /// The Home page.
 #[derive(Debug, Serialize)]
-pub struct Home {}
+pub struct Home {
+    /// The jobs on the job board.
+    pub jobs: Vec<BoardJob>,
+    /// The task type filters for the job board, starting with the chip for every job.
+    pub chips: Vec<TaskTypeChip>,
+    /// What the job board was searched for.
+    pub search: Option<String>,
+    /// The search, percent encoded for the `q` query parameter of links.
+    pub search_param: Option<String>,
+}
 
+/// The Default implementation has an empty job board, for when the page is
+/// shown again after an error.
 impl Default for Home {
     fn default() -> Self {
-        Self {}
+        Self {
+            jobs: vec![],
+            chips: vec![],
+            search: None,
+            search_param: None,
+        }
+    }
+}
+
+impl Home {
+    /// Create the Home page for a page of the job board, filtered by `selected`
+    /// and searched for `search`.
+    ///
+    /// Jobs which have not been classified are counted in the chip for every
+    /// job, but have no chip of their own.
+    pub fn new(
+        listing: crate::domain::board::JobListing,
+        selected: Option<crate::domain::job::field::TaskType>,
+        search: Option<crate::domain::board::SearchQuery>,
+    ) -> Self {
+        let total = listing.facets.iter().map(|facet| facet.count).sum();
+        let all = TaskTypeChip {
+            value: None,
+            label: "All jobs",
+            count: total,
+            selected: selected.is_none(),
+        };
+        let chips = listing.facets.iter().filter_map(|facet| {
+            facet.task_type.map(|task_type| TaskTypeChip {
+                value: Some(task_type.as_str()),
+                label: task_type.label(),
+                count: facet.count,
+                selected: selected == Some(task_type),
+            })
+        });
+        Self {
+            jobs: listing
+                .jobs
+                .into_iter()
+                .map(|listed| (listed.job, listed.snippet))
+                .map(|(job, snippet)| BoardJob {
+                    task_bid: job
+                        .reward
+                        .task_bid
+                        .as_ref()
+                        .map(|amount| job.reward.format(amount)),
+                    shortcode: job.shortcode,
+                    escrow_id: job.escrow_id.into_inner(),
+                    task_type: job.task_type.map(|task_type| task_type.label()),
+                    snippet,
+                })
+                .collect(),
+            chips: std::iter::once(all).chain(chips).collect(),
+            search_param: search
+                .as_ref()
+                .map(|search| RawStr::new(search.as_str()).percent_encode().to_string()),
+            search: search.map(|search| search.as_str().to_owned()),
+        }
     }
 }
 
@@ -37,10 +129,43 @@ impl PageContext for Home {
     }
 }
 
+/// The reward of a [`Job`](crate::Job), formatted with its token's decimals and symbol.
+#[derive(Debug, Default, Serialize)]
+pub struct RewardText {
+    pub token: Option<String>,
+    pub balance: Option<String>,
+    pub task_bid: Option<String>,
+    pub total_tasks: Option<u64>,
+}
+
+impl From<&crate::domain::job::field::Reward> for RewardText {
+    fn from(reward: &crate::domain::job::field::Reward) -> Self {
+        Self {
+            token: reward.token.clone(),
+            balance: reward.balance.as_ref().map(|amount| reward.format(amount)),
+            task_bid: reward.task_bid.as_ref().map(|amount| reward.format(amount)),
+            total_tasks: reward.total_tasks,
+        }
+    }
+}
+
 /// The page for viewing a [`Job`](crate::Job).
-#[derive(Debug, Serialize, Constructor)]
+#[derive(Debug, Serialize)]
 pub struct ViewJob {
     pub job: crate::Job,
+    pub responses: crate::domain::stats::ResponseBreakdown,
+    pub reward: RewardText,
+}
+
+impl ViewJob {
+    /// Create the page for a job, with its reward formatted for display.
+    pub fn new(job: crate::Job, responses: crate::domain::stats::ResponseBreakdown) -> Self {
+        Self {
+            reward: RewardText::from(&job.reward),
+            job,
+            responses,
+        }
+    }
 }
 
 impl PageContext for ViewJob {
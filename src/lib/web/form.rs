@@ -12,6 +12,7 @@ pub struct NewJob {
     pub posted: field::Posted,
     pub expires: field::Expires,
     pub password: field::Password,
+    pub shortcode: field::VanityCode,
 }
 
 /// The form to submit a [`Password`](crate::domain::job::field::Password) for a protected [`Job`](crate::Job).
@@ -1,30 +1,59 @@
 //! Page routing, errors, and data structures.
 
 use crate::data::AppDatabase;
+use crate::domain::board::SearchQuery;
+use crate::domain::job::field::{EscrowId, ManifestPolicy, ShortCodeConfig, TaskType};
+use crate::domain::stats::Channel;
 use crate::service;
 use crate::service::action;
-use crate::web::{
-    ctx, form, renderer::Renderer, responsecounter::ResponseCounter, PageError, PASSWORD_COOKIE,
-};
-use crate::{ServiceError, ShortCode};
+use crate::web::responsecounter::{Hit, ResponseCounter, Visitor};
+use crate::web::trace::RequestId;
+use crate::web::{ctx, form, renderer::Renderer, CookieConfig, PageError, PASSWORD_COOKIE};
+use crate::{JobError, ServiceError, ShortCode};
 use rocket::form::{Contextual, Form};
-use rocket::http::{Cookie, CookieJar, Status};
+use rocket::http::{CookieJar, Status};
 use rocket::response::content::RawHtml;
 use rocket::response::{status, Redirect};
 use rocket::{uri, State};
+use std::str::FromStr;
 
-/// Route to the home page.
-#[rocket::get("/")]
-fn home(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
-    let context = ctx::Home::default();
-    RawHtml(renderer.render(context, &[]))
+/// Route to the home page, with the job board searched for `q` and filtered by `task_type`.
+#[rocket::get("/?<q>&<task_type>")]
+#[tracing::instrument(name = "home", skip_all, fields(request_id = %request_id))]
+async fn home(
+    request_id: RequestId,
+    q: Option<&str>,
+    task_type: Option<&str>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    let task_type = task_type.map(TaskType::from_str).transpose()?;
+    let search = q.and_then(SearchQuery::new);
+    let req = service::ask::ListJobs {
+        task_type,
+        search: search.clone(),
+        ..Default::default()
+    };
+    match action::list_jobs(req, database.get_pool()).await {
+        Ok(listing) => Ok(RawHtml(
+            renderer.render(ctx::Home::new(listing, task_type, search), &[]),
+        )),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to list jobs");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
 }
 
 /// Route to submit a new [`Job`](crate::Job).
 #[rocket::post("/", data = "<form>")]
+#[tracing::instrument(name = "new_job", skip_all, fields(request_id = %request_id))]
 pub async fn new_job(
+    request_id: RequestId,
     form: Form<Contextual<'_, form::NewJob>>,
     database: &State<AppDatabase>,
+    shortcodes: &State<ShortCodeConfig>,
+    manifests: &State<ManifestPolicy>,
     renderer: &State<Renderer<'_>>,
 ) -> Result<Redirect, (Status, RawHtml<String>)> {
     let form = form.into_inner();
@@ -35,11 +64,28 @@ pub async fn new_job(
             posted: value.posted,
             expires: value.expires,
             password: value.password,
+            shortcode: value.shortcode,
         };
-        match action::new_job(req, database.get_pool()).await {
+        match action::new_job(req, shortcodes, manifests, database.get_pool()).await {
             Ok(job) => Ok(Redirect::to(uri!(get_job(shortcode = job.shortcode)))),
+            Err(ServiceError::Conflict(msg)) => Err((
+                Status::Conflict,
+                RawHtml(renderer.render_with_data(
+                    ctx::Home::default(),
+                    ("job", &form.context),
+                    &[msg.as_str()],
+                )),
+            )),
+            Err(ServiceError::Job(e)) => Err((
+                Status::BadRequest,
+                RawHtml(renderer.render_with_data(
+                    ctx::Home::default(),
+                    ("job", &form.context),
+                    &[e.to_string().as_str()],
+                )),
+            )),
             Err(e) => {
-                eprintln!("internal error: {}", e);
+                tracing::error!(error = %e, "failed to create job");
                 Err((
                     Status::InternalServerError,
                     RawHtml(renderer.render(
@@ -58,7 +104,7 @@ pub async fn new_job(
                 if let ErrorKind::Validation(msg) = &err.kind {
                     msg.as_ref()
                 } else {
-                    eprintln!("unhandled error: {}", err);
+                    tracing::warn!(error = %err, "unhandled form error");
                     "An error occurred, please try again"
                 }
             })
@@ -76,8 +122,11 @@ pub async fn new_job(
 
 /// Route to get a [`Job`](crate::Job).
 #[rocket::get("/job/<shortcode>")]
+#[tracing::instrument(name = "get_job", skip_all, fields(request_id = %request_id, shortcode))]
 pub async fn get_job(
-    shortcode: ShortCode,
+    request_id: RequestId,
+    shortcode: Result<ShortCode, JobError>,
+    visitor: Visitor,
     database: &State<AppDatabase>,
     hit_counter: &State<ResponseCounter>,
     renderer: &State<Renderer<'_>>,
@@ -92,10 +141,18 @@ pub async fn get_job(
             RawHtml(renderer.render(context, &[])),
         ))
     }
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
     match action::get_job(shortcode.clone().into(), database.get_pool()).await {
         Ok(job) => {
-            hit_counter.hit(shortcode.clone(), 1);
-            let context = ctx::ViewJob::new(job);
+            hit_counter.hit(
+                Hit::new(shortcode.clone(), Channel::Html).with_visitor(visitor),
+                1,
+            );
+            let responses = action::get_response_breakdown(&shortcode, database.get_pool())
+                .await
+                .map_err(|_| PageError::Internal("server error".to_owned()))?;
+            let context = ctx::ViewJob::new(job, responses);
             render_with_status(Status::Ok, context, renderer)
         }
         Err(e) => match e {
@@ -111,24 +168,37 @@ pub async fn get_job(
 
 /// Route to submit a [`Password`](crate::domain::job::field::Password) for a password-protected [`Job`](crate::Job).
 #[rocket::post("/job/<shortcode>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "submit_job_password", skip_all, fields(request_id = %request_id, shortcode))]
 pub async fn submit_job_password(
+    request_id: RequestId,
     cookies: &CookieJar<'_>,
     form: Form<Contextual<'_, form::GetPasswordProtectedJob>>,
-    shortcode: ShortCode,
+    shortcode: Result<ShortCode, JobError>,
+    visitor: Visitor,
     hit_counter: &State<ResponseCounter>,
     database: &State<AppDatabase>,
     renderer: &State<Renderer<'_>>,
+    cookie_config: &State<CookieConfig>,
 ) -> Result<RawHtml<String>, PageError> {
+    let shortcode = shortcode?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
     if let Some(form) = &form.value {
         let req = service::ask::GetJob {
-            shortcode: shortcode.clone(),
+            job: shortcode.clone().into(),
             password: form.password.clone(),
         };
         match action::get_job(req, database.get_pool()).await {
             Ok(job) => {
-                hit_counter.hit(shortcode.clone(), 1);
-                let context = ctx::ViewJob::new(job);
-                cookies.add(Cookie::new(
+                hit_counter.hit(
+                    Hit::new(shortcode.clone(), Channel::Html).with_visitor(visitor),
+                    1,
+                );
+                let responses = action::get_response_breakdown(&shortcode, database.get_pool())
+                    .await
+                    .map_err(|_| PageError::Internal("server error".to_owned()))?;
+                let context = ctx::ViewJob::new(job, responses);
+                cookies.add(cookie_config.cookie(
                     PASSWORD_COOKIE,
                     form.password.clone().into_inner().unwrap_or_default(),
                 ));
@@ -154,15 +224,20 @@ pub async fn submit_job_password(
 
 /// Route to get just the [`EscrowId`](crate::domain::job::field::EscrowId) of a [`Job`](crate::Job).
 #[rocket::get("/job/raw/<shortcode>")]
+#[tracing::instrument(name = "get_raw_job", skip_all, fields(request_id = %request_id, shortcode))]
 pub async fn get_raw_job(
+    request_id: RequestId,
     cookies: &CookieJar<'_>,
-    shortcode: ShortCode,
+    shortcode: Result<ShortCode, JobError>,
+    visitor: Visitor,
     hit_counter: &State<ResponseCounter>,
     database: &State<AppDatabase>,
 ) -> Result<status::Custom<String>, Status> {
     use crate::domain::job::field::Password;
+    let shortcode = shortcode.map_err(|_| Status::BadRequest)?;
+    tracing::Span::current().record("shortcode", shortcode.as_str());
     let req = service::ask::GetJob {
-        shortcode: shortcode.clone(),
+        job: shortcode.clone().into(),
         password: cookies
             .get(PASSWORD_COOKIE)
             .map(|cookie| cookie.value())
@@ -172,7 +247,10 @@ pub async fn get_raw_job(
     };
     match action::get_job(req, database.get_pool()).await {
         Ok(job) => {
-            hit_counter.hit(shortcode.clone(), 1);
+            hit_counter.hit(
+                Hit::new(shortcode.clone(), Channel::Raw).with_visitor(visitor),
+                1,
+            );
             Ok(status::Custom(Status::Ok, job.escrow_id.into_inner()))
         }
         Err(e) => match e {
@@ -183,27 +261,52 @@ pub async fn get_raw_job(
     }
 }
 
+/// Route to redirect from an escrow address to the most recent [`Job`](crate::Job) for it.
+#[rocket::get("/escrow/<escrow_id>")]
+#[tracing::instrument(name = "redirect_escrow", skip_all, fields(request_id = %request_id, escrow_id))]
+pub async fn redirect_escrow(
+    request_id: RequestId,
+    escrow_id: Result<EscrowId, JobError>,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    let escrow_id = escrow_id?;
+    tracing::Span::current().record("escrow_id", escrow_id.as_str());
+    match action::get_shortcode(escrow_id, database.get_pool()).await {
+        Ok(found) => Ok(Redirect::to(uri!(get_job(shortcode = found)))),
+        Err(ServiceError::NotFound) => Err(PageError::NotFound("Job not found".to_owned())),
+        Err(_) => Err(PageError::Internal("server error".to_owned())),
+    }
+}
+
 /// The URI [`routes`](rocket::Route) which can be mounted by [`rocket`].
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes![home, get_job, new_job, submit_job_password, get_raw_job]
+    rocket::routes![
+        home,
+        get_job,
+        new_job,
+        submit_job_password,
+        get_raw_job,
+        redirect_escrow
+    ]
 }
 
 pub mod catcher {
     //! Contains all the page catchers.
+    use rocket::http::Status;
     use rocket::Request;
     use rocket::{catch, catchers, Catcher};
 
     /// Catch unhandled errors.
     #[catch(default)]
-    fn default(req: &Request) -> &'static str {
-        eprintln!("General error: {:?}", req);
+    fn default(status: Status, req: &Request) -> &'static str {
+        tracing::warn!(status = status.code, method = %req.method(), path = %req.uri().path(), "unhandled error");
         "something went wrong..."
     }
 
     /// Catch server errors.
     #[catch(500)]
     fn internal_error(req: &Request) -> &'static str {
-        eprintln!("Internal error: {:?}", req);
+        tracing::error!(method = %req.method(), path = %req.uri().path(), "internal error");
         "internal server error"
     }
 
@@ -226,7 +329,7 @@ pub mod test {
 
     #[test]
     fn gets_home() {
-        let (_, client) = init_test_client();
+        let (_rt, client) = init_test_client();
 
         let response = client.get("/").dispatch();
         assert_eq!(response.status(), Status::Ok);
@@ -234,30 +337,200 @@ pub mod test {
 
     #[test]
     fn error_on_missing_job() {
-        let (_, client) = init_test_client();
+        let (_rt, client) = init_test_client();
 
         let response = client.get("/job/aasldfjkasldgkj").dispatch();
         assert_eq!(response.status(), Status::NotFound);
     }
 
+    #[test]
+    fn malformed_shortcodes_are_bad_requests() {
+        let (_rt, client) = init_test_client();
+
+        let response = client.get("/job/not.valid").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.get("/job/raw/not.valid").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn escrow_addresses_redirect_to_the_job() {
+        use rocket::http::ContentType;
+
+        let (_rt, client) = init_test_client();
+        let address = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+        let response = client.get(format!("/escrow/{}", address)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.get("/escrow/not-an-address").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        client
+            .post("/")
+            .header(ContentType::Form)
+            .body(format!(
+                "escrow_id={}&manifest_url=&posted=0&expires=&password=&shortcode=by-escrow",
+                address
+            ))
+            .dispatch();
+        for address in [address.to_owned(), address.to_lowercase()] {
+            let response = client.get(format!("/escrow/{}", address)).dispatch();
+            assert_eq!(response.status(), Status::SeeOther);
+            assert_eq!(
+                response.headers().get_one("Location"),
+                Some("/job/by-escrow")
+            );
+        }
+    }
+
+    #[test]
+    fn custom_shortcodes_can_only_be_used_once() {
+        use rocket::http::ContentType;
+
+        let (_rt, client) = init_test_client();
+        let form = "escrow_id=0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed&manifest_url=&posted=0&expires=&password=&shortcode=my-job";
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(form)
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/job/my-job"));
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(form)
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+    }
+
+    #[test]
+    fn job_board_filters_by_task_type_and_shows_rewards() {
+        use crate::data::query;
+        use crate::domain::job::field::{
+            ManifestCheck, ManifestStatus, SearchText, TaskType, TokenAmount,
+        };
+        use crate::service::action;
+        use crate::Time;
+        use rocket::http::ContentType;
+
+        let (rt, client) = init_test_client();
+        client
+            .post("/")
+            .header(ContentType::Form)
+            .body("escrow_id=0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed&manifest_url=&posted=0&expires=&password=&shortcode=board-job")
+            .dispatch();
+        let db = client.rocket().state::<AppDatabase>().unwrap();
+        rt.block_on(async {
+            let job = action::get_job("board-job".into(), db.get_pool())
+                .await
+                .unwrap();
+            let check = ManifestCheck {
+                job_id: job.job_id,
+                status: ManifestStatus::Unverifiable,
+                task_type: Some(TaskType::ImageLabelBinary),
+                task_bid: TokenAmount::from_decimal("0.05", 18).ok(),
+                total_tasks: Some(200),
+                search_text: Some(SearchText {
+                    description: Some("Is there a <cat> in the photo?".to_owned()),
+                    manifest: "image_label_binary".to_owned(),
+                }),
+                checked: Time::now(),
+            };
+            query::record_manifest_check(check, db.get_pool())
+                .await
+                .unwrap();
+        });
+
+        let page = |uri: &str| client.get(uri).dispatch().into_string().unwrap_or_default();
+        let home = page("/");
+        assert!(home.contains("/job/board-job"));
+        assert!(home.contains("?task_type=image_label_binary"));
+        assert!(home.contains("0.05"));
+        let job = page("/job/board-job");
+        assert!(job.contains("0.05"));
+        assert!(job.contains("200"));
+        assert!(page("/?task_type=image_label_binary").contains("/job/board-job"));
+        assert!(!page("/?task_type=text_free_entry").contains("/job/board-job"));
+        let search = page("/?q=cat");
+        assert!(search.contains("/job/board-job"));
+        assert!(search.contains("&lt;<mark>cat</mark>&gt;"));
+        assert!(search.contains("q=cat&amp;task_type=image_label_binary"));
+        assert!(!page("/?q=dog").contains("/job/board-job"));
+
+        let response = client.get("/?task_type=unknown").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn job_page_shows_manifest_verification() {
+        use rocket::http::ContentType;
+
+        let (_rt, client) = init_test_client();
+        let form = |manifest_url: &str, shortcode: &str| {
+            format!(
+                "escrow_id=0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed&manifest_url={}&posted=0&expires=&password=&shortcode={}",
+                manifest_url, shortcode
+            )
+        };
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(form("https%3A%2F%2F127.0.0.1%2Fjob.json", "private"))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert!(response
+            .into_string()
+            .unwrap_or_default()
+            .contains("not a public address"));
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(form("https%3A%2F%2Fexample.com%2Fjob.json", "public"))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let page = client
+            .get("/job/public")
+            .dispatch()
+            .into_string()
+            .unwrap_or_default();
+        assert!(page.contains("Unverifiable"));
+    }
+
     #[test]
     fn requires_password_when_applicable() {
-        use crate::domain::job::field::{EscrowId, Expires, ManifestUrl, Password};
+        use crate::domain::job::field::{
+            EscrowId, Expires, ManifestUrl, Password, Posted, VanityCode,
+        };
         use crate::service;
-        use rocket::http::{Cookie, EscrowIdType};
+        use rocket::http::{ContentType, Cookie, SameSite};
 
         let (rt, client) = init_test_client();
 
         let db = client.rocket().state::<AppDatabase>().unwrap();
 
         let req = service::ask::NewJob {
-            escrow_id: EscrowId::new("escrow_id").unwrap(),
+            escrow_id: EscrowId::new("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap(),
             expires: Expires::default(),
             password: Password::new("123".to_owned()).unwrap(),
-            title: ManifestUrl::default(),
+            manifest_url: ManifestUrl::default(),
+            posted: Posted::new(0),
+            shortcode: VanityCode::default(),
         };
         let job = rt
-            .block_on(async move { service::action::new_job(req, db.get_pool()).await })
+            .block_on(async move {
+                service::action::new_job(
+                    req,
+                    &Default::default(),
+                    &Default::default(),
+                    db.get_pool(),
+                )
+                .await
+            })
             .unwrap();
 
         // Block job when no password is provided
@@ -273,10 +546,16 @@ pub mod test {
         // Get job when the password is provided
         let response = client
             .post(format!("/job/{}", job.shortcode.as_str()))
-            .header(EscrowIdType::Form)
+            .header(ContentType::Form)
             .body("password=123")
             .dispatch();
         assert_eq!(response.status(), Status::Ok);
+        let cookie = response
+            .cookies()
+            .get("password")
+            .expect("password cookie is set");
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
         // Get job when the password is provided
         let response = client
             .get(format!("/job/raw/{}", job.shortcode.as_str()))
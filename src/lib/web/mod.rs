@@ -1,17 +1,80 @@
 //! All web-related things: API, templates, routing, workers.
 
+pub mod admin;
 pub mod api;
 pub mod ctx;
 pub mod form;
 pub mod http;
 pub mod renderer;
 pub mod responsecounter;
+pub mod status;
+pub mod trace;
 
 pub use responsecounter::ResponseCounter;
 
+use rocket::http::{Cookie, SameSite};
+use serde::{Deserialize, Serialize};
+
 /// Cookie name for storing password, after it is entered by the user.
 pub const PASSWORD_COOKIE: &str = "password";
 
+/// The `SameSite` policy of the cookies set by the service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSitePolicy {
+    /// Only sent with requests from this site.
+    Strict,
+    /// Also sent when following links from other sites.
+    Lax,
+    /// Sent with all requests. Browsers require the cookie to be secure.
+    None,
+}
+
+/// Settings for the cookies set by the service.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CookieConfig {
+    /// Only send cookies over HTTPS.
+    pub secure: bool,
+    /// The `SameSite` policy of the cookies.
+    pub same_site: SameSitePolicy,
+    /// How long cookies are kept, in seconds. Cookies last for the browser session if unset.
+    pub max_age_secs: Option<u64>,
+}
+
+/// The Default implementation sets session cookies which are `SameSite=Lax` and sent over HTTP.
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secure: false,
+            same_site: SameSitePolicy::Lax,
+            max_age_secs: None,
+        }
+    }
+}
+
+impl CookieConfig {
+    /// Build a cookie named `name` with these settings.
+    ///
+    /// Cookies are never readable from JavaScript.
+    pub fn cookie(&self, name: &'static str, value: String) -> Cookie<'static> {
+        let same_site = match self.same_site {
+            SameSitePolicy::Strict => SameSite::Strict,
+            SameSitePolicy::Lax => SameSite::Lax,
+            SameSitePolicy::None => SameSite::None,
+        };
+        let mut cookie = Cookie::build(name, value)
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(same_site)
+            .finish();
+        if let Some(max_age) = self.max_age_secs {
+            cookie.set_max_age(rocket::time::Duration::seconds(max_age as i64));
+        }
+        cookie
+    }
+}
+
 /// The possible errors that can occur when responding to an HTTP request.
 ///
 /// This is only used when responding to page requests. See [`ApiError`](api::ApiError) for error responses from the API.
@@ -23,6 +86,9 @@ pub enum PageError {
     /// Problem rendering the page.
     #[response(status = 500)]
     Render(String),
+    /// Malformed request.
+    #[response(status = 400)]
+    BadRequest(String),
     /// Data not found.
     #[response(status = 404)]
     NotFound(String),
@@ -37,6 +103,13 @@ impl From<handlebars::RenderError> for PageError {
     }
 }
 
+/// A malformed path parameter, such as a [`ShortCode`](crate::ShortCode).
+impl From<crate::JobError> for PageError {
+    fn from(err: crate::JobError) -> Self {
+        PageError::BadRequest(format!("{}", err))
+    }
+}
+
 impl From<serde_json::Error> for PageError {
     fn from(err: serde_json::Error) -> Self {
         PageError::Serialization(format!("{}", err))
@@ -72,6 +145,13 @@ pub mod test {
             database,
             response_counter: hit_counter,
             maintenance,
+            admin_token: Default::default(),
+            cookies: Default::default(),
+            backup_directory: Default::default(),
+            shortcodes: Default::default(),
+            manifests: Default::default(),
+            webhooks: Default::default(),
+            address: crate::config::ServerConfig::default().bind(),
         }
     }
 
@@ -1,18 +1,155 @@
-//! Background thread that commits responses to the database.
+//! Background task that commits responses to the database.
 
 use crate::data::DatabasePool;
+use crate::domain::stats::{Channel, Granularity};
+use crate::metrics;
 use crate::service::{self, ServiceError};
-use crate::ShortCode;
-use crossbeam_channel::TryRecvError;
-use crossbeam_channel::{unbounded, Sender};
+use crate::web::api::ApiKey;
+use crate::{ShortCode, Time};
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
 
-/// Thread-safe shared storage of pending responses.
-type HitStore = Arc<Mutex<HashMap<ShortCode, u32>>>;
+/// Identifies an entry of [`PendingHits`].
+enum PendingKey {
+    Response((ShortCode, i64)),
+    Channel((ShortCode, Channel)),
+    ApiKey((ShortCode, ApiKey)),
+    Visitor((ShortCode, i64, String)),
+}
+
+/// Responses which have not yet been committed to the database.
+#[derive(Default)]
+struct PendingHits {
+    /// Responses per job and hourly bucket.
+    responses: HashMap<(ShortCode, i64), u32>,
+    /// Responses per job and [`Channel`].
+    channels: HashMap<(ShortCode, Channel), u32>,
+    /// API responses per job and [`ApiKey`].
+    api_keys: HashMap<(ShortCode, ApiKey), u32>,
+    /// Visitors seen per job and day.
+    visitors: HashSet<(ShortCode, i64, String)>,
+    /// The entries of each job.
+    entries: HashMap<ShortCode, Vec<PendingKey>>,
+    /// Every job with entries, in the order its first entry was added.
+    order: VecDeque<ShortCode>,
+}
+
+/// Add `count` to the entry for `key`, remembering the job's new entries.
+fn add_count<K: Clone + Eq + Hash>(
+    counts: &mut HashMap<K, u32>,
+    entries: &mut Vec<PendingKey>,
+    key: K,
+    count: u32,
+    pending_key: fn(K) -> PendingKey,
+) {
+    match counts.entry(key) {
+        Entry::Occupied(entry) => *entry.into_mut() += count,
+        Entry::Vacant(entry) => {
+            entries.push(pending_key(entry.key().clone()));
+            entry.insert(count);
+        }
+    }
+}
+
+impl PendingHits {
+    /// Add `count` responses described by the [`Hit`].
+    fn add(&mut self, hit: Hit, count: u32) {
+        let now = Time::now().timestamp();
+        let bucket = Granularity::Hourly.bucket_start(now);
+        let entries = match self.entries.entry(hit.shortcode.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                self.order.push_back(entry.key().clone());
+                entry.insert(vec![])
+            }
+        };
+        add_count(
+            &mut self.responses,
+            entries,
+            (hit.shortcode.clone(), bucket),
+            count,
+            PendingKey::Response,
+        );
+        add_count(
+            &mut self.channels,
+            entries,
+            (hit.shortcode.clone(), hit.channel),
+            count,
+            PendingKey::Channel,
+        );
+        if let Some(api_key) = hit.api_key {
+            add_count(
+                &mut self.api_keys,
+                entries,
+                (hit.shortcode.clone(), api_key),
+                count,
+                PendingKey::ApiKey,
+            );
+        }
+        if let Some(visitor) = hit.visitor {
+            let day = Granularity::Daily.bucket_start(now);
+            let key = (hit.shortcode, day, visitor.0);
+            if self.visitors.insert(key.clone()) {
+                entries.push(PendingKey::Visitor(key));
+            }
+        }
+    }
+
+    /// Drop the entries of the oldest jobs until at most `max` remain, returning
+    /// the number dropped.
+    ///
+    /// Every entry of a job is dropped together, so the responses which are
+    /// committed for a job always match its channel counts.
+    fn truncate(&mut self, max: usize) -> usize {
+        let mut dropped = 0;
+        while self.len() > max {
+            let shortcode = match self.order.pop_front() {
+                Some(shortcode) => shortcode,
+                None => break,
+            };
+            for key in self.entries.remove(&shortcode).unwrap_or_default() {
+                match key {
+                    PendingKey::Response(key) => {
+                        self.responses.remove(&key);
+                    }
+                    PendingKey::Channel(key) => {
+                        self.channels.remove(&key);
+                    }
+                    PendingKey::ApiKey(key) => {
+                        self.api_keys.remove(&key);
+                    }
+                    PendingKey::Visitor(key) => {
+                        self.visitors.remove(&key);
+                    }
+                }
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
+    /// The number of distinct entries waiting to be committed.
+    fn len(&self) -> usize {
+        self.responses.len() + self.channels.len() + self.api_keys.len() + self.visitors.len()
+    }
+
+    /// Returns whether there is nothing to commit.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
 
 /// The possible errors that can occur when processing responses.
 #[derive(Debug, thiserror::Error)]
@@ -20,113 +157,607 @@ enum HitCountError {
     /// Problem with the service.
     #[error("service error: {0}")]
     Service(#[from] ServiceError),
-    /// Problem with the channel.
-    #[error("communication error: {0}")]
-    Channel(#[from] crossbeam_channel::SendError<HitCountMsg>),
 }
 
+/// The secret mixed into every [`Visitor`] fingerprint, so that stored
+/// fingerprints can not be traced back to client addresses.
+///
+/// Fingerprints only match across restarts when the same salt is configured.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct VisitorSalt(String);
+
+impl VisitorSalt {
+    /// Create a new [`VisitorSalt`].
+    pub fn new<T: Into<String>>(salt: T) -> Self {
+        Self(salt.into())
+    }
+
+    /// Create a random [`VisitorSalt`].
+    pub fn random() -> Self {
+        Self(
+            (0..32)
+                .map(|_| format!("{:02x}", rand::random::<u8>()))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Debug for VisitorSalt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VisitorSalt(<redacted>)")
+    }
+}
+
+/// An approximate identity for the client that accessed a [`Job`](crate::Job).
+///
+/// The fingerprint is a salted SHA-256 hash of the client IP address and user
+/// agent, so clients behind the same proxy with the same browser will be counted once.
+#[derive(Debug, Clone)]
+pub struct Visitor(String);
+
+impl Visitor {
+    /// Create a [`Visitor`] fingerprint from the client address and user agent.
+    pub fn new(
+        salt: &VisitorSalt,
+        address: Option<std::net::IpAddr>,
+        user_agent: Option<&str>,
+    ) -> Self {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(salt.0.as_bytes());
+        hasher.update([0]);
+        if let Some(address) = address {
+            hasher.update(address.to_string());
+        }
+        hasher.update([0]);
+        if let Some(user_agent) = user_agent {
+            hasher.update(user_agent);
+        }
+        // Half of the digest is plenty to tell the visitors of a job apart.
+        Self(
+            hasher.finalize()[..16]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+
+    /// Return a reference to the underlying [`&str`].
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Allows a [`Visitor`] to be used as a [request guard](https://rocket.rs/v0.5-rc/guide/requests/#request-guards) in a route.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Visitor {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let counter = rocket::outcome::try_outcome!(req.guard::<&State<ResponseCounter>>().await);
+        Outcome::Success(Visitor::new(
+            counter.visitor_salt(),
+            req.client_ip(),
+            req.headers().get_one("User-Agent"),
+        ))
+    }
+}
+
+/// A single access of a [`Job`](crate::Job).
+#[derive(Debug, Clone)]
+pub struct Hit {
+    /// The job that was accessed.
+    pub shortcode: ShortCode,
+    /// How the job was accessed.
+    pub channel: Channel,
+    /// The API key used to access the job, if any.
+    pub api_key: Option<ApiKey>,
+    /// The client that accessed the job, if known.
+    pub visitor: Option<Visitor>,
+}
+
+impl Hit {
+    /// Create a new [`Hit`] for the [`Job`](crate::Job) referenced by the [`ShortCode`].
+    pub fn new(shortcode: ShortCode, channel: Channel) -> Self {
+        Self {
+            shortcode,
+            channel,
+            api_key: None,
+            visitor: None,
+        }
+    }
+
+    /// Attribute this hit to an [`ApiKey`].
+    pub fn with_api_key(mut self, api_key: ApiKey) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Attribute this hit to a [`Visitor`].
+    pub fn with_visitor(mut self, visitor: Visitor) -> Self {
+        self.visitor = Some(visitor);
+        self
+    }
+}
+
+/// The number of times the final commit is attempted during shutdown.
+const SHUTDOWN_COMMIT_ATTEMPTS: u32 = 3;
+
 /// Message used on the communication channel.
 enum HitCountMsg {
-    /// Save the responses to the database.
-    Commit,
-    /// Add some responses to this [`ShortCode`](crate::domain::job::field::ShortCode).
-    Hit(ShortCode, u32),
+    /// Add some responses to the [`Job`](crate::Job) described by the [`Hit`].
+    Hit(Hit, u32),
+    /// Commit all pending responses and stop the task.
+    Shutdown,
+}
+
+/// Settings for the [`ResponseCounter`] task.
+#[derive(Debug, Clone)]
+pub struct ResponseCounterConfig {
+    /// How often pending responses are committed.
+    pub commit_interval: Duration,
+    /// Pending responses are committed early once this many entries are buffered.
+    pub batch_size: usize,
+    /// The most entries that are buffered while commits keep failing. Every
+    /// entry of the oldest jobs is dropped to make room for new ones.
+    pub max_pending: usize,
+    /// The secret mixed into [`Visitor`] fingerprints.
+    pub visitor_salt: VisitorSalt,
+}
+
+/// The Default implementation commits every 5 seconds, or every 1000 entries,
+/// buffers up to 100000 entries, and uses a random [`VisitorSalt`].
+impl Default for ResponseCounterConfig {
+    fn default() -> Self {
+        Self {
+            commit_interval: Duration::from_secs(5),
+            batch_size: 1000,
+            max_pending: 100_000,
+            visitor_salt: VisitorSalt::random(),
+        }
+    }
+}
+
+/// State of the [`ResponseCounter`] task that can be read from outside the task.
+struct CounterState {
+    /// Whether the task is still accepting hits.
+    running: AtomicBool,
+    /// The number of distinct entries waiting to be committed.
+    pending: AtomicUsize,
 }
 
-/// A threaded hit counter.
+impl CounterState {
+    /// Update the number of entries waiting to be committed.
+    fn set_pending(&self, pending: usize) {
+        self.pending.store(pending, Ordering::Relaxed);
+        metrics::RESPONSE_COUNTER_PENDING.set(pending as i64);
+    }
+}
+
+/// A background hit counter.
+///
+/// The hit counter spawns a tokio task which manages a buffer of accumulated responses.
+/// Periodically, or once the buffer reaches the configured batch size, the task will
+/// commit the responses to the database, both as a running total on the job and as
+/// hourly buckets for the response time series. Responses are also recorded per
+/// [`Channel`], per [`ApiKey`], and per [`Visitor`].
 ///
-/// The hit counter spawns a separate thread which manages a buffer of accumulated responses.
-/// Periodically, the thread will commit the responses to the database.
+/// Failed commits are retried on the next interval. Meanwhile at most
+/// [`max_pending`](ResponseCounterConfig::max_pending) entries are buffered, and
+/// the entries of the oldest jobs are dropped to make room for new ones.
+///
+/// Calling [`shutdown`](ResponseCounter::shutdown) commits everything that is still
+/// buffered and stops the task.
 ///
 /// This is done as a performance optimization for SQLite, since writes to a SQLite
 /// database block all reads.
 pub struct ResponseCounter {
-    tx: Sender<HitCountMsg>,
+    tx: UnboundedSender<HitCountMsg>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    state: Arc<CounterState>,
+    visitor_salt: VisitorSalt,
 }
 
 impl ResponseCounter {
     /// Save the pending responses to the database.
-    fn commit_responses(
-        responses: HitStore,
-        handle: Handle,
-        pool: DatabasePool,
+    ///
+    /// All responses are written in a single transaction, so either every pending
+    /// response is saved or none are.
+    async fn commit_responses(
+        pending: &PendingHits,
+        pool: &DatabasePool,
     ) -> Result<(), HitCountError> {
-        let responses = Arc::clone(&responses);
-        let responses: Vec<(ShortCode, u32)> = {
-            let mut responses = responses.lock();
-            let responses_vec = responses.iter().map(|(k, v)| (k.clone(), *v)).collect();
-            responses.clear();
-            responses_vec
-        };
-        handle.block_on(async move {
-            let transaction = service::action::begin_transaction(&pool).await?;
-            for (shortcode, responses) in responses {
-                if let Err(e) =
-                    service::action::increase_hit_count(&shortcode, responses, &pool).await
-                {
-                    eprintln!("error increasing hit count: {}", e);
-                }
-            }
-            Ok(service::action::end_transaction(transaction).await?)
-        })
+        let hits: Vec<(ShortCode, u32)> = pending
+            .responses
+            .iter()
+            .fold(HashMap::new(), |mut hits, ((shortcode, _), count)| {
+                *hits.entry(shortcode.clone()).or_insert(0) += count;
+                hits
+            })
+            .into_iter()
+            .collect();
+        let stats: Vec<(ShortCode, i64, u32)> = pending
+            .responses
+            .iter()
+            .map(|((shortcode, bucket), count)| (shortcode.clone(), *bucket, *count))
+            .collect();
+        let channels: Vec<(ShortCode, Channel, u32)> = pending
+            .channels
+            .iter()
+            .map(|((shortcode, channel), count)| (shortcode.clone(), *channel, *count))
+            .collect();
+        let api_keys: Vec<(ShortCode, ApiKey, u32)> = pending
+            .api_keys
+            .iter()
+            .map(|((shortcode, api_key), count)| (shortcode.clone(), api_key.clone(), *count))
+            .collect();
+        let visitors: Vec<(ShortCode, i64, String)> = pending.visitors.iter().cloned().collect();
+
+        let mut transaction = service::action::begin_transaction(pool).await?;
+        service::action::increase_hit_counts(&hits, &mut transaction).await?;
+        service::action::record_response_stats(&stats, &mut transaction).await?;
+        service::action::record_channel_responses(&channels, &mut transaction).await?;
+        service::action::record_api_key_responses(&api_keys, &mut transaction).await?;
+        service::action::record_visitors(&visitors, &mut transaction).await?;
+        Ok(service::action::end_transaction(transaction).await?)
     }
 
-    /// Process an incoming [`message`](HitCountMsg).
-    fn process_msg(
-        msg: HitCountMsg,
-        responses: HitStore,
-        handle: Handle,
-        pool: DatabasePool,
-    ) -> Result<(), HitCountError> {
-        match msg {
-            HitCountMsg::Commit => Self::commit_responses(responses, handle, pool)?,
-            HitCountMsg::Hit(shortcode, count) => {
-                let mut hitcount = responses.lock();
-                let hitcount = hitcount.entry(shortcode).or_insert(0);
-                *hitcount += count;
-            }
+    /// Commit and clear the pending responses, if there are any.
+    ///
+    /// If the commit fails, the responses stay pending so that they are retried
+    /// by the next flush.
+    async fn flush(pending: &mut PendingHits, pool: &DatabasePool) -> Result<(), HitCountError> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let timer = metrics::RESPONSE_COUNTER_COMMIT_DURATION.start_timer();
+        let res = Self::commit_responses(pending, pool).await;
+        timer.observe_duration();
+        if res.is_err() {
+            metrics::RESPONSE_COUNTER_COMMIT_FAILURES.inc();
         }
+        res?;
+        *pending = PendingHits::default();
         Ok(())
     }
 
-    /// Create a new [`ResponseCounter`].
-    pub fn new(pool: DatabasePool, handle: Handle) -> Self {
-        let (tx, rx) = unbounded();
-        let tx_clone = tx.clone();
-
-        let _ = std::thread::spawn(move || {
-            println!("HitCounter thread spawned");
-            let store: HitStore = Arc::new(Mutex::new(HashMap::new()));
-
-            loop {
-                match rx.try_recv() {
-                    Ok(msg) => {
-                        if let Err(e) =
-                            Self::process_msg(msg, store.clone(), handle.clone(), pool.clone())
-                        {
-                            eprintln!("message processing error: {}", e);
-                        }
-                    }
-                    Err(e) => match e {
-                        TryRecvError::Empty => {
-                            std::thread::sleep(Duration::from_secs(5));
-                            if let Err(e) = tx_clone.send(HitCountMsg::Commit) {
-                                eprintln!("error sending commit msg to responses channel: {}", e);
+    /// Run the counter task, keeping the shared [`CounterState`] up to date.
+    async fn run(
+        rx: UnboundedReceiver<HitCountMsg>,
+        pool: DatabasePool,
+        config: ResponseCounterConfig,
+        state: Arc<CounterState>,
+    ) {
+        Self::process(rx, pool, config, &state).await;
+        state.set_pending(0);
+        state.running.store(false, Ordering::Relaxed);
+    }
+
+    /// Buffer incoming hits and commit them until a shutdown is requested.
+    async fn process(
+        mut rx: UnboundedReceiver<HitCountMsg>,
+        pool: DatabasePool,
+        config: ResponseCounterConfig,
+        state: &CounterState,
+    ) {
+        let mut pending = PendingHits::default();
+        let mut retrying = false;
+        let mut dropping = false;
+        let mut interval = tokio::time::interval(config.commit_interval);
+        // The first tick completes immediately.
+        interval.tick().await;
+
+        loop {
+            let flush = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(HitCountMsg::Hit(hit, count)) => {
+                        pending.add(hit, count);
+                        let dropped = pending.truncate(config.max_pending);
+                        if dropped > 0 {
+                            if !dropping {
+                                tracing::warn!(
+                                    max_pending = config.max_pending,
+                                    "response buffer is full, dropping the oldest entries"
+                                );
+                                dropping = true;
                             }
+                            metrics::RESPONSE_COUNTER_DROPPED.inc_by(dropped as u64);
                         }
-                        _ => break,
-                    },
+                        state.set_pending(pending.len());
+                        // Failed commits are only retried on the interval, rather than on every hit.
+                        !retrying && pending.len() >= config.batch_size
+                    }
+                    Some(HitCountMsg::Shutdown) | None => break,
+                },
+                _ = interval.tick() => true,
+            };
+            if flush {
+                retrying = match Self::flush(&mut pending, &pool).await {
+                    Ok(()) => {
+                        dropping = false;
+                        false
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to commit responses, will retry");
+                        true
+                    }
+                };
+                state.set_pending(pending.len());
+            }
+        }
+
+        // Hits which raced with the shutdown request are still counted.
+        rx.close();
+        while let Ok(msg) = rx.try_recv() {
+            if let HitCountMsg::Hit(hit, count) = msg {
+                pending.add(hit, count);
+            }
+        }
+        for attempt in 1..=SHUTDOWN_COMMIT_ATTEMPTS {
+            match Self::flush(&mut pending, &pool).await {
+                Ok(()) => return,
+                Err(e) => {
+                    tracing::warn!(attempt, error = %e, "failed to commit responses on shutdown");
+                    tokio::time::sleep(Duration::from_millis(100 * u64::from(attempt))).await;
                 }
             }
+        }
+        tracing::error!(
+            entries = pending.len(),
+            "discarding uncommitted response entries"
+        );
+    }
+
+    /// Create a new [`ResponseCounter`] with the default [`ResponseCounterConfig`].
+    pub fn new(pool: DatabasePool, handle: Handle) -> Self {
+        Self::with_config(pool, handle, ResponseCounterConfig::default())
+    }
+
+    /// Create a new [`ResponseCounter`].
+    pub fn with_config(pool: DatabasePool, handle: Handle, config: ResponseCounterConfig) -> Self {
+        let (tx, rx) = unbounded_channel();
+        let visitor_salt = config.visitor_salt.clone();
+        let state = Arc::new(CounterState {
+            running: AtomicBool::new(true),
+            pending: AtomicUsize::new(0),
         });
+        let task = handle.spawn(Self::run(rx, pool, config, Arc::clone(&state)));
+        Self {
+            tx,
+            task: Mutex::new(Some(task)),
+            state,
+            visitor_salt,
+        }
+    }
+
+    /// The secret mixed into the [`Visitor`] fingerprints of the hits.
+    pub fn visitor_salt(&self) -> &VisitorSalt {
+        &self.visitor_salt
+    }
+
+    /// Returns whether the background task is still accepting hits.
+    pub fn is_running(&self) -> bool {
+        self.state.running.load(Ordering::Relaxed)
+    }
+
+    /// The number of distinct response entries waiting to be committed.
+    pub fn pending(&self) -> usize {
+        self.state.pending.load(Ordering::Relaxed)
+    }
 
-        Self { tx }
+    /// Add `count` number of responses to the [`Job`](crate::Job) that is referenced by the [`Hit`].
+    pub fn hit(&self, hit: Hit, count: u32) {
+        if self.tx.send(HitCountMsg::Hit(hit, count)).is_err() {
+            tracing::warn!("response counter has shut down, discarding hit");
+        }
     }
 
-    /// Add `count` number of responses to the [`Job`](crate::Job) that is referenced by the [`ShortCode`](crate::domain::job::field::ShortCode).
-    pub fn hit(&self, shortcode: ShortCode, count: u32) {
-        if let Err(e) = self.tx.send(HitCountMsg::Hit(shortcode, count)) {
-            eprintln!("hit count error: {}", e);
+    /// Commit all pending responses and stop the background task.
+    ///
+    /// Hits received after shutdown are discarded. Calling this more than once has no effect.
+    pub async fn shutdown(&self) {
+        let task = self.task.lock().take();
+        if let Some(task) = task {
+            let _ = self.tx.send(HitCountMsg::Shutdown);
+            if let Err(e) = task.await {
+                tracing::error!(error = %e, "response counter task failed");
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Hit, PendingHits, ResponseCounter, ResponseCounterConfig, Visitor, VisitorSalt};
+    use crate::data::test::{execute, new_db};
+    use crate::domain::job::field::{EscrowId, Expires, ManifestUrl, Password, Posted, VanityCode};
+    use crate::domain::stats::Channel;
+    use crate::service::{action, ask};
+    use crate::test::async_runtime;
+    use crate::Job;
+    use std::time::Duration;
+
+    fn new_job(rt: &tokio::runtime::Runtime, pool: &crate::data::DatabasePool) -> Job {
+        let req = ask::NewJob {
+            escrow_id: EscrowId::new("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap(),
+            manifest_url: ManifestUrl::default(),
+            posted: Posted::new(0),
+            expires: Expires::default(),
+            password: Password::default(),
+            shortcode: VanityCode::default(),
+        };
+        rt.block_on(action::new_job(
+            req,
+            &Default::default(),
+            &Default::default(),
+            pool,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn shutdown_commits_pending_hits() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let job = new_job(&rt, &pool);
+
+        let config = ResponseCounterConfig {
+            commit_interval: Duration::from_secs(3600),
+            batch_size: usize::MAX,
+            ..ResponseCounterConfig::default()
+        };
+        let counter = ResponseCounter::with_config(pool.clone(), rt.handle().clone(), config);
+        for _ in 0..100 {
+            counter.hit(Hit::new(job.shortcode.clone(), Channel::Html), 1);
+        }
+        for _ in 0..20 {
+            counter.hit(Hit::new(job.shortcode.clone(), Channel::Raw), 2);
+        }
+        rt.block_on(counter.shutdown());
+
+        let job = rt
+            .block_on(action::get_job(job.shortcode.clone().into(), &pool))
+            .unwrap();
+        assert_eq!(job.responses.into_inner(), 140);
+        let breakdown = rt
+            .block_on(action::get_response_breakdown(&job.shortcode, &pool))
+            .unwrap();
+        assert_eq!(breakdown.html, 100);
+        assert_eq!(breakdown.raw, 40);
+    }
+
+    #[test]
+    fn commits_when_batch_is_full() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let job = new_job(&rt, &pool);
+
+        let config = ResponseCounterConfig {
+            commit_interval: Duration::from_secs(3600),
+            batch_size: 1,
+            ..ResponseCounterConfig::default()
+        };
+        let counter = ResponseCounter::with_config(pool.clone(), rt.handle().clone(), config);
+        counter.hit(Hit::new(job.shortcode.clone(), Channel::Api), 1);
+
+        let responses = rt.block_on(async {
+            for _ in 0..50 {
+                let job = action::get_job(job.shortcode.clone().into(), &pool)
+                    .await
+                    .unwrap();
+                if job.responses.clone().into_inner() > 0 {
+                    return job.responses.into_inner();
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            0
+        });
+        assert_eq!(responses, 1);
+        rt.block_on(counter.shutdown());
+    }
+
+    #[test]
+    fn failed_commit_is_rolled_back_and_retried() {
+        let rt = async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let job = new_job(&rt, &pool);
+
+        let mut pending = PendingHits::default();
+        pending.add(
+            Hit::new(job.shortcode.clone(), Channel::Html).with_visitor(Visitor::new(
+                &VisitorSalt::random(),
+                None,
+                None,
+            )),
+            3,
+        );
+
+        // Break the last statement of the batch so the whole transaction fails.
+        rt.block_on(execute(
+            &pool,
+            "ALTER TABLE job_visitors RENAME TO visitors",
+        ))
+        .unwrap();
+        assert!(rt
+            .block_on(ResponseCounter::flush(&mut pending, &pool))
+            .is_err());
+        assert!(!pending.is_empty());
+        let unchanged = rt
+            .block_on(action::get_job(job.shortcode.clone().into(), &pool))
+            .unwrap();
+        assert_eq!(unchanged.responses.into_inner(), 0);
+
+        rt.block_on(execute(
+            &pool,
+            "ALTER TABLE visitors RENAME TO job_visitors",
+        ))
+        .unwrap();
+        assert!(rt
+            .block_on(ResponseCounter::flush(&mut pending, &pool))
+            .is_ok());
+        assert!(pending.is_empty());
+        let breakdown = rt
+            .block_on(action::get_response_breakdown(&job.shortcode, &pool))
+            .unwrap();
+        assert_eq!(breakdown.html, 3);
+        assert_eq!(breakdown.unique_visitors, 1);
+    }
+
+    #[test]
+    fn visitors_depend_on_the_salt() {
+        let address = Some("203.0.113.7".parse().unwrap());
+        let salt = VisitorSalt::new("salt");
+        let visitor = Visitor::new(&salt, address, Some("agent"));
+        assert_eq!(visitor.as_str().len(), 32);
+        assert_eq!(
+            visitor.as_str(),
+            Visitor::new(&salt, address, Some("agent")).as_str()
+        );
+        assert_ne!(
+            visitor.as_str(),
+            Visitor::new(&VisitorSalt::new("pepper"), address, Some("agent")).as_str()
+        );
+        assert_ne!(
+            visitor.as_str(),
+            Visitor::new(&salt, address, Some("other agent")).as_str()
+        );
+        assert_eq!(format!("{:?}", salt), "VisitorSalt(<redacted>)");
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_when_full() {
+        let mut pending = PendingHits::default();
+        let salt = VisitorSalt::random();
+        pending.add(
+            Hit::new("old".into(), Channel::Html).with_visitor(Visitor::new(&salt, None, None)),
+            1,
+        );
+        pending.add(Hit::new("new".into(), Channel::Raw), 1);
+        pending.add(Hit::new("new".into(), Channel::Raw), 1);
+        // The old job's newest entry is added after the new job's.
+        pending.add(Hit::new("old".into(), Channel::Api), 1);
+        assert_eq!(pending.len(), 6);
+
+        assert_eq!(pending.truncate(2), 4);
+        assert_eq!(pending.len(), 2);
+        assert!(pending.visitors.is_empty());
+        assert!(pending
+            .responses
+            .keys()
+            .all(|(shortcode, _)| shortcode.as_str() == "new"));
+        assert_eq!(
+            pending.channels.get(&("new".into(), Channel::Raw)).copied(),
+            Some(2)
+        );
+        assert_eq!(
+            pending.responses.values().sum::<u32>(),
+            pending.channels.values().sum::<u32>()
+        );
+        assert_eq!(pending.truncate(2), 0);
+    }
+}
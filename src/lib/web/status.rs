@@ -0,0 +1,221 @@
+//! Health checks and service status.
+
+use crate::data::AppDatabase;
+use crate::domain::health::{Readiness, SourceStatus, Status};
+use crate::domain::maintenance::Maintenance;
+use crate::service::action;
+use crate::web::api::ApiError;
+use crate::web::ResponseCounter;
+use crate::Time;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status as HttpStatus};
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::{Data, Request, Response};
+use serde::Serialize;
+use std::time::Instant;
+
+/// Each job source, and the maintenance task which syncs it.
+const SOURCES: &[(&str, &str)] = &[("graph", "sync")];
+
+/// Body of a successful liveness check.
+#[derive(Debug, Serialize)]
+pub struct Alive {
+    status: &'static str,
+}
+
+/// Route to check that the process is alive and handling requests.
+#[rocket::get("/healthz")]
+pub fn healthz() -> Json<Alive> {
+    Json(Alive { status: "ok" })
+}
+
+/// Route to check that the service can handle requests.
+///
+/// Responds with `503 Service Unavailable` if the database cannot be reached, or
+/// if any migration has not been applied.
+#[rocket::get("/readyz")]
+pub async fn readyz(database: &State<AppDatabase>) -> Custom<Json<Readiness>> {
+    let readiness = action::check_readiness(database.get_pool()).await;
+    let status = if readiness.is_ready() {
+        HttpStatus::Ok
+    } else {
+        HttpStatus::ServiceUnavailable
+    };
+    Custom(status, Json(readiness))
+}
+
+/// Route to report how up to date each job source is, along with job and
+/// response counter statistics.
+#[rocket::get("/status")]
+pub async fn status(
+    database: &State<AppDatabase>,
+    maintenance: &State<Maintenance>,
+    hit_counter: &State<ResponseCounter>,
+) -> Result<Json<Status>, ApiError> {
+    let pool = database.get_pool();
+    let latest_job_posted = action::get_latest_posted(pool).await?;
+    let lag_seconds = latest_job_posted
+        .as_ref()
+        .map(|posted| Time::now().timestamp() - posted.timestamp());
+    let sources = SOURCES
+        .iter()
+        .map(|(name, task)| {
+            let task = maintenance.task(task);
+            let blocks = maintenance.sync_progress().get();
+            SourceStatus {
+                name,
+                last_success: task.as_ref().and_then(|t| t.last_success.clone()),
+                last_error: task.as_ref().and_then(|t| t.last_error.clone()),
+                consecutive_failures: task.map(|t| t.consecutive_failures).unwrap_or_default(),
+                latest_job_posted: latest_job_posted.clone(),
+                lag_seconds,
+                indexed_block: blocks.indexed,
+                synced_block: blocks.synced,
+                lag_blocks: blocks.lag(),
+            }
+        })
+        .collect();
+    Ok(Json(Status {
+        sources,
+        response_counter_running: hit_counter.is_running(),
+        pending_responses: hit_counter.pending(),
+        jobs: action::get_job_counts(pool).await?,
+    }))
+}
+
+/// Route to export metrics in the Prometheus text format.
+#[rocket::get("/metrics")]
+pub fn metrics() -> (ContentType, String) {
+    let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (content_type, crate::metrics::gather())
+}
+
+/// When the current request started, stored in the request-local cache.
+struct RequestStart(Option<Instant>);
+
+/// Fairing which records the count and duration of requests for each route.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let route = req
+            .route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let method = req.method().as_str();
+        crate::metrics::HTTP_REQUESTS
+            .with_label_values(&[&route, method, &res.status().code.to_string()])
+            .inc();
+        if let RequestStart(Some(start)) = req.local_cache(|| RequestStart(None)) {
+            crate::metrics::HTTP_REQUEST_DURATION
+                .with_label_values(&[&route, method])
+                .observe(start.elapsed().as_secs_f64());
+        }
+    }
+}
+
+/// The health check and metrics [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn probe_routes() -> Vec<rocket::Route> {
+    rocket::routes!(healthz, readyz, metrics)
+}
+
+/// The status [`routes`](rocket::Route) which can be mounted by [`rocket`].
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes!(status)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::test::execute;
+    use crate::web::test::{client, config};
+    use rocket::http::Status;
+
+    #[test]
+    fn probes_report_ready_database() {
+        let rt = crate::test::async_runtime();
+        let client = client(config(rt.handle()));
+
+        let res = client.get("/healthz").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client.get("/readyz").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let body: serde_json::Value = res.into_json().expect("readiness is json");
+        assert_eq!(body["database"], true);
+        assert_eq!(body["pending_migrations"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn readyz_reports_pending_migrations() {
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let pool = config.database.get_pool();
+        let latest = pool.migrator().iter().map(|m| m.version).max().unwrap();
+        rt.block_on(execute(
+            pool,
+            &format!("DELETE FROM _sqlx_migrations WHERE version = {}", latest),
+        ))
+        .unwrap();
+        let client = client(config);
+
+        let res = client.get("/readyz").dispatch();
+        assert_eq!(res.status(), Status::ServiceUnavailable);
+        let body: serde_json::Value = res.into_json().expect("readiness is json");
+        assert_eq!(body["pending_migrations"], serde_json::json!([latest]));
+    }
+
+    #[test]
+    fn metrics_count_requests_and_api_errors() {
+        let rt = crate::test::async_runtime();
+        let client = client(config(rt.handle()));
+
+        let res = client.get("/api/job/missing").dispatch();
+        assert_eq!(res.status(), Status::BadRequest);
+        let res = client.get("/healthz").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client.get("/metrics").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let body = res.into_string().expect("metrics are text");
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/healthz",status="200"}"#));
+        assert!(
+            body.contains(r#"http_request_duration_seconds_count{method="GET",route="/healthz"}"#)
+        );
+        assert!(body.contains(r#"api_errors_total{variant="key_error"}"#));
+    }
+
+    #[test]
+    fn status_reports_sources_and_counts() {
+        let rt = crate::test::async_runtime();
+        let config = config(rt.handle());
+        let progress = config.maintenance.sync_progress();
+        progress.indexed(100);
+        progress.synced(100);
+        progress.indexed(112);
+        let client = client(config);
+
+        let res = client.get("/api/v1/status").dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let body: serde_json::Value = res.into_json().expect("status is json");
+        assert_eq!(body["sources"][0]["name"], "graph");
+        assert_eq!(body["sources"][0]["indexed_block"], 112);
+        assert_eq!(body["sources"][0]["synced_block"], 100);
+        assert_eq!(body["sources"][0]["lag_blocks"], 12);
+        assert_eq!(body["response_counter_running"], true);
+        assert!(body["jobs"]["total"].is_u64());
+    }
+}
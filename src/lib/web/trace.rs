@@ -0,0 +1,148 @@
+//! Request IDs and request logging.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::convert::Infallible;
+use std::fmt;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// HTTP header which carries the request ID, on both requests and responses.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The longest request ID that is accepted from a client.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// An identifier for a single request, used to correlate log entries.
+///
+/// A request ID provided by the client in the `x-request-id` header is reused,
+/// as long as it is short and only contains ASCII letters, digits, `-` and `_`.
+/// Otherwise, a new ID is generated.
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Use the client-provided ID, or generate a new one.
+    fn new(provided: Option<&str>) -> Self {
+        match provided {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LEN
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                Self(id.to_owned())
+            }
+            _ => Self(Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// Get the [`RequestId`] of the request, creating it if needed.
+    fn of<'r>(req: &'r Request<'_>) -> &'r Self {
+        req.local_cache(|| Self::new(req.headers().get_one(REQUEST_ID_HEADER)))
+    }
+
+    /// Return a reference to the underlying [`&str`].
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Allows a [`RequestId`] to be used as a [request guard](https://rocket.rs/v0.5-rc/guide/requests/#request-guards) in a route.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(req).clone())
+    }
+}
+
+/// When the current request started, stored in the request-local cache.
+struct RequestStart(Option<Instant>);
+
+/// Fairing which assigns a [`RequestId`] to every request, returns it in the
+/// `x-request-id` response header, and logs each completed request.
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestId::of(req);
+        req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let request_id = RequestId::of(req);
+        res.set_header(Header::new(REQUEST_ID_HEADER, request_id.0.clone()));
+
+        let elapsed_ms = match req.local_cache(|| RequestStart(None)) {
+            RequestStart(Some(start)) => start.elapsed().as_secs_f64() * 1000.0,
+            RequestStart(None) => 0.0,
+        };
+        let route = req.route().map(|route| route.uri.to_string());
+        let status = res.status().code;
+        tracing::info!(
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            route = route.as_deref().unwrap_or("unmatched"),
+            status,
+            elapsed_ms,
+            "request completed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RequestId, REQUEST_ID_HEADER};
+    use crate::web::test::init_test_client;
+    use rocket::http::Header;
+
+    #[test]
+    fn invalid_request_ids_are_replaced() {
+        assert_eq!(RequestId::new(Some("abc-123_X")).as_str(), "abc-123_X");
+        assert_ne!(RequestId::new(Some("bad id\n")).as_str(), "bad id\n");
+        assert_ne!(
+            RequestId::new(Some(&"a".repeat(65))).as_str(),
+            "a".repeat(65)
+        );
+        assert!(!RequestId::new(Some("")).as_str().is_empty());
+        assert!(!RequestId::new(None).as_str().is_empty());
+    }
+
+    #[test]
+    fn responses_carry_request_id() {
+        let (_rt, client) = init_test_client();
+
+        let res = client
+            .get("/healthz")
+            .header(Header::new(REQUEST_ID_HEADER, "from-client"))
+            .dispatch();
+        assert_eq!(
+            res.headers().get_one(REQUEST_ID_HEADER),
+            Some("from-client")
+        );
+
+        let res = client.get("/healthz").dispatch();
+        let generated = res.headers().get_one(REQUEST_ID_HEADER).unwrap_or_default();
+        assert_eq!(generated.len(), 36);
+    }
+}